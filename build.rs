@@ -0,0 +1,179 @@
+// Turns the vanilla data reports (`java -DbundlerMainClass=net.minecraft.data.Main -jar server.jar --reports`)
+// into tables we can index at runtime, so the server doesn't parse json on startup.
+use std::fmt::Write;
+use serde_json::Value;
+
+fn read(path: &str) -> Value {
+    println!("cargo:rerun-if-changed={path}");
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+/// Entries of a registry in `registries.json`, ordered by protocol id
+fn registry(registries: &Value, name: &str) -> Vec<String> {
+    let mut entries: Vec<_> = registries[name]["entries"].as_object().unwrap().iter()
+        .map(|(name, entry)| (entry["protocol_id"].as_u64().unwrap(), name.clone()))
+        .collect();
+    entries.sort();
+    assert!(entries.iter().enumerate().all(|(i, (id, _))| i as u64 == *id), "{name} has gaps in its ids");
+    entries.into_iter().map(|(_, name)| name).collect()
+}
+/// `minecraft:inner_left` -> `InnerLeft`
+fn variant(name: &str) -> String {
+    name.trim_start_matches("minecraft:").split('_').flat_map(|word| {
+        let mut chars = word.chars();
+        chars.next().map(|c| c.to_ascii_uppercase()).into_iter().chain(chars)
+    }).collect()
+}
+/// `minecraft:stone_stairs` -> `STONE_STAIRS`
+fn constant(name: &str) -> String {
+    name.trim_start_matches("minecraft:").to_ascii_uppercase()
+}
+fn strings(values: &Value) -> Vec<&str> {
+    values.as_array().unwrap().iter().map(|v| v.as_str().unwrap()).collect()
+}
+
+fn main() {
+    let registries = read("data/reports/registries.json");
+    let blocks = read("data/reports/blocks.json");
+    let properties = read("data/block_properties.json");
+    let item_properties = read("data/item_properties.json");
+    let shapes = read("data/block_shapes.json");
+
+    let items = registry(&registries, "minecraft:item");
+    let block_names = registry(&registries, "minecraft:block");
+    let item_id = |name: &str| items.iter().position(|i| i == name).unwrap_or_else(|| panic!("no item {name}"));
+    let default_state = |name: &str| blocks[name]["states"].as_array().unwrap().iter()
+        .find(|s| s["default"].as_bool() == Some(true))
+        .unwrap_or_else(|| panic!("{name} has no default state"))["id"].as_u64().unwrap();
+
+    let mut out = String::new();
+    writeln!(out, "pub(crate) static ITEM_NAMES: [&str; {}] = {:?};", items.len(), items).unwrap();
+    let max_stacks: Vec<_> = items.iter().map(|i| item_properties[i]["max_stack"].as_u64().unwrap_or(64)).collect();
+    writeln!(out, "pub(crate) static ITEM_MAX_STACK: [u8; {}] = {:?};", items.len(), max_stacks).unwrap();
+    // most block items are named after their block, the rest say so in item_properties.json
+    let item_blocks: Vec<_> = items.iter().map(|i| match item_properties[i].get("block") {
+        Some(Value::Null) => 0,
+        Some(block) => default_state(block.as_str().unwrap()),
+        None if i != "minecraft:air" && blocks.get(i).is_some() => default_state(i),
+        None => 0,
+    }).collect();
+    writeln!(out, "pub(crate) static ITEM_BLOCKS: [u16; {}] = {:?};", items.len(), item_blocks).unwrap();
+
+    // A property gets one type, no matter which blocks use it. `north` is a bool on fences
+    // but an enum on walls, so it gets an enum with `True` and `False` in it.
+    let mut all_properties: Vec<(&str, Vec<&str>)> = vec![];
+    for name in &block_names {
+        for (property, values) in blocks[name]["properties"].as_object().into_iter().flatten() {
+            let i = all_properties.iter().position(|(p, _)| p == property).unwrap_or_else(|| {
+                all_properties.push((property, vec![]));
+                all_properties.len() - 1
+            });
+            for value in strings(values) {
+                if !all_properties[i].1.contains(&value) {
+                    all_properties[i].1.push(value);
+                }
+            }
+        }
+    }
+    writeln!(out, "pub mod props {{").unwrap();
+    writeln!(out, "    use super::{{Property, NUMBERS}};").unwrap();
+    for (property, values) in &all_properties {
+        let ty = variant(property);
+        let (from_value, value) = if values.iter().all(|v| matches!(*v, "true" | "false")) {
+            writeln!(out, "    #[derive(Debug, Clone, Copy, PartialEq, Eq)]\n    pub struct {ty}(pub bool);").unwrap();
+            ("value.parse().ok().map(Self)".to_owned(), "if self.0 { \"true\" } else { \"false\" }".to_owned())
+        } else if values.iter().all(|v| v.parse::<u8>().is_ok()) {
+            writeln!(out, "    #[derive(Debug, Clone, Copy, PartialEq, Eq)]\n    pub struct {ty}(pub u8);").unwrap();
+            ("value.parse().ok().map(Self)".to_owned(), "NUMBERS[self.0 as usize]".to_owned())
+        } else {
+            let variants: Vec<_> = values.iter().map(|v| variant(v)).collect();
+            writeln!(out, "    #[derive(Debug, Clone, Copy, PartialEq, Eq)]\n    pub enum {ty} {{ {} }}", variants.join(", ")).unwrap();
+            let parse: String = values.iter().zip(&variants).map(|(v, var)| format!("{v:?} => Some(Self::{var}), ")).collect();
+            let print: String = values.iter().zip(&variants).map(|(v, var)| format!("Self::{var} => {v:?}, ")).collect();
+            (format!("match value {{ {parse}_ => None }}"), format!("match self {{ {print}}}"))
+        };
+        writeln!(out, "    impl Property for {ty} {{").unwrap();
+        writeln!(out, "        const NAME: &'static str = {property:?};").unwrap();
+        writeln!(out, "        fn from_value(value: &str) -> Option<Self> {{ {from_value} }}").unwrap();
+        writeln!(out, "        fn value(self) -> &'static str {{ {value} }}").unwrap();
+        writeln!(out, "    }}").unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\npub enum BlockKind {{").unwrap();
+    for name in &block_names {
+        writeln!(out, "    {},", variant(name)).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    writeln!(out, "impl Block {{").unwrap();
+    for name in &block_names[1..] {
+        writeln!(out, "    pub const {}: Self = block!({});", constant(name), default_state(name)).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    writeln!(out, "pub(crate) static BLOCKS: [BlockInfo; {}] = [", block_names.len()).unwrap();
+    for name in &block_names {
+        let states = blocks[name]["states"].as_array().unwrap();
+        let first_state = states.iter().map(|s| s["id"].as_u64().unwrap()).min().unwrap();
+        // serde_json sorts the properties by name, which is the order vanilla numbers states in
+        let block_properties: Vec<_> = blocks[name]["properties"].as_object().into_iter().flatten()
+            .map(|(property, values)| format!("({property:?}, &{:?})", strings(values)))
+            .collect();
+        let props = &properties[name];
+        writeln!(
+            out,
+            "    BlockInfo {{ name: {name:?}, kind: BlockKind::{}, first_state: {first_state}, default_state: {}, properties: &[{}], hardness: {:?}, tool: {}, needs: {}, drop: {}, light: {}, opacity: {} }},",
+            variant(name),
+            default_state(name),
+            block_properties.join(", "),
+            props["hardness"].as_f64().unwrap() as f32,
+            props["tool"].as_str().map_or("None".into(), |t| format!("Some(Tool::{})", variant(t))),
+            props["tier"].as_str().map_or("None".into(), |t| format!("Some(Tier::{})", variant(t))),
+            props["drops"].as_str().map_or("None".into(), |i| format!("Some({})", item_id(i))),
+            props["light"].as_u64().unwrap_or(0),
+            props["opacity"].as_u64().unwrap_or(0),
+        ).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    // collision boxes as [x1, y1, z1, x2, y2, z2] within the block
+    let boxes = shapes["shapes"].as_array().unwrap();
+    writeln!(out, "pub(crate) static SHAPES: [&[[f32; 6]]; {}] = [", boxes.len()).unwrap();
+    for shape in boxes {
+        let shape: Vec<Vec<f32>> = serde_json::from_value(shape.clone()).unwrap();
+        writeln!(out, "    &{shape:?},").unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    let mut state_shapes = vec![];
+    for name in &block_names {
+        let states = blocks[name]["states"].as_array().unwrap().len();
+        match &shapes["blocks"][name] {
+            Value::Array(ids) => state_shapes.extend(ids.iter().map(|i| i.as_u64().unwrap())),
+            id => state_shapes.extend(std::iter::repeat_n(id.as_u64().unwrap(), states)),
+        }
+    }
+    writeln!(out, "pub(crate) static STATE_SHAPES: [u16; {}] = {:?};", state_shapes.len(), state_shapes).unwrap();
+
+    // the registries the client is sent on join, as datapack json. They're sorted by name,
+    // so the ids don't depend on the filesystem
+    let mut datapack = vec![];
+    for kind in ["dimension_type", "worldgen/biome", "chat_type"] {
+        let dir = format!("data/minecraft/{kind}");
+        println!("cargo:rerun-if-changed={dir}");
+        let mut paths: Vec<_> = std::fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|e| e == "json"))
+            .collect();
+        paths.sort();
+        datapack.extend(paths.into_iter().map(|path| (kind, path)));
+    }
+    writeln!(out, "pub(crate) static BUILTIN_DATAPACK: [(&str, &str, &str); {}] = [", datapack.len()).unwrap();
+    for (kind, path) in &datapack {
+        let name = path.file_stem().unwrap().to_str().unwrap();
+        let path = std::fs::canonicalize(path).unwrap();
+        writeln!(out, "    ({kind:?}, \"minecraft:{name}\", include_str!({:?})),", path.to_str().unwrap()).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    std::fs::write(std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("registry.rs"), out).unwrap();
+}
@@ -14,6 +14,9 @@ impl<T> SlotMap<T> {
     pub fn get(&mut self, i: usize) -> Option<&mut T> {
         self.entries.get_mut(i).and_then(|r| r.as_mut().ok())
     }
+    pub fn get_ref(&self, i: usize) -> Option<&T> {
+        self.entries.get(i).and_then(|r| r.as_ref().ok())
+    }
     pub fn next_idx(&self) -> usize {
         if self.head == u32::MAX {
             self.entries.len()
@@ -34,6 +37,26 @@ impl<T> SlotMap<T> {
             id
         }
     }
+    /// Moves the entry at `i` to the lowest free index below `limit`, if it isn't there already.
+    /// Returns where it ends up
+    pub fn move_below(&mut self, i: usize, limit: usize) -> Option<usize> {
+        self.entries.get(i)?.as_ref().ok()?;
+        if i < limit {
+            return Some(i);
+        }
+        let free = (0..limit.min(self.entries.len())).find(|&j| self.entries[j].is_err())?;
+        // unlink it from the free list
+        let next = *self.entries[free].as_ref().map(|_| ()).expect_err("corrupted slotmap");
+        if self.head == free as u32 {
+            self.head = next;
+        } else {
+            let before = self.entries.iter_mut().find(|e| matches!(e, Err(n) if *n == free as u32)).expect("corrupted slotmap");
+            *before = Err(next);
+        }
+        let value = self.release(i).unwrap();
+        self.entries[free] = Ok(value);
+        Some(free)
+    }
     pub fn release(&mut self, i: usize) -> Option<T> {
         self.entries.get_mut(i).and_then(|r| {
             match core::mem::replace(r, Err(self.head)) {
@@ -64,6 +87,9 @@ impl<'a, T> Iterator for IterMut<'a, T> {
     }
 }
 impl<T> SlotMap<T> {
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> + '_ {
+        self.entries.iter().enumerate().filter_map(|(i, v)| Some((i, v.as_ref().ok()?)))
+    }
     pub fn iter_mut(&mut self) -> IterMut<'_, T> {
         IterMut { entries: self.entries.iter_mut().enumerate() }
     }
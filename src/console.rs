@@ -0,0 +1,129 @@
+//! Commands from whoever's running the server, typed into stdin or sent over RCON.
+//!
+//! Both are read on their own threads and handed to the main loop, which runs them between
+//! ticks and sends back what they printed.
+use std::io::{self, BufRead, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+
+/// Where vanilla listens for RCON
+pub const RCON_PORT: u16 = 25575;
+/// The biggest RCON packet vanilla will take
+const MAX_RCON_PACKET: usize = 1460;
+
+pub struct Command {
+    pub line: String,
+    /// Where the answer goes. Stdin's answers are just printed
+    reply: Option<mpsc::Sender<String>>,
+}
+impl Command {
+    pub fn reply(self, output: String) {
+        match self.reply {
+            Some(reply) => {
+                let _ = reply.send(output);
+            }
+            None => println!("{output}"),
+        }
+    }
+}
+
+pub struct Console {
+    commands: mpsc::Receiver<Command>,
+}
+impl Console {
+    /// Starts reading stdin, and listening for RCON if there's a password for it
+    pub fn spawn(rcon_password: Option<String>) -> io::Result<Self> {
+        let (send, commands) = mpsc::channel();
+        let stdin = send.clone();
+        std::thread::Builder::new().name("console".to_owned()).spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(e) => {
+                        log::error!("couldn't read the console: {e}");
+                        return;
+                    }
+                };
+                if !line.trim().is_empty() && stdin.send(Command { line, reply: None }).is_err() {
+                    return;
+                }
+            }
+        })?;
+        if let Some(password) = rcon_password {
+            let listener = TcpListener::bind(("0.0.0.0", RCON_PORT))?;
+            std::thread::Builder::new().name("rcon".to_owned()).spawn(move || {
+                for conn in listener.incoming() {
+                    let conn = match conn {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            log::warn!("couldn't accept an rcon connection: {e}");
+                            continue;
+                        }
+                    };
+                    let (password, send) = (password.clone(), send.clone());
+                    let _ = std::thread::Builder::new().name("rcon client".to_owned()).spawn(move || {
+                        if let Err(e) = rcon(conn, &password, send) {
+                            log::debug!("rcon connection closed: {e}");
+                        }
+                    });
+                }
+            })?;
+        }
+        Ok(Self { commands })
+    }
+    /// Commands that have come in since we last asked
+    pub fn commands(&self) -> impl Iterator<Item = Command> + '_ {
+        self.commands.try_iter()
+    }
+}
+
+/// Reads an RCON packet: its request id, type and body
+fn read_packet(conn: &mut TcpStream) -> io::Result<(i32, i32, String)> {
+    let mut len = [0; 4];
+    conn.read_exact(&mut len)?;
+    let len = i32::from_le_bytes(len) as usize;
+    if !(10..=MAX_RCON_PACKET).contains(&len) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("rcon packet {len} bytes long")));
+    }
+    let mut packet = vec![0; len];
+    conn.read_exact(&mut packet)?;
+    let id = i32::from_le_bytes(packet[0..4].try_into().unwrap());
+    let kind = i32::from_le_bytes(packet[4..8].try_into().unwrap());
+    // the body ends with two nuls
+    let body = String::from_utf8_lossy(&packet[8..len - 2]).into_owned();
+    Ok((id, kind, body))
+}
+fn write_packet(conn: &mut TcpStream, id: i32, kind: i32, body: &str) -> io::Result<()> {
+    let mut packet = vec![];
+    packet.extend((body.len() as i32 + 10).to_le_bytes());
+    packet.extend(id.to_le_bytes());
+    packet.extend(kind.to_le_bytes());
+    packet.extend(body.as_bytes());
+    packet.extend([0, 0]);
+    conn.write_all(&packet)
+}
+fn rcon(mut conn: TcpStream, password: &str, commands: mpsc::Sender<Command>) -> io::Result<()> {
+    const RESPONSE: i32 = 0;
+    const COMMAND: i32 = 2;
+    const LOGIN: i32 = 3;
+    let mut logged_in = false;
+    loop {
+        let (id, kind, body) = read_packet(&mut conn)?;
+        match kind {
+            LOGIN if body == password => {
+                logged_in = true;
+                write_packet(&mut conn, id, COMMAND, "")?;
+            }
+            LOGIN => write_packet(&mut conn, -1, COMMAND, "")?,
+            COMMAND if logged_in => {
+                let (reply, answer) = mpsc::channel();
+                if commands.send(Command { line: body, reply: Some(reply) }).is_err() {
+                    return Ok(());
+                }
+                let output = answer.recv().unwrap_or_default();
+                write_packet(&mut conn, id, RESPONSE, &output)?;
+            }
+            _ => write_packet(&mut conn, -1, RESPONSE, "")?,
+        }
+    }
+}
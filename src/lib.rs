@@ -1,7 +1,9 @@
 mod collections;
 pub mod types;
+pub mod registry;
 pub mod world;
 pub mod network;
+pub mod console;
 
 pub use world::World;
 pub use network::Network;
@@ -9,6 +11,7 @@ pub use network::Network;
 mod prelude {
     pub(crate) use crate::collections::*;
     pub(crate) use crate::types::*;
+    pub(crate) use crate::registry::*;
     pub(crate) use crate::network::{Response, Inboxes, client::Inbox};
     pub(crate) use std::{io, time};
     pub(crate) use std::net::TcpStream;
@@ -30,18 +30,94 @@ impl Announcer {
 
 fn main() -> std::io::Result<()> {
     env_logger::init();
-    let mut args = std::env::args_os();
-        
-    // let mut level = if let Some(path) = args.nth(1) {
-    //     mcserv::world::Level::from_path(path)?
-    // } else {
-    //     mcserv::world::Level::empty()
-    // };
-    let mut world = mcserv::World::new();
+    // mcserv [world] [--template <world>] [--seed <seed>] [--flat <layers>]
+    //        [--max-memory <MiB>] [--view-distance <min>..<max>] [--max-players <min>..<max>] [--prefer players|view-distance]
+    //        [--max-queue <n>] [--priority-slots <n>] [--rcon-password <password>]
+    //        [--backup-dir <dir>] [--backup-every <minutes>] [--keep-backups <n>] [--backup-max-age <hours>] [--restore <backup>]
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg);
+    let mut path = None;
+    let mut template = None;
+    let mut seed = None;
+    let mut layers = None;
+    let mut limits = mcserv::world::Limits::default();
+    let mut rcon_password = None;
+    let mut backups = mcserv::world::Backups::default();
+    let mut restore = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| invalid(format!("{arg} needs a value")));
+        match arg.as_str() {
+            "--template" => template = Some(value()?),
+            "--seed" => seed = Some(value()?),
+            "--flat" => layers = Some(value()?),
+            "--max-memory" => {
+                let mib: usize = value()?.parse().map_err(|_| invalid("--max-memory is in MiB".to_owned()))?;
+                limits.max_memory = mib.saturating_mul(1024 * 1024);
+            }
+            "--view-distance" => {
+                limits.view_distance = mcserv::world::Limits::parse_range(&value()?, limits.view_distance.clone())
+                    .ok_or_else(|| invalid("--view-distance takes a range like 7.., between 2 and 32".to_owned()))?;
+            }
+            "--max-players" => {
+                limits.max_players = mcserv::world::Limits::parse_range(&value()?, limits.max_players.clone())
+                    .ok_or_else(|| invalid("--max-players takes a range like 8.., between 1 and 32".to_owned()))?;
+            }
+            "--prefer" => limits.prefer = match value()?.as_str() {
+                "players" => mcserv::world::Prefer::Players,
+                "view-distance" => mcserv::world::Prefer::ViewDistance,
+                other => return Err(invalid(format!("can't prefer {other:?}"))),
+            },
+            "--max-queue" => limits.max_queue = value()?.parse().map_err(|_| invalid("--max-queue takes a number".to_owned()))?,
+            "--priority-slots" => limits.priority_slots = value()?.parse().map_err(|_| invalid("--priority-slots takes a number".to_owned()))?,
+            "--rcon-password" => rcon_password = Some(value()?),
+            "--backup-dir" => backups.dir = Some(value()?.into()),
+            "--backup-every" => backups.every = Some(value()?.parse().map_err(|_| invalid("--backup-every is in minutes".to_owned()))?),
+            "--keep-backups" => backups.keep = value()?.parse().map_err(|_| invalid("--keep-backups takes a number".to_owned()))?,
+            "--backup-max-age" => {
+                let hours: u64 = value()?.parse().map_err(|_| invalid("--backup-max-age is in hours".to_owned()))?;
+                backups.max_age = Some(Duration::from_secs(hours * 60 * 60));
+            }
+            "--restore" => restore = Some(value()?),
+            _ => path = Some(arg),
+        }
+    }
+    limits.check().map_err(invalid)?;
+    if let Some(backup) = restore {
+        let path = path.as_ref().ok_or_else(|| invalid("--restore needs a world to restore into".to_owned()))?;
+        mcserv::world::restore(backup.as_ref(), path.as_ref())?;
+    }
+    if backups.dir.is_none() {
+        // next to the world, so they don't get copied along with it
+        backups.dir = path.as_ref().map(|path| format!("{}-backups", path.trim_end_matches('/')).into());
+    }
+    let mut level = if let Some(path) = path {
+        mcserv::world::Level::from_path(path)?
+    } else {
+        mcserv::world::Level::empty()
+    };
+    if let Some(template) = template {
+        level.set_template(template)?;
+    }
+    use mcserv::world::generator::{self, Terrain};
+    if let Some(seed) = seed {
+        let seed = level.set_seed(generator::parse_seed(&seed));
+        for (dimension, terrain) in [("minecraft:overworld", Terrain::Overworld), ("minecraft:the_nether", Terrain::Nether), ("minecraft:the_end", Terrain::End)] {
+            let noise = generator::Noise::new(seed, terrain, level.codec());
+            level.set_generator(dimension, std::sync::Arc::new(noise));
+        }
+    }
+    if let Some(layers) = layers {
+        let flat = generator::Superflat::parse(&layers, level.codec()).map_err(invalid)?;
+        level.set_generator("minecraft:overworld", std::sync::Arc::new(flat));
+    }
     let mut network = mcserv::Network::new()?;
+    let mut world = mcserv::World::new(level, network.notifier());
+    world.set_limits(limits);
+    world.set_backups(backups);
     
     let starttime = Instant::now();
     let mut announcer = Announcer::new()?;
+    let console = mcserv::console::Console::spawn(rcon_password)?;
     loop {
         announcer.announce();
 
@@ -50,5 +126,23 @@ fn main() -> std::io::Result<()> {
 
         let time_passed_in_ticks = (Instant::now() - starttime).as_millis() / 50;
         world.tick_until(time_passed_in_ticks as u32, network.inboxes());
+
+        for command in console.commands() {
+            if command.line.trim() == "stop" {
+                command.reply("stopping".to_owned());
+                world.shutdown(network.inboxes(), "The server was stopped");
+                network.flush(&world, Duration::from_secs(2));
+                return Ok(());
+            }
+            let output = world.console_command(network.inboxes(), &command.line);
+            command.reply(output);
+        }
+
+        if let Some(reason) = world.shutdown_reason() {
+            let reason = reason.to_owned();
+            world.shutdown(network.inboxes(), &reason);
+            network.flush(&world, Duration::from_secs(2));
+            return Err(std::io::Error::new(std::io::ErrorKind::OutOfMemory, reason));
+        }
     }
 }
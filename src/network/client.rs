@@ -7,6 +7,8 @@ pub(super) enum State {
     Handshaking,
     Status,
     Login,
+    /// Waiting in limbo for a slot
+    Queued,
     Play,
 }
 #[derive(Debug)]
@@ -16,6 +18,29 @@ impl Inbox<'_> {
         self.0.queue.push_back(response);
         self.0.waiting_for_write = true;
     }
+    /// Drops responses that are still queued, if `cancelled` says so
+    pub fn cancel(&mut self, mut cancelled: impl FnMut(&Response) -> bool) {
+        self.0.queue.retain(|response| !cancelled(response));
+    }
+    /// What hasn't gone out to them yet, oldest first
+    pub fn queued(&self) -> impl Iterator<Item = &Response> + '_ {
+        self.0.queue.iter()
+    }
+    /// Whether their connection has stopped taking what we send, leaving some half sent.
+    /// They can do that on purpose just by not reading
+    pub fn backed_up(&self) -> bool {
+        self.0.pending_byte_cursor < self.0.last_pending_byte
+    }
+    /// Hangs up on them once everything that's queued has been sent
+    pub fn close(&mut self) {
+        self.0.closing = true;
+        self.0.waiting_for_write = true;
+    }
+    /// Lets a queued player into the world
+    pub fn start_playing(&mut self) {
+        self.0.state = State::Play;
+        self.0.waiting_for_write = true;
+    }
     pub fn reborrow(&mut self) -> Inbox<'_> {
         Inbox(&mut self.0)
     }
@@ -28,12 +53,24 @@ pub struct Client {
     pending_bytes: Vec<u8>,
     pending_byte_cursor: usize,
     last_pending_byte: usize,
+    closing: bool,
 
     pub(super) waiting_for_write: bool,
 }
 impl Client {
     pub(super) fn is_playing(&self) -> bool {
-        matches!(self.state, State::Play)
+        matches!(self.state, State::Play) && !self.closing
+    }
+    pub(super) fn is_queued(&self) -> bool {
+        matches!(self.state, State::Queued) && !self.closing
+    }
+    /// What's waiting to be sent to them
+    pub(super) fn buffered_bytes(&self) -> usize {
+        self.pending_bytes.capacity() + self.queue.capacity() * std::mem::size_of::<Response>()
+    }
+    /// Whether everything queued for them has gone out
+    pub(super) fn is_flushed(&self) -> bool {
+        self.queue.is_empty() && self.pending_byte_cursor >= self.last_pending_byte
     }
     pub(super) fn conn(&self) -> &TcpStream {
         &self.conn
@@ -50,6 +87,7 @@ impl Client {
             pending_bytes: vec![],
             pending_byte_cursor: 0,
             last_pending_byte: 0,
+            closing: false,
             waiting_for_write: false,
         })
     }
@@ -93,14 +131,19 @@ impl Client {
                 }
             }
         }
+        if self.closing {
+            // they'll see the connection close, and so will we
+            let _ = self.conn.shutdown(std::net::Shutdown::Both);
+        }
     }
     fn continue_login(&mut self, pkt: &[u8]) -> Result<Option<Name>, Disconnection> {
         let (id, pkt) = super::wire::varint(pkt).ok_or(Disconnection::new())?;
         match id {
             0 => {
                 let mut name = [0xFF; 16];
+                // names end up in file paths, so only what vanilla allows
                 let given_name = super::wire::str(pkt)
-                    .filter(|s| core::str::from_utf8(s.0).is_ok())
+                    .filter(|s| !s.0.is_empty() && s.0.iter().all(|&c| c.is_ascii_alphanumeric() || c == b'_'))
                     .ok_or(Disconnection::new())?.0;
                 name.get_mut(..given_name.len()).ok_or(Disconnection::new())?.copy_from_slice(given_name);
                 Ok(Some(Name::from_utf8(name)))
@@ -128,18 +171,32 @@ impl Client {
             let mut buf = &scratch[read..write];
             while let Some((pkt, rem)) = super::wire::str(buf) {
                 let client = network.clients.get(id).unwrap();
+                if client.closing {
+                    buf = &[];
+                    break;
+                }
                 buf = rem;
                 let result = match client.state {
                     State::Handshaking => super::new_connections::recv_handshaking(pkt),
                     State::Status => super::new_connections::recv_status(pkt, Inbox(client)).map(|_| State::Status),
                     State::Login => client.continue_login(pkt).map(|start_playing| {
-                        if let Some(name) = start_playing {
-                            world.login(id, name, Inbox(client));
-                            State::Play
-                        } else {
-                            State::Login
+                        let name = match start_playing {
+                            Some(name) => name,
+                            None => return State::Login,
+                        };
+                        match world.login(id, name, Inbox(client)) {
+                            Ok(crate::world::Admission::Playing) => State::Play,
+                            Ok(crate::world::Admission::Queued) => State::Queued,
+                            Err(reason) => {
+                                log::info!("turned {} away: {reason}", name.as_str());
+                                let mut inbox = Inbox(client);
+                                inbox.submit(Response::LoginDisconnect(reason));
+                                inbox.close();
+                                State::Login
+                            }
                         }
                     }),
+                    State::Queued => world.queued_request(id, pkt).map(|()| State::Queued),
                     State::Play => world.play_request(id, super::Inboxes(network), pkt).map(|()| State::Play)
                 };
                 match result {
@@ -13,6 +13,10 @@ impl Inboxes<'_> {
     pub fn get(&mut self, idx: usize) -> Option<client::Inbox> {
         self.0.clients.get(idx).map(|c| c.inbox())
     }
+    /// What's waiting to be sent to everyone, and the buffers it's written into
+    pub fn buffered_bytes(&self) -> usize {
+        self.0.scratch_buffer.capacity() + self.0.clients.iter().map(|(_, c)| c.buffered_bytes()).sum::<usize>()
+    }
     pub fn reborrow(&mut self) -> Inboxes {
         Inboxes(self.0)
     }
@@ -20,6 +24,26 @@ impl Inboxes<'_> {
         self.0.clients.retain(|idx, client| {
             let was_waiting_for_write = core::mem::take(&mut client.waiting_for_write);
             if !client.is_playing() {
+                client.waiting_for_write |= was_waiting_for_write;
+                true
+            } else if keep(idx, client.inbox()) {
+                if client.waiting_for_write && !was_waiting_for_write {
+                    self.0.poller.modify(client.conn(), polling::Event::all(idx)).unwrap();
+                }
+                client.waiting_for_write |= was_waiting_for_write;
+                true
+            } else {
+                self.0.poller.delete(client.conn()).unwrap();
+                false
+            }
+        });
+    }
+    /// Like `retain`, but for the players waiting in the queue instead
+    pub fn queued(self, mut keep: impl FnMut(usize, client::Inbox) -> bool) {
+        self.0.clients.retain(|idx, client| {
+            let was_waiting_for_write = core::mem::take(&mut client.waiting_for_write);
+            if !client.is_queued() {
+                client.waiting_for_write |= was_waiting_for_write;
                 true
             } else if keep(idx, client.inbox()) {
                 if client.waiting_for_write && !was_waiting_for_write {
@@ -33,4 +57,14 @@ impl Inboxes<'_> {
             }
         });
     }
-}
\ No newline at end of file
+    /// Moves a client to a free index below `limit`, returning the one it's at now
+    pub fn renumber(&mut self, idx: usize, limit: usize) -> Option<usize> {
+        let new = self.0.clients.move_below(idx, limit)?;
+        if new != idx {
+            let client = self.0.clients.get(new).unwrap();
+            client.waiting_for_write = true;
+            self.0.poller.modify(client.conn(), polling::Event::all(new)).unwrap();
+        }
+        Some(new)
+    }
+}
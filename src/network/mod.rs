@@ -15,18 +15,32 @@ pub struct Network {
     pub(super) listener: std::net::TcpListener,
     pub(super) scratch_buffer: Vec<u8>,
     
-    pub(super) poller: polling::Poller,
+    pub(super) poller: std::sync::Arc<polling::Poller>,
     pub(super) events: Vec<polling::Event>,
     
     pub(super) clients: SlotMap<client::Client>,
 }
 
+/// Wakes the network up from another thread, so the world can pick up what's been done
+#[derive(Debug, Clone)]
+pub struct Notifier(std::sync::Arc<polling::Poller>);
+impl Notifier {
+    pub fn notify(&self) {
+        if let Err(e) = self.0.notify() {
+            log::error!("couldn't wake the network up: {e}");
+        }
+    }
+}
+
 const LISTENER: usize = usize::MAX - 1;
 impl Network {
     pub fn new() -> io::Result<Self> {
-        let poller = polling::Poller::new()?;
+        Self::listen("0.0.0.0:25565")
+    }
+    fn listen(addr: &str) -> io::Result<Self> {
+        let poller = std::sync::Arc::new(polling::Poller::new()?);
     
-        let listener = std::net::TcpListener::bind("0.0.0.0:25565")?;
+        let listener = std::net::TcpListener::bind(addr)?;
         listener.set_nonblocking(true)?;
         poller.add(&listener, polling::Event::readable(LISTENER))?;
         Ok(Self {
@@ -41,12 +55,19 @@ impl Network {
     }
 
 
+    pub fn notifier(&self) -> Notifier {
+        Notifier(self.poller.clone())
+    }
+
     pub fn process_packets_until(&mut self, deadline: time::Instant, world: &mut crate::World) {
         loop {
-            match self.poller.wait(&mut self.events, match deadline.checked_duration_since(time::Instant::now()) {
+            let woken = self.poller.wait(&mut self.events, match deadline.checked_duration_since(time::Instant::now()) {
                 Some(v) => Some(v),
                 None => return,
-            }) {
+            });
+            // the chunk loaders wake us when they're done
+            world.finish_loading(Inboxes(self));
+            match woken {
                 Ok(0) => return,
                 Ok(_) => {}
                 Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
@@ -68,6 +89,11 @@ impl Network {
                     if client::Client::read(self, world, event.key).is_err() {
                         let client = self.clients.get(event.key).expect("received message from dead client");
                         log::debug!("client disconnected {}", event.key);
+                        if client.is_playing() {
+                            world.logout(event.key);
+                        } else {
+                            world.leave_queue(event.key);
+                        }
                         self.poller.delete(client.conn()).unwrap();
                         self.clients.release(event.key);
                         continue;
@@ -85,6 +111,41 @@ impl Network {
             }
         }
     }
+    /// Sends whatever's still queued, for a little while, before the server stops
+    pub fn flush(&mut self, world: &crate::World, timeout: time::Duration) {
+        let deadline = time::Instant::now() + timeout;
+        while time::Instant::now() < deadline {
+            let mut flushed = true;
+            for (pid, client) in self.clients.iter_mut() {
+                client.write(world, pid, &mut self.scratch_buffer);
+                flushed &= client.is_flushed();
+            }
+            if flushed {
+                return;
+            }
+            std::thread::sleep(time::Duration::from_millis(10));
+        }
+    }
+    /// A network on a port of its own, for tests to run side by side
+    #[cfg(test)]
+    pub(crate) fn local() -> Self {
+        Self::listen("127.0.0.1:0").unwrap()
+    }
+    /// Connects someone called `name` and lets them into the world. Returns their pid, and
+    /// their end of the connection, which has to stay open
+    #[cfg(test)]
+    pub(crate) fn join(&mut self, world: &mut crate::World, name: &str) -> (usize, std::net::TcpStream) {
+        let conn = std::net::TcpStream::connect(self.listener.local_addr().unwrap()).unwrap();
+        self.listener.set_nonblocking(false).unwrap();
+        let (accepted, _) = self.listener.accept().unwrap();
+        let pid = self.clients.insert(client::Client::accept(accepted).unwrap());
+        let mut padded = [0xFF; 16];
+        padded[..name.len()].copy_from_slice(name.as_bytes());
+        let mut inbox = self.clients.get(pid).unwrap().inbox();
+        inbox.start_playing();
+        world.login(pid, Name::from_utf8(padded), inbox).unwrap();
+        (pid, conn)
+    }
     fn accept_players(&mut self) {
         loop {
             match self.listener.accept() {
@@ -5,19 +5,35 @@ fn decode<'a, T: wire::Wire<'a>>(pkt: &'a [u8]) -> Result<T, Disconnection> {
 }
 
 impl crate::World {
+    /// Queued players aren't in the world, so there's not much they can do
+    pub(super) fn queued_request(&mut self, pid: usize, pkt: &[u8]) -> Result<(), Disconnection> {
+        let (id, pkt) = wire::varint(pkt).ok_or(Disconnection::new())?;
+        match id {
+            0x07 => {
+                let (_locale, view_distance): (&[u8], u8) = decode(pkt)?;
+                self.request_queued_view_distance(pid, view_distance);
+            }
+            0x1f => self.acknowledge_queued_ping(pid),
+            _ => {}
+        }
+        Ok(())
+    }
     pub(super) fn play_request(&mut self, pid: usize, mut inboxes: Inboxes, pkt: &[u8]) -> Result<(), Disconnection> {
         let (id, pkt) = wire::varint(pkt).ok_or(Disconnection::new())?;
+        // they're not really anywhere while they're in the lobby, so they can only talk
+        if self.in_lobby(pid) && !matches!(id, 0x00 | 0x04 | 0x07 | 0x0c | 0x1f) {
+            return Ok(());
+        }
         match id {
-            0x00 => {} // confirm Position(()) packet. Maybe trusted clients wont move in the world until we get this?
+            0x00 => {
+                let var(id) = decode(pkt)?;
+                self.confirm_teleport(pid, id);
+            }
             0x03 => self.run_command(pid, inboxes, decode(pkt)?),
             0x04 => self.chat_message(inboxes, decode(pkt)?),
             0x06 => {
                 match decode(pkt)? {
-                    0u8 => { // do respawn plz
-                        inboxes.reborrow().get(pid).unwrap().submit(Response::Respawn());
-                        self.request_move(pid, inboxes.reborrow(), 0.0, 0.0, 0.0);
-                        inboxes.reborrow().get(pid).unwrap().submit(Response::Position());
-                    }
+                    0u8 => self.respawn(pid, inboxes), // do respawn plz
                     _ => todo!()
                 }
             }
@@ -31,18 +47,36 @@ impl crate::World {
             }
             0x0b => self.closed_inventory(pid, decode(pkt)?), // close container
             0x0c => log::trace!("message on plugin channel {:?}", String::from_utf8_lossy(decode(pkt)?)),
+            0x0f => {
+                let ((var(eid), var(kind)), rem): ((var<i32>, var<i32>), _) = Wire::decode(pkt)?;
+                // interacting at a spot on them says where, and everything but an attack says with which hand
+                let rem = if kind == 2 { <(f32, f32, f32)>::decode(rem)?.1 } else { rem };
+                let rem = if kind != 1 { Hand::decode(rem)?.1 } else { rem };
+                let _sneaking: bool = decode(rem)?;
+                match kind {
+                    // using something on them
+                    0 | 2 => {}
+                    1 => self.attack(pid, inboxes, eid),
+                    _ => return Err(Disconnection::new()),
+                }
+            }
             0x13 => {
                 let (x, feet_y, z, on_ground): (_, _, _, bool) = decode(pkt)?;
+                self.set_on_ground(pid, on_ground);
                 self.request_move(pid, inboxes, x, feet_y, z);
             }
             0x14 => {
                 let (x, feet_y, z, yaw, pitch, on_ground): (_, _, _, f32, f32, bool) = decode(pkt)?;
+                self.set_on_ground(pid, on_ground);
+                self.set_rotation(pid, yaw, pitch);
                 self.request_move(pid, inboxes, x, feet_y, z);
             }
             0x15 => {
                 let (yaw, pitch, on_ground): (f32, f32, bool) = decode(pkt)?;
+                self.set_on_ground(pid, on_ground);
+                self.set_rotation(pid, yaw, pitch);
             }
-            0x16 => log::info!("is on ground? {:?}", decode::<bool>(pkt)?),
+            0x16 => self.set_on_ground(pid, decode(pkt)?),
             0x1b => {
                 let flags: u8 = decode(pkt)?;
                 let _is_flying = flags & 2 != 0;
@@ -51,22 +85,29 @@ impl crate::World {
                 let (var(status), Position(pos), face, var(seq)) = decode(pkt)?;
                 let _: BlockFace = face;
                 match status {
-                    0 => { // started digging. instabreak in creative?
-                        self.break_at(inboxes.reborrow(), pos);
-                        inboxes.get(pid).unwrap().submit(Response::AckBlockChange(seq));
-                    }
-                    5 => todo!(), // todo
+                    0 => self.start_digging(pid, inboxes.reborrow(), pos),
+                    1 => self.cancel_digging(pid, inboxes.reborrow()),
+                    2 => self.finish_digging(pid, inboxes.reborrow(), pos),
+                    3 => self.drop_held(pid, inboxes.reborrow(), true),
+                    4 => self.drop_held(pid, inboxes.reborrow(), false),
+                    5 => self.stop_using_item(pid),
                     6 => todo!("swap item in hand"),
                     _ => todo!()
                 }
+                if status <= 2 {
+                    inboxes.get(pid).unwrap().submit(Response::AckBlockChange(seq));
+                }
             } // interacted with block
-            0x1d => {} // player command (crouching, running)
-            0x1f => self.acknowledge_ping(pid),
+            0x1d => {
+                let (var(_eid), var(action)): (var<i32>, var<i32>) = decode(pkt)?;
+                self.player_command(pid, action);
+            }
+            0x1f => self.acknowledge_ping(pid, decode(pkt)?),
             // 0x20 => {}
-            0x27 => self.set_held_item(pid, inboxes, decode::<i16>(pkt)? as u8),
+            0x27 => self.set_held_item(pid, inboxes, decode(pkt)?),
             0x2a => {
-                let (slot, item) = decode(pkt)?;
-                self.set_creative_slot(pid, inboxes, slot, item);
+                let (slot, stack) = decode(pkt)?;
+                self.set_creative_slot(pid, inboxes, slot, stack);
             }
             0x2E => {
                 let hand: Hand = decode(pkt)?;
@@ -75,11 +116,14 @@ impl crate::World {
             0x30 => {
                 let (hand, Position(position), face, pos_on_block, inblock, var(seq)) = decode(pkt)?;
                 let _: bool = inblock;
-                let _: V3<f32> = pos_on_block;
-                self.use_item_at_block(pid, inboxes.reborrow(), position, hand, face);
+                self.use_item_at_block(pid, inboxes.reborrow(), position, hand, face, pos_on_block);
+                inboxes.get(pid).unwrap().submit(Response::AckBlockChange(seq));
+            }
+            0x31 => {
+                let (hand, var(seq)) = decode(pkt)?;
+                self.use_item(pid, hand);
                 inboxes.get(pid).unwrap().submit(Response::AckBlockChange(seq));
             }
-            // 0x31 => {} // use item
             _ => todo!("unimpled play packet 0x{id:02x}")
         }
         Ok(())
@@ -29,32 +29,39 @@ response! {
     Pong(n: u64): 1 n
     Chat(msg: String): 0x5F (serde_json::to_string(&serde_json::json!({
         "text": msg
-    })).unwrap().into_bytes(), 0u8)
+    })).unwrap().into_bytes(), var(world.codec().chat_type_id("minecraft:system").unwrap_or(0)))
     Login(name: Name): 2 (name.0, name.as_str(), &[(); 0][..])
+    LoginDisconnect(reason: String): 0 serde_json::to_string(&serde_json::json!({
+        "text": reason
+    })).unwrap().into_bytes()
 
-    Play(mode: GameMode): 0x23 (
-        1i32, // eid
-        false, // is hardcore
+    Play(eid: i32, mode: GameMode, dimension: usize, death: Option<(usize, V3<i32>)>): 0x23 (
+        eid,
+        world.level_data().hardcore,
         match mode {
             GameMode::Survival => 0u8,
             GameMode::Creative => 1
         },
         -1i8, // no previous gamemode
 
-        &[0u8; 0][..], // dimensions
-        ToWireFn(registry_data),
+        world.dimensions().iter().map(|d| d.name.as_str()).collect::<Vec<_>>(),
+        ToWireFn(|pkt: &mut [u8]| {
+            let codec = world.codec().encoded();
+            pkt[..codec.len()].copy_from_slice(codec);
+            codec.len()
+        }),
 
-        "", // dimension type
-        "", // dimension name
-        0u64, // hashed seed
+        world.dimensions()[*dimension].kind.as_str(), // dimension type
+        world.dimensions()[*dimension].name.as_str(), // dimension name
+        world.level_data().hashed_seed(),
         2u8, // max players (ignored)
         8u8, //render distance
         8u8, // simulation distance
-        false, // reduced debuginfo
-        true, // respawn screen enabled
+        world.level_data().rule_enabled("reducedDebugInfo"),
+        !world.level_data().rule_enabled("doImmediateRespawn"), // respawn screen enabled
         false, // is debug world
-        true, // is superflat
-        None::<()>, // death location (disabled rn)
+        world.dimensions()[*dimension].generator.superflat(),
+        death.map(|(dimension, pos)| (world.dimensions()[dimension].name.as_str(), Position(pos))),
     )
     MoveFast(): 0x65 (
         1u8, // eid
@@ -62,21 +69,24 @@ response! {
            10.0f64,
            &[(); 0][..])][..]
     )
-    Ping(): 0x2D 35453423i32
+    Ping(id: i32): 0x2D id
+    Disconnect(reason: String): 0x17 serde_json::to_string(&serde_json::json!({
+        "text": reason
+    })).unwrap().into_bytes()
     AckBlockChange(seq: i32): 0x05 var(*seq)
     SetBlock(pos: V3<i32>, id: Option<Block>): 0x09 (
-        (pos.x as i64) << 38 | ((pos.z as i64 & 0x3FFFFFF) << 12) | (pos.y as i64 & 0xFFF),
+        Position(*pos),
         var(id.map_or(0, |b| b.net_id()) as u32)
     )
+    DestroyStage(eid: i32, pos: V3<i32>, stage: u8): 0x06 (var(*eid), Position(*pos), stage)
+    GameEvent(event: u8, value: f32): 0x1B (event, value)
     CenterChunk(x: i32, z: i32): 0x48 [var(*x), var(*z)]
     LoadChunk(x: i32, z: i32): 0x1F {
-        let un_skylit_chunks = 0b11_1111_1111_1111_1111_1101_1111u64;
-        let un_blocklit_chunks = 0b11_1111_1111_1111_1111_1111_1111u64;
         let mut chunkdata = Vec::with_capacity(10 * 1024);
-        let chunk = world.chunk_at(*x, *z);
+        let chunk = world.chunk_at(pid, *x, *z);
         match &chunk.content {
-            crate::world::ChunkContent::OneToOne { nonaircounts, blocks } => {
-                for (nonaircount, blocks) in nonaircounts.iter().zip(blocks.chunks(16 * 16 * 16 / 4)) {
+            crate::world::ChunkContent::OneToOne { nonaircounts, blocks, biomes, .. } => {
+                for ((nonaircount, blocks), biomes) in nonaircounts.iter().zip(blocks.chunks(16 * 16 * 16 / 4)).zip(biomes.chunks(64)) {
                     chunkdata.extend(nonaircount.to_be_bytes());
                     if *nonaircount == 0 {
                         chunkdata.push(0);
@@ -92,7 +102,7 @@ response! {
                             chunkdata.extend(blocks.to_be_bytes());
                         }
                     }
-                    chunkdata.extend([0, 0, 0]); // no biome data
+                    biome_container(&mut chunkdata, biomes, world.codec().biomes.len());
                 }
             }
         }
@@ -101,8 +111,10 @@ response! {
             x, z,
             ToWireFn(|mut dst: &mut [u8]| {
                 let len = dst.len();
+                use crate::world::Heightmap;
                 fastnbt::to_writer(&mut dst, &fastnbt::nbt!({
-                    "MOTION_BLOCKING": fastnbt::LongArray::new((0..((256 / (64 / 9)) + 1)).map(|_| 0).collect())
+                    "MOTION_BLOCKING": fastnbt::LongArray::new(chunk.heightmaps.pack(Heightmap::MotionBlocking)),
+                    "WORLD_SURFACE": fastnbt::LongArray::new(chunk.heightmaps.pack(Heightmap::WorldSurface)),
                 })).unwrap();
                 len - dst.len()
             }),
@@ -114,39 +126,98 @@ response! {
             
             &[(); 0][..], // no tile entities
 
-            1u8, // trust server lighting
-
-            [&[!un_skylit_chunks][..], &[!un_blocklit_chunks]],
-            [&[un_skylit_chunks][..], &[un_blocklit_chunks]],
-            [&[&[0xFFu8; 2048][..]][..], &[][..]]
+            light(&chunk.light, &chunk.light.all()),
         )
     }
+    UpdateLight(x: i32, z: i32, sections: crate::world::SectionSet): 0x22 (
+        var(*x), var(*z),
+        light(&world.chunk_at(pid, *x, *z).light, sections),
+    )
     UnloadChunk(x: i32, z: i32): 0x1A (x, z)
-    Position(): 0x36 (
-        world.player_pos(pid),
+    Position(pos: (f64, f64, f64), id: i32): 0x36 (
+        *pos,
         (0.0f32, 0.0f32),
         0b000_00000u8, // positions are relative?
-        0u8, // teleport id
+        var(*id), // teleport id
         false, // should dismount?
     )
-    SetInventorySlot(slot: u16, id: Item, count: u8, seq: u32): 0x13 (
+    SetInventorySlot(slot: u16, stack: Option<ItemStack>, seq: u32): 0x13 (
         0u8,
         var(*seq),
         slot,
-        Some((id, count, 0u8))
+        stack
     )
     SetRenderDistance(distance: u8): 0x49 distance
+    SpawnPosition(pos: V3<i32>, angle: f32): 0x4A (Position(*pos), angle)
+    Difficulty(difficulty: u8, locked: bool): 0x0B (difficulty, locked)
+    SpawnItem(eid: i32, pos: (f64, f64, f64), velocity: (f64, f64, f64)): 0x00 (
+        var(*eid),
+        (0u64, *eid as u64), // uuid
+        var(44), // item
+        pos,
+        [0u8; 3], // pitch, yaw, head yaw
+        var(0),
+        velocity_units(*velocity),
+    )
+    EntityVelocity(eid: i32, velocity: (f64, f64, f64)): 0x4F (var(*eid), velocity_units(*velocity))
+    ItemMetadata(eid: i32, stack: ItemStack): 0x4D (
+        var(*eid),
+        8u8, // item
+        var(6), // slot
+        Some(stack),
+        0xFFu8,
+    )
+    SpawnPlayer(eid: i32, name: Name, pos: (f64, f64, f64), yaw: f32, pitch: f32): 0x02 (
+        var(*eid),
+        name.0, // uuid
+        pos,
+        angle(*yaw),
+        angle(*pitch),
+    )
+    AddPlayerInfo(name: Name, mode: GameMode): 0x34 (
+        var(0), // add
+        var(1),
+        name.0, // uuid
+        name.as_str(),
+        &[(); 0][..], // no properties
+        var(match mode {
+            GameMode::Survival => 0,
+            GameMode::Creative => 1
+        }),
+        var(0), // ping
+        None::<()>, // display name
+        None::<()>, // signature
+    )
+    RemovePlayerInfo(name: Name): 0x34 (var(4), var(1), name.0)
+    TeleportEntity(eid: i32, pos: (f64, f64, f64), yaw: f32, pitch: f32, on_ground: bool): 0x63 (
+        var(*eid),
+        pos,
+        angle(*yaw),
+        angle(*pitch),
+        on_ground,
+    )
+    HeadRotation(eid: i32, yaw: f32): 0x3C (var(*eid), angle(*yaw))
+    EntityEvent(eid: i32, status: u8): 0x18 (eid, status)
+    PickupItem(eid: i32, collector: i32, count: u8): 0x62 (var(*eid), var(*collector), var(*count as i32))
+    RemoveEntities(eids: Vec<i32>): 0x38 eids.iter().map(|eid| var(*eid)).collect::<Vec<_>>()
+    UpdateTime(age: i64, time_of_day: i64): 0x59 (age, time_of_day)
     SetHealth(health: f32, food: i32, saturation: f32): 0x52 (health, var(*food), saturation)
-    Respawn(): 0x3B (
-        &b""[..],
-        &b""[..],
-        0u64,
-        1u8,
-        -1i8,
-        false,
-        true,
-        true,
-        None::<()>,
+    CombatDeath(eid: i32, killer: i32, msg: String): 0x33 (var(*eid), killer, serde_json::to_string(&serde_json::json!({
+        "text": msg
+    })).unwrap().into_bytes())
+    Respawn(mode: GameMode, dimension: usize, death: Option<(usize, V3<i32>)>): 0x3B (
+        world.dimensions()[*dimension].kind.as_str(), // dimension type
+        world.dimensions()[*dimension].name.as_str(), // dimension name
+        world.level_data().hashed_seed(),
+        match mode {
+            GameMode::Survival => 0u8,
+            GameMode::Creative => 1
+        },
+        -1i8, // no previous gamemode
+        false, // is debug world
+        world.dimensions()[*dimension].generator.superflat(),
+        true, // keep metadata
+        death.map(|(dimension, pos)| (world.dimensions()[dimension].name.as_str(), Position(pos))),
     )
 }
 
@@ -159,261 +230,70 @@ macro_rules! snbt {
         )
     }
 }
-fn registry_data(mut pkt: &mut [u8]) -> usize {
-    let len = pkt.len();
-    log::warn!("using a very odd version of the world NBT data");
-    fastnbt::to_writer(&mut pkt, &fastnbt::nbt! ({
-        // chat is not culled
-        "minecraft:chat_type": {
-            "value": [
-            {
-                "name": "minecraft:chat",
-                "id": 0,
-                "element": {
-                "chat": {
-                    "decoration": {
-                        "parameters": [
-                            "sender",
-                            "content"
-                        ],
-                        "translation_key": "chat.type.text",
-                        "style": {}
-                    }
-                },
-                "narration": {
-                    "decoration": {
-                    "parameters": [
-                        "sender",
-                        "content"
-                    ],
-                    "translation_key": "chat.type.text.narrate",
-                    "style": {}
-                    },
-                    "priority": "chat"
-                }
-                }
-            },
-            {
-                "element": {
-                "narration": {
-                    "priority": "system"
-                },
-                "chat": {}
-                },
-                "name": "minecraft:system",
-                "id": 1
-            },
-            {
-                "id": 2,
-                "name": "minecraft:game_info",
-                "element": {
-                "overlay": {}
-                }
-            },
-            {
-                "element": {
-                "narration": {
-                    "priority": "chat",
-                    "decoration": {
-                    "style": {},
-                    "parameters": [
-                        "sender",
-                        "content"
-                    ],
-                    "translation_key": "chat.type.text.narrate"
-                    }
-                },
-                "chat": {
-                    "decoration": {
-                    "style": {},
-                    "parameters": [
-                        "sender",
-                        "content"
-                    ],
-                    "translation_key": "chat.type.announcement"
-                    }
-                }
-                },
-                "id": 3,
-                "name": "minecraft:say_command"
-            },
-            {
-                "id": 4,
-                "element": {
-                "chat": {
-                    "decoration": {
-                    "translation_key": "commands.message.display.incoming",
-                    "style": {
-                        "italic": 1,
-                        "color": "gray"
-                    },
-                    "parameters": [
-                        "sender",
-                        "content"
-                    ]
-                    }
-                },
-                "narration": {
-                    "priority": "chat",
-                    "decoration": {
-                    "translation_key": "chat.type.text.narrate",
-                    "style": {},
-                    "parameters": [
-                        "sender",
-                        "content"
-                    ]
-                    }
-                }
-                },
-                "name": "minecraft:msg_command"
-            },
-            {
-                "name": "minecraft:team_msg_command",
-                "id": 5,
-                "element": {
-                "narration": {
-                    "priority": "chat",
-                    "decoration": {
-                    "style": {},
-                    "parameters": [
-                        "sender",
-                        "content"
-                    ],
-                    "translation_key": "chat.type.text.narrate"
-                    }
-                },
-                "chat": {
-                    "decoration": {
-                    "translation_key": "chat.type.team.text",
-                    "style": {},
-                    "parameters": [
-                        "team_name",
-                        "sender",
-                        "content"
-                    ]
-                    }
-                }
-                }
-            },
-            {
-                "id": 6,
-                "element": {
-                "chat": {
-                    "decoration": {
-                    "translation_key": "chat.type.emote",
-                    "style": {},
-                    "parameters": [
-                        "sender",
-                        "content"
-                    ]
-                    }
-                },
-                "narration": {
-                    "priority": "chat",
-                    "decoration": {
-                    "style": {},
-                    "translation_key": "chat.type.emote",
-                    "parameters": [
-                        "sender",
-                        "content"
-                    ]
-                    }
-                }
-                },
-                "name": "minecraft:emote_command"
-            },
-            {
-                "element": {
-                "chat": {},
-                "narration": {
-                    "priority": "chat"
-                }
-                },
-                "id": 7,
-                "name": "minecraft:tellraw_command"
-            }
-            ],
-            "type": "minecraft:chat_type"
-        },
-        "minecraft:dimension_type": {
-            "type": "minecraft:dimension_type",
-            "value": [
-            {
-                "id": 0,
-                "name": "",
-                "element": {
-                "ultrawarm": 0,
-                "logical_height": 384,
-                "infiniburn": "#minecraft:infiniburn_overworld",
-                "piglin_safe": 0,
-                "ambient_light": 0.0,
-                "has_skylight": 1,
-                "effects": "",
-                "has_raids": 0,
-                "monster_spawn_block_light_limit": 0,
-                "respawn_anchor_works": 0,
-                "height": 384,
-                "has_ceiling": 0,
-                "monster_spawn_light_level": {
-                    "value": {
-                    "max_inclusive": 7,
-                    "min_inclusive": 0
-                    },
-                    "type": "minecraft:uniform"
-                },
-                "natural": 0,
-                "min_y": -64,
-                "coordinate_scale": 1.0,
-                "bed_works": 0
-                }
-            }
-            ]
-        },
-        "minecraft:worldgen/biome": {
-            "type": "minecraft:worldgen/biome",
-            "value": [
-            {
-                "id": 0,
-                "element": {
-                "precipitation": "none",
-                "temperature": 0.5,
-                "downfall": 0.5,
-                "effects": {
-                    "water_color": 4159204,
-                    "mood_sound": {
-                    "sound": "minecraft:ambient.cave",
-                    "offset": 2.0,
-                    "block_search_extent": 8,
-                    "tick_delay": 6000
-                    },
-                    "water_fog_color": 329011,
-                    "fog_color": 12638463,
-                    "sky_color": 8103167
-                }
-                },
-                "name": "minecraft:the_void"
-            },
-            {
-                "id": 1,
-                "name": "minecraft:plains",
-                "element": {
-                "temperature": 0.8,
-                "precipitation": "none",
-                "downfall": 0.0,
-                "effects": {
-                    "water_fog_color": 329011,
-                    "fog_color": 12638463,
-                    "water_color": 4159204,
-                    "sky_color": 7907327
-                }
-                }
-            }
-            ]
+/// Velocities go over the wire in 8000ths of a block a tick, and can't be much over 4
+fn velocity_units((x, y, z): (f64, f64, f64)) -> [i16; 3] {
+    [x, y, z].map(|v| (v.clamp(-3.9, 3.9) * 8000.0) as i16)
+}
+/// Rotations go over the wire in 256ths of a turn
+fn angle(degrees: f32) -> u8 {
+    (degrees / 360.0 * 256.0) as i32 as u8
+}
+/// A section's biomes, with as small a palette as the client will take
+fn biome_container(chunkdata: &mut Vec<u8>, biomes: &[u16], registry_len: usize) {
+    let push_var = |chunkdata: &mut Vec<u8>, n: usize| {
+        let n = var(n as u32);
+        let len = chunkdata.len();
+        chunkdata.resize(len + n.byte_len(), 0);
+        n.encode(&mut chunkdata[len..]);
+    };
+    let mut palette = vec![];
+    for biome in biomes {
+        if !palette.contains(biome) {
+            palette.push(*biome);
+        }
+    }
+    if palette.len() == 1 {
+        chunkdata.push(0);
+        push_var(chunkdata, palette[0] as usize);
+        push_var(chunkdata, 0);
+        return;
+    }
+    let bits_for = |n: usize| usize::BITS - (n - 1).leading_zeros();
+    // more than 3 bits and the client wants ids from the whole registry
+    let (bits, indices): (_, Vec<_>) = if bits_for(palette.len()) <= 3 {
+        chunkdata.push(bits_for(palette.len()) as u8);
+        push_var(chunkdata, palette.len());
+        for biome in &palette {
+            push_var(chunkdata, *biome as usize);
         }
-    })).unwrap();
-    len - pkt.len()
+        (bits_for(palette.len()), biomes.iter().map(|b| palette.iter().position(|p| p == b).unwrap() as u64).collect())
+    } else {
+        let bits = bits_for(registry_len);
+        chunkdata.push(bits as u8);
+        (bits, biomes.iter().map(|b| *b as u64).collect())
+    };
+    let per_long = 64 / bits as usize;
+    let longs: Vec<u64> = indices.chunks(per_long)
+        .map(|c| c.iter().enumerate().fold(0, |long, (i, index)| long | index << (i * bits as usize)))
+        .collect();
+    push_var(chunkdata, longs.len());
+    for long in longs {
+        chunkdata.extend(long.to_be_bytes());
+    }
+}
+/// The light half of chunk and light update packets, for the sections in `mask`
+fn light<'a>(light: &'a crate::world::ChunkLight, mask: &crate::world::SectionSet) -> impl ToWire + 'a {
+    use crate::world::LightKind;
+    let (sky, dark_sky, sky_arrays) = light.packet(LightKind::Sky, mask);
+    let (block, dark_block, block_arrays) = light.packet(LightKind::Block, mask);
+    (
+        true, // trust server lighting
+        [sky, block],
+        [dark_sky, dark_block],
+        sky_arrays,
+        block_arrays,
+    )
 }
-
 trait ToWire {
     fn encode(&self, pkt: &mut [u8]) -> usize;
 }
@@ -452,7 +332,7 @@ impl ToWire for str {
     }
 }
 
-use super::wire::var;
+use super::wire::{var, Position};
 const CONTINUE_BIT: u8 = 0b1000_0000;
 impl var<u32> {
     fn byte_len(&self) -> usize {
@@ -503,6 +383,12 @@ impl<T: ToWire, const N: usize> ToWire for [T; N] {
         written
     }
 }
+impl ToWire for Position {
+    fn encode(&self, pkt: &mut [u8]) -> usize {
+        let V3 { x, y, z } = self.0;
+        ((x as i64) << 38 | ((z as i64 & 0x3FFFFFF) << 12) | (y as i64 & 0xFFF)).encode(pkt)
+    }
+}
 impl ToWire for Block {
     fn encode(&self, pkt: &mut [u8]) -> usize {
         var(self.net_id() as u32).encode(pkt)
@@ -513,6 +399,20 @@ impl ToWire for Item {
         var(self.net_id() as u32).encode(pkt)
     }
 }
+impl ToWire for ItemStack {
+    fn encode(&self, pkt: &mut [u8]) -> usize {
+        let written = (self.item, self.count).encode(pkt);
+        written + match &self.tag {
+            None => 0u8.encode(&mut pkt[written..]),
+            Some(tag) => {
+                let mut dst = &mut pkt[written..];
+                let len = dst.len();
+                fastnbt::to_writer(&mut dst, tag).unwrap();
+                len - dst.len()
+            }
+        }
+    }
+}
 impl<T: ToWire> ToWire for Option<T> {
     fn encode(&self, pkt: &mut [u8]) -> usize {
         match self {
@@ -554,4 +454,4 @@ macro_rules! impl_n {
         }
     )*};
 }
-impl_n!(u8 i8 u16 i32 i64 u64 f32 f64);
\ No newline at end of file
+impl_n!(u8 i8 u16 i16 i32 i64 u64 f32 f64);
\ No newline at end of file
@@ -78,7 +78,19 @@ impl<'a, T: Wire<'a>> Wire<'a> for Option<T> {
 }
 impl Wire<'_> for crate::types::Item {
     fn decode(pkt: &[u8]) -> Result<(Self, &[u8]), Disconnection> {
-        Wire::decode(pkt).and_then(|(id, rem)| Ok((Self::new(id).ok_or(Disconnection::new())?, rem)))
+        let (var(id), rem) = Wire::decode(pkt)?;
+        Ok((u16::try_from(id).ok().and_then(Self::new).ok_or(Disconnection::new())?, rem))
+    }
+}
+impl Wire<'_> for ItemStack {
+    fn decode(pkt: &[u8]) -> Result<(Self, &[u8]), Disconnection> {
+        let ((item, count), pkt) = Wire::decode(pkt)?;
+        let (tag, pkt) = nbt(pkt).ok_or(Disconnection::new())?;
+        let tag = match tag {
+            [0] => None,
+            tag => Some(fastnbt::from_bytes(tag).map_err(|_| Disconnection::new())?),
+        };
+        Ok((Self { item, count, tag }, pkt))
     }
 }
 pub fn byte(buf: &[u8]) -> Option<(u8, &[u8])> {
@@ -101,6 +113,84 @@ macro_rules! be {
 }
 be! { u16 i16 i32 i64 u64 f32 f64 }
 
+/// Splits a complete named NBT tag (or a lone `TAG_End`) off the front of `buf`
+pub fn nbt(buf: &[u8]) -> Option<(&[u8], &[u8])> {
+    const COMPOUND: u32 = u32::MAX;
+    let (&tag, mut cursor) = buf.split_first()?;
+    if tag != 0 {
+        let (name_len, rem) = u16(cursor)?;
+        cursor = rem.get(name_len as usize..)?;
+    }
+    // explicit stack so deeply nested items cant blow ours
+    let mut stack: Vec<(u8, u32)> = vec![];
+    let mut next = Some(tag);
+    loop {
+        if let Some(tag) = next.take() {
+            let skip = match tag {
+                0 => 0,
+                1 => 1,
+                2 => 2,
+                3 | 5 => 4,
+                4 | 6 => 8,
+                7 | 11 | 12 => {
+                    let (len, rem) = i32(cursor)?;
+                    cursor = rem;
+                    usize::try_from(len).ok()? * [1, 4, 8][(tag > 7) as usize + (tag > 11) as usize]
+                }
+                8 => {
+                    let (len, rem) = u16(cursor)?;
+                    cursor = rem;
+                    len as usize
+                }
+                9 => {
+                    let (&element, rem) = cursor.split_first()?;
+                    let (len, rem) = i32(rem)?;
+                    cursor = rem;
+                    // every element but End takes at least a byte, so anything longer is lying
+                    if len > 0 && (element == 0 || len as usize > cursor.len()) {
+                        return None;
+                    }
+                    if len > 0 {
+                        stack.push((element, len as u32));
+                    }
+                    0
+                }
+                10 => {
+                    stack.push((0, COMPOUND));
+                    0
+                }
+                _ => return None,
+            };
+            cursor = cursor.get(skip..)?;
+        }
+        if stack.len() > 512 {
+            return None;
+        }
+        match stack.last_mut() {
+            None => break,
+            Some((_, COMPOUND)) => {
+                let (&tag, rem) = cursor.split_first()?;
+                cursor = rem;
+                if tag == 0 {
+                    stack.pop();
+                } else {
+                    let (name_len, rem) = u16(cursor)?;
+                    cursor = rem.get(name_len as usize..)?;
+                    next = Some(tag);
+                }
+            }
+            Some((_, 0)) => {
+                stack.pop();
+            }
+            Some((element, remaining)) => {
+                *remaining -= 1;
+                next = Some(*element);
+            }
+        }
+    }
+    Some(buf.split_at(buf.len() - cursor.len()))
+}
+
 pub fn varint(buf: &[u8]) -> Option<(i32, &[u8])> {
     let mut n = 0u32;
     let mut i = 0;
@@ -175,4 +265,69 @@ pub fn pos(buf: &[u8]) -> Option<(V3<i32>, &[u8])> {
     if y >= 1 << 11 { y -= 1 << 12 }
     if z >= 1 << 25 { z -= 1 << 26 }
     Some((V3(x, y, z), rem))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::nbt;
+
+    /// A named compound holding `body`, followed by some trailing bytes that shouldn't be taken
+    fn compound(body: &[u8]) -> Vec<u8> {
+        let mut buf = vec![10, 0, 1, b'a'];
+        buf.extend_from_slice(body);
+        buf.push(0);
+        buf.extend_from_slice(&[0xAB, 0xCD]);
+        buf
+    }
+
+    #[test]
+    fn splits_compounds() {
+        // a short, a string and an empty list
+        let buf = compound(&[2, 0, 1, b's', 0, 7, 8, 0, 1, b't', 0, 2, b'h', b'i', 9, 0, 1, b'l', 1, 0, 0, 0, 0]);
+        let (tag, rest) = nbt(&buf).unwrap();
+        assert_eq!(tag.len(), buf.len() - 2);
+        assert_eq!(rest, [0xAB, 0xCD]);
+        assert_eq!(nbt(&[0, 1, 2]), Some((&[0][..], &[1, 2][..])));
+    }
+    #[test]
+    fn splits_arrays() {
+        // byte, int and long arrays each have their own element size
+        let buf = compound(&[
+            7, 0, 1, b'b', 0, 0, 0, 3, 1, 2, 3,
+            11, 0, 1, b'i', 0, 0, 0, 1, 0, 0, 0, 5,
+            12, 0, 1, b'l', 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 9,
+        ]);
+        assert_eq!(nbt(&buf).unwrap().1, [0xAB, 0xCD]);
+        // a long array claiming more than is there
+        assert_eq!(nbt(&compound(&[12, 0, 1, b'l', 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 9])), None);
+        assert_eq!(nbt(&compound(&[11, 0, 1, b'i', 0xFF, 0xFF, 0xFF, 0xFF])), None);
+    }
+    #[test]
+    fn rejects_truncated() {
+        let buf = compound(&[9, 0, 1, b'l', 10, 0, 0, 0, 2, 1, 0, 1, b'x', 5, 0, 0]);
+        let whole = buf.len() - 2;
+        assert!(nbt(&buf[..whole]).is_some());
+        for end in 0..whole {
+            assert_eq!(nbt(&buf[..end]), None, "accepted {end} bytes");
+        }
+    }
+    #[test]
+    fn rejects_end_lists() {
+        // these used to spin through billions of empty elements
+        assert_eq!(nbt(&compound(&[9, 0, 1, b'l', 0, 0x7F, 0xFF, 0xFF, 0xFF])), None);
+        assert_eq!(nbt(&compound(&[9, 0, 1, b'l', 1, 0x7F, 0xFF, 0xFF, 0xFF])), None);
+        assert!(nbt(&compound(&[9, 0, 1, b'l', 0, 0, 0, 0, 0])).is_some());
+    }
+    #[test]
+    fn caps_depth() {
+        let nested = |depth: usize| {
+            let mut buf = vec![10, 0, 0];
+            for _ in 0..depth {
+                buf.extend_from_slice(&[10, 0, 0]);
+            }
+            buf.extend(std::iter::repeat_n(0, depth + 1));
+            buf
+        };
+        assert!(nbt(&nested(100)).is_some());
+        assert_eq!(nbt(&nested(1000)), None);
+    }
+}
@@ -0,0 +1,237 @@
+//! Everything the vanilla data reports tell us about blocks and items. The tables
+//! themselves are generated by build.rs.
+//!
+//! A [Block] is a block *state*: `minecraft:stone_stairs[facing=east,half=bottom,shape=straight,waterlogged=false]`.
+//! Its network id is `first_state` of its [BlockInfo] plus the index of each property value,
+//! where the last property (by name) changes fastest.
+use crate::types::{Block, Item};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+    Pickaxe,
+    Axe,
+    Shovel,
+    Hoe,
+    Sword,
+    Shears,
+}
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Tier {
+    Wooden,
+    Golden,
+    Stone,
+    Iron,
+    Diamond,
+    Netherite,
+}
+impl Tier {
+    /// How many times faster than a fist a matching tool of this tier digs
+    pub fn speed(self) -> f32 {
+        match self {
+            Tier::Wooden => 2.0,
+            Tier::Stone => 4.0,
+            Tier::Iron => 6.0,
+            Tier::Diamond => 8.0,
+            Tier::Netherite => 9.0,
+            Tier::Golden => 12.0,
+        }
+    }
+}
+/// What we know about a block, shared between all of its states
+#[derive(Debug)]
+pub struct BlockInfo {
+    pub name: &'static str,
+    pub kind: BlockKind,
+    pub first_state: u16,
+    pub default_state: u16,
+    /// Each property and the values it can take, sorted by name
+    pub properties: &'static [(&'static str, &'static [&'static str])],
+    /// Negative if the block can't be broken
+    pub hardness: f32,
+    /// The tool that breaks this block fastest
+    pub tool: Option<Tool>,
+    /// The block only drops when mined with `tool` of at least this tier
+    pub needs: Option<Tier>,
+    pub drop: Option<u16>,
+    /// How much light the block gives off, in its brightest state
+    pub light: u8,
+    /// How much light is lost passing through the block
+    pub opacity: u8,
+}
+impl BlockInfo {
+    pub fn from_name(name: &str) -> Option<&'static Self> {
+        let name = name.strip_prefix("minecraft:").unwrap_or(name);
+        BLOCKS.iter().find(|b| &b.name[10..] == name)
+    }
+    pub fn states(&self) -> u16 {
+        self.properties.iter().map(|(_, values)| values.len() as u16).product()
+    }
+    pub fn default_state(&self) -> Block {
+        Block::new(self.default_state).unwrap()
+    }
+    /// How far apart the states are for successive values of the `i`th property
+    fn stride(&self, i: usize) -> u16 {
+        self.properties[i + 1..].iter().map(|(_, values)| values.len() as u16).product()
+    }
+}
+
+/// A typed block state property, like [props::Facing]. These get generated for every
+/// property name, so `with` and `get` work on any block that has one.
+pub trait Property: Sized {
+    const NAME: &'static str;
+    fn from_value(value: &str) -> Option<Self>;
+    fn value(self) -> &'static str;
+}
+static NUMBERS: [&str; 26] = [
+    "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12",
+    "13", "14", "15", "16", "17", "18", "19", "20", "21", "22", "23", "24", "25",
+];
+
+macro_rules! block {
+    ($id:literal) => {match Block::new($id) {
+        Some(v) => v,
+        None => panic!()
+    }};
+}
+include!(concat!(env!("OUT_DIR"), "/registry.rs"));
+
+impl Block {
+    pub fn info(&self) -> &'static BlockInfo {
+        &BLOCKS[BLOCKS.partition_point(|b| b.first_state <= self.net_id()) - 1]
+    }
+    pub fn kind(&self) -> BlockKind {
+        self.info().kind
+    }
+    pub fn is_air(&self) -> bool {
+        matches!(self.kind(), BlockKind::Air | BlockKind::CaveAir | BlockKind::VoidAir)
+    }
+    /// Collision boxes as `[x1, y1, z1, x2, y2, z2]`, relative to the block's corner
+    pub fn collision_boxes(&self) -> &'static [[f32; 6]] {
+        SHAPES[STATE_SHAPES[self.net_id() as usize] as usize]
+    }
+    /// The block light level this state gives off
+    pub fn light_emission(&self) -> u8 {
+        use BlockKind::*;
+        let info = self.info();
+        if self.get() == Some(props::Lit(false)) {
+            return 0;
+        }
+        match info.kind {
+            Light => self.get().map_or(15, |props::Level(level)| level),
+            RedstoneLamp => 15,
+            Furnace | BlastFurnace | Smoker => 13,
+            RedstoneOre | DeepslateRedstoneOre => 9,
+            // lit candles, 3 for each candle
+            _ if self.get::<props::Lit>().is_some() && info.name.contains("candle") => {
+                3 * self.get().map_or(1, |props::Candles(n)| n)
+            }
+            SeaPickle if self.get() == Some(props::Waterlogged(true)) => {
+                3 + 3 * self.get().map_or(1, |props::Pickles(n)| n)
+            }
+            SeaPickle => 0,
+            RespawnAnchor => [0, 3, 7, 11, 15][self.get().map_or(0, |props::Charges(n)| n as usize).min(4)],
+            _ => info.light,
+        }
+    }
+    /// Every property of this state with its current value
+    pub fn properties(&self) -> impl Iterator<Item = (&'static str, &'static str)> {
+        let info = self.info();
+        let offset = self.net_id() - info.first_state;
+        info.properties.iter().enumerate().map(move |(i, (name, values))| {
+            (*name, values[(offset / info.stride(i)) as usize % values.len()])
+        })
+    }
+    pub fn property(&self, name: &str) -> Option<&'static str> {
+        self.properties().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+    /// The same block with one property changed. None if it has no such property or value
+    pub fn with_property(&self, name: &str, value: &str) -> Option<Self> {
+        let info = self.info();
+        let i = info.properties.iter().position(|(n, _)| *n == name)?;
+        let values = info.properties[i].1;
+        let new = values.iter().position(|v| *v == value)? as u16;
+        let stride = info.stride(i);
+        let old = (self.net_id() - info.first_state) / stride % values.len() as u16;
+        Block::new(self.net_id() - old * stride + new * stride)
+    }
+    pub fn get<P: Property>(&self) -> Option<P> {
+        P::from_value(self.property(P::NAME)?)
+    }
+    /// Sets a property if this block has it (and allows that value), otherwise leaves it be
+    pub fn with<P: Property>(self, value: P) -> Self {
+        self.with_property(P::NAME, value.value()).unwrap_or(self)
+    }
+    /// Reads a state as written in commands, like `stone_stairs[facing=east]`.
+    /// Properties that aren't given keep their default values.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (name, properties) = match s.split_once('[') {
+            Some((name, properties)) => (name, properties.strip_suffix(']')?),
+            None => (s, ""),
+        };
+        let mut block = BlockInfo::from_name(name)?.default_state();
+        for property in properties.split(',').filter(|p| !p.trim().is_empty()) {
+            let (name, value) = property.split_once('=')?;
+            block = block.with_property(name.trim(), value.trim())?;
+        }
+        Some(block)
+    }
+}
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.info().name)?;
+        for (i, (name, value)) in self.properties().enumerate() {
+            write!(f, "{}{name}={value}", if i == 0 { '[' } else { ',' })?;
+        }
+        if !self.info().properties.is_empty() {
+            f.write_str("]")?;
+        }
+        Ok(())
+    }
+}
+impl fmt::Debug for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Item {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.strip_prefix("minecraft:").unwrap_or(name);
+        Item::new(ITEM_NAMES.iter().position(|i| &i[10..] == name)? as u16)
+    }
+    pub fn name(&self) -> &'static str {
+        ITEM_NAMES[self.net_id() as usize]
+    }
+    pub fn max_stack(&self) -> u8 {
+        ITEM_MAX_STACK[self.net_id() as usize]
+    }
+    /// The block this item places
+    pub fn block(&self) -> Option<Block> {
+        Block::new(ITEM_BLOCKS[self.net_id() as usize])
+    }
+    pub fn tool(&self) -> Option<(Tool, Tier)> {
+        if self.name() == "minecraft:shears" {
+            return Some((Tool::Shears, Tier::Wooden));
+        }
+        let (tier, tool) = self.name().strip_prefix("minecraft:")?.split_once('_')?;
+        let tool = match tool {
+            "pickaxe" => Tool::Pickaxe,
+            "axe" => Tool::Axe,
+            "shovel" => Tool::Shovel,
+            "hoe" => Tool::Hoe,
+            "sword" => Tool::Sword,
+            _ => return None,
+        };
+        let tier = match tier {
+            "wooden" => Tier::Wooden,
+            "golden" => Tier::Golden,
+            "stone" => Tier::Stone,
+            "iron" => Tier::Iron,
+            "diamond" => Tier::Diamond,
+            "netherite" => Tier::Netherite,
+            _ => return None,
+        };
+        Some((tool, tier))
+    }
+}
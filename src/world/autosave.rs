@@ -0,0 +1,159 @@
+//! Saving the world a little at a time, so it never costs more than a bit of each tick.
+//!
+//! Every few minutes we note which chunks have changed, and write a handful of them each
+//! tick until they're done. Players are small, so they're all saved when a pass starts.
+//! Changed chunks nobody can see any more are written the same way, ahead of the pass,
+//! and only forgotten once they're on disk.
+//! `save-off` stops anything being written (say, while a backup's copying the world) until
+//! `save-on`, and `save-all` writes everything straight away.
+use crate::prelude::*;
+use super::World;
+use std::collections::VecDeque;
+
+/// How often we start saving, like vanilla
+const INTERVAL: u32 = 5 * 60 * 20;
+/// The most chunks we'll write in a tick
+const CHUNKS_PER_TICK: usize = 8;
+/// And how long we'll spend writing them
+const TICK_BUDGET: time::Duration = time::Duration::from_millis(10);
+/// How often we say how a pass is going
+const PROGRESS_INTERVAL: u32 = 10 * 20;
+
+pub(super) struct Autosave {
+    /// The tick the next pass starts
+    next_pass: u32,
+    /// Chunks that were changed when the pass started, and haven't been written yet
+    waiting: VecDeque<(usize, (i32, i32))>,
+    /// Changed chunks nobody can see, to be written and then unloaded
+    unloading: VecDeque<(usize, (i32, i32))>,
+    started: Option<(time::Instant, u32)>,
+    total: usize,
+    saved: usize,
+}
+impl Autosave {
+    pub fn new() -> Self {
+        Self { next_pass: INTERVAL, waiting: VecDeque::new(), unloading: VecDeque::new(), started: None, total: 0, saved: 0 }
+    }
+}
+
+impl World {
+    pub(super) fn tick_autosave(&mut self) {
+        if !self.level.storage.saving() || self.level.in_memory_overlay() {
+            return;
+        }
+        if self.autosave.started.is_none() && self.tick >= self.autosave.next_pass {
+            self.start_autosave();
+        }
+        let began = time::Instant::now();
+        let mut written = 0;
+        while written < CHUNKS_PER_TICK && began.elapsed() < TICK_BUDGET {
+            // the ones being unloaded are holding on to memory, so they go first
+            let (d, pos, unloading) = match self.autosave.unloading.pop_front() {
+                Some((d, pos)) => (d, pos, true),
+                None => match self.autosave.waiting.pop_front() {
+                    Some((d, pos)) => (d, pos, false),
+                    None => break,
+                },
+            };
+            let dimension = &mut self.dimensions[d];
+            // it might have been saved on its way out
+            let chunk = match dimension.index.get(&pos) {
+                Some(&idx) => &mut dimension.chunks[idx as usize],
+                None => continue,
+            };
+            if chunk.dirty {
+                written += 1;
+                match self.level.save_chunk(&dimension.name, pos, &chunk.content, &chunk.heightmaps, &self.entities.saved_items(d, pos)) {
+                    Ok(()) => {
+                        chunk.dirty = false;
+                        self.autosave.saved += !unloading as usize;
+                    }
+                    Err(e) if unloading => {
+                        log::error!("couldn't save chunk {pos:?}: {e}");
+                        // it'll have to stay until saving works
+                        self.autosave.unloading.push_back((d, pos));
+                        break;
+                    }
+                    Err(e) => log::error!("couldn't autosave chunk {pos:?}: {e}"),
+                }
+            }
+            // unless someone's come back for it
+            if unloading && chunk.visible_to == 0 {
+                dimension.evict(pos);
+            }
+        }
+        let (started, first_tick) = match self.autosave.started {
+            Some(started) => started,
+            None => return,
+        };
+        if self.autosave.waiting.is_empty() {
+            log::info!(
+                "autosaved {} of {} chunks in {:.1}s, over {} ticks",
+                self.autosave.saved,
+                self.autosave.total,
+                started.elapsed().as_secs_f64(),
+                self.tick - first_tick + 1,
+            );
+            self.autosave.started = None;
+        } else if (self.tick - first_tick) % PROGRESS_INTERVAL == PROGRESS_INTERVAL - 1 {
+            log::info!("autosaving: {} of {} chunks done", self.autosave.total - self.autosave.waiting.len(), self.autosave.total);
+        }
+    }
+    fn start_autosave(&mut self) {
+        self.autosave.next_pass = self.tick + INTERVAL;
+        let started = time::Instant::now();
+        let online: Vec<_> = self.online().collect();
+        let players = online.iter().filter(|&&pid| self.save_player(pid)).count();
+        self.save_level_data();
+        self.autosave.waiting = self.dimensions.iter().enumerate()
+            .flat_map(|(d, dimension)| dimension.index.iter()
+                .filter(|(_, &idx)| dimension.chunks[idx as usize].dirty)
+                .map(move |(&pos, _)| (d, pos)))
+            .collect();
+        self.autosave.total = self.autosave.waiting.len();
+        self.autosave.saved = 0;
+        self.autosave.started = Some((started, self.tick));
+        log::info!("autosaving {} chunks, after saving {players} of {} players", self.autosave.total, online.len());
+    }
+    /// Writes everything now, even if saving's been turned off
+    pub(super) fn save_all(&mut self) -> String {
+        let started = time::Instant::now();
+        let unsaved = self.unsaved_chunks();
+        let chunks = self.save_chunks() & self.save_level_data();
+        let online: Vec<_> = self.online().collect();
+        let players = online.iter().filter(|&&pid| self.save_player(pid)).count();
+        // nothing's left for the pass that's under way, and what was waiting to be written can go
+        self.autosave.waiting.clear();
+        for (d, pos) in std::mem::take(&mut self.autosave.unloading) {
+            let dimension = &self.dimensions[d];
+            if dimension.index.get(&pos).is_some_and(|&idx| dimension.chunks[idx as usize].visible_to == 0) {
+                self.unload_chunk(d, pos);
+            }
+        }
+        let took = started.elapsed().as_secs_f64() * 1000.0;
+        log::info!("saved {} of {unsaved} chunks and {players} of {} players in {took:.0}ms", unsaved - self.unsaved_chunks(), online.len());
+        match chunks && players == online.len() {
+            true => format!("saved everything in {took:.0}ms"),
+            false => format!("couldn't save everything: {}", self.level.storage.last_error()),
+        }
+    }
+    /// Forgets a chunk nobody can see, or has the autosave write it first if it's changed
+    pub(super) fn unload_chunk(&mut self, d: usize, pos: (i32, i32)) {
+        if !self.dimensions[d].evict(pos) && !self.autosave.unloading.contains(&(d, pos)) {
+            self.autosave.unloading.push_back((d, pos));
+        }
+    }
+    pub(super) fn set_saving(&mut self, on: bool) -> String {
+        if self.resume_after_backup(on) {
+            return format!("saving will be {} once the backup's done", if on { "on" } else { "off" });
+        }
+        self.level.storage.set_saving(on);
+        if on {
+            log::info!("saving is back on");
+            "saving is back on".to_owned()
+        } else {
+            log::info!("saving is off until save-on");
+            "saving is off. nothing will be written until save-on (or save-all)".to_owned()
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! Backups of the world, taken while it's running.
+//!
+//! Everything unsaved is written first, then saving's turned off while a thread copies the
+//! world into a directory named after when it started. Region files have to be copied, since
+//! we write into them in place, but player data (and level.dat) only ever gets replaced whole,
+//! so those are hard linked. Once the copy's done saving goes back on and old backups are
+//! thrown away. Restoring happens before the server starts, and won't touch the world unless
+//! every chunk in the backup can be read.
+use crate::prelude::*;
+use super::storage::{copy_missing, read_region, region_dir, saved_chunks};
+use super::World;
+use std::path::{Path, PathBuf};
+
+/// Where backups go, and how often and how long they're kept
+#[derive(Debug, Clone)]
+pub struct Backups {
+    pub dir: Option<PathBuf>,
+    /// In minutes. Only taken when asked for without it
+    pub every: Option<u32>,
+    /// How many to keep. The newest one's always kept
+    pub keep: usize,
+    pub max_age: Option<time::Duration>,
+}
+impl Default for Backups {
+    fn default() -> Self {
+        Self { dir: None, every: None, keep: 5, max_age: None }
+    }
+}
+
+struct Running {
+    name: String,
+    dir: PathBuf,
+    started: time::Instant,
+    /// Whether saving should go back on afterwards
+    resume: bool,
+    copy: std::thread::JoinHandle<io::Result<usize>>,
+}
+
+pub(super) struct Backup {
+    config: Backups,
+    next: u32,
+    running: Option<Running>,
+}
+impl Backup {
+    pub fn new(config: Backups, tick: u32) -> Self {
+        let next = tick + config.every.unwrap_or(0) * 60 * 20;
+        Self { config, next, running: None }
+    }
+}
+
+impl World {
+    pub fn set_backups(&mut self, config: Backups) {
+        self.backup = Backup::new(config, self.tick);
+    }
+    pub(super) fn backup_running(&self) -> bool {
+        self.backup.running.is_some()
+    }
+    pub(super) fn backup_command(&mut self, args: &str) -> String {
+        match args.split_whitespace().collect::<Vec<_>>()[..] {
+            [] => self.start_backup(),
+            ["list"] => self.list_backups(),
+            ["verify", name] => match &self.backup.config.dir {
+                Some(dir) => match verify(&dir.join(name)) {
+                    Ok(chunks) => format!("all {chunks} chunks in {name} can be read"),
+                    Err(e) => format!("{name} is broken: {e}"),
+                },
+                None => "there's nowhere backups are kept".to_owned(),
+            },
+            _ => "usage: backup [list|verify <name>]".to_owned(),
+        }
+    }
+    fn start_backup(&mut self) -> String {
+        if self.backup.running.is_some() {
+            return "a backup's already being taken".to_owned();
+        }
+        let (world, dir) = match (self.level.path(), &self.backup.config.dir) {
+            (Some(world), Some(dir)) => (world, dir.clone()),
+            (None, _) => return "there's no world directory to back up".to_owned(),
+            (_, None) => return "there's nowhere to put backups (see --backup-dir)".to_owned(),
+        };
+        if self.level.storage.failing() {
+            return format!("can't back up while saving's failing: {}", self.level.storage.last_error());
+        }
+        // so the backup has everything up to now
+        let mut saved = self.save_chunks() & self.save_level_data();
+        for pid in self.online().collect::<Vec<_>>() {
+            saved &= self.save_player(pid);
+        }
+        if !saved {
+            return format!("couldn't save everything first: {}", self.level.storage.last_error());
+        }
+        let resume = self.level.storage.saving();
+        self.level.storage.set_saving(false);
+        let name = timestamp(time::SystemTime::now());
+        let to = dir.join(&name);
+        let regions: Vec<_> = self.dimensions.iter().map(|d| region_dir(Path::new(""), &d.name)).collect();
+        log::info!("backing up the world to {}", to.display());
+        let copy = {
+            let to = to.clone();
+            std::thread::Builder::new().name("backup".to_owned()).spawn(move || copy_world(&world, &to, &regions))
+        };
+        match copy {
+            Ok(copy) => {
+                self.backup.running = Some(Running { name: name.clone(), dir: to, started: time::Instant::now(), resume, copy });
+                format!("backing up to {name}. saving's off until it's done")
+            }
+            Err(e) => {
+                self.level.storage.set_saving(resume);
+                format!("couldn't start the backup: {e}")
+            }
+        }
+    }
+    /// Waits for the backup to finish, if there's one being taken
+    pub(super) fn finish_backup(&mut self) {
+        if let Some(running) = self.backup.running.take() {
+            log::info!("waiting for the backup to finish");
+            self.backup_finished(running);
+        }
+    }
+    /// What `save-on` and `save-off` do while a backup's being taken
+    pub(super) fn resume_after_backup(&mut self, on: bool) -> bool {
+        match &mut self.backup.running {
+            Some(running) => {
+                running.resume = on;
+                true
+            }
+            None => false,
+        }
+    }
+    pub(super) fn tick_backup(&mut self) {
+        match &self.backup.running {
+            Some(running) if running.copy.is_finished() => {
+                let running = self.backup.running.take().unwrap();
+                self.backup_finished(running);
+            }
+            Some(_) => {}
+            None => if let Some(every) = self.backup.config.every {
+                if self.tick >= self.backup.next {
+                    self.backup.next = self.tick + every * 60 * 20;
+                    let started = self.start_backup();
+                    if self.backup.running.is_none() {
+                        log::error!("couldn't take the scheduled backup: {started}");
+                    }
+                }
+            },
+        }
+    }
+    fn backup_finished(&mut self, running: Running) {
+        let copied = running.copy.join().unwrap_or_else(|_| Err(io::Error::other("the backup thread panicked")));
+        if running.resume {
+            self.level.storage.set_saving(true);
+        }
+        match copied {
+            Ok(files) => {
+                log::info!("backed up {files} files to {} in {:.1}s", running.name, running.started.elapsed().as_secs_f64());
+                self.prune_backups();
+            }
+            Err(e) => {
+                log::error!("couldn't back up the world to {}: {e}", running.dir.display());
+                if let Err(e) = std::fs::remove_dir_all(&running.dir) {
+                    log::error!("couldn't clean up the broken backup: {e}");
+                }
+            }
+        }
+    }
+    fn list_backups(&self) -> String {
+        let dir = match &self.backup.config.dir {
+            Some(dir) => dir,
+            None => return "there's nowhere backups are kept".to_owned(),
+        };
+        match backups(dir) {
+            Ok(backups) if backups.is_empty() => "there aren't any backups yet".to_owned(),
+            Ok(backups) => backups.join("\n"),
+            Err(e) => format!("couldn't read {}: {e}", dir.display()),
+        }
+    }
+    /// Throws away backups past how many we keep, or how long for
+    fn prune_backups(&self) {
+        let Backups { dir, keep, max_age, .. } = &self.backup.config;
+        let dir = dir.as_ref().unwrap();
+        let backups = match backups(dir) {
+            Ok(backups) => backups,
+            Err(e) => return log::error!("couldn't read {}: {e}", dir.display()),
+        };
+        let newest = backups.len().saturating_sub(1);
+        for (i, name) in backups.iter().enumerate() {
+            let path = dir.join(name);
+            let expired = max_age.is_some_and(|max_age| {
+                path.metadata().and_then(|meta| meta.modified()).ok()
+                    .and_then(|modified| modified.elapsed().ok())
+                    .is_some_and(|age| age > max_age)
+            });
+            if i == newest || (i + (*keep).max(1) > newest && !expired) {
+                continue;
+            }
+            match std::fs::remove_dir_all(&path) {
+                Ok(()) => log::info!("threw away the {name} backup"),
+                Err(e) => log::error!("couldn't throw away the {name} backup: {e}"),
+            }
+        }
+    }
+}
+
+/// Our backups in `dir`, oldest first
+fn backups(dir: &Path) -> io::Result<Vec<String>> {
+    let mut backups = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        match entry.file_name().into_string() {
+            Ok(name) if entry.path().is_dir() && is_timestamp(&name) => backups.push(name),
+            _ => {}
+        }
+    }
+    // the names sort by when they were taken
+    backups.sort();
+    Ok(backups)
+}
+
+/// Copies the world's regions, player data and level.dat into `to`. Returns how many files it took
+fn copy_world(from: &Path, to: &Path, regions: &[PathBuf]) -> io::Result<usize> {
+    let mut files = 0;
+    for region in regions {
+        let dir = from.join(region);
+        if !dir.is_dir() {
+            continue;
+        }
+        std::fs::create_dir_all(to.join(region))?;
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            std::fs::copy(entry.path(), to.join(region).join(entry.file_name()))?;
+            files += 1;
+        }
+    }
+    std::fs::create_dir_all(to.join("playerdata"))?;
+    for entry in std::fs::read_dir(from.join("playerdata"))? {
+        let entry = entry?;
+        // half written saves aren't worth keeping
+        if entry.path().extension().is_some_and(|ext| ext == "dat") {
+            link_or_copy(&entry.path(), &to.join("playerdata").join(entry.file_name()))?;
+            files += 1;
+        }
+    }
+    if from.join("level.dat").exists() {
+        link_or_copy(&from.join("level.dat"), &to.join("level.dat"))?;
+        files += 1;
+    }
+    Ok(files)
+}
+/// Hard links can't cross filesystems, so backups elsewhere get copies
+fn link_or_copy(from: &Path, to: &Path) -> io::Result<()> {
+    std::fs::hard_link(from, to).or_else(|_| std::fs::copy(from, to).map(|_| ()))
+}
+
+/// Checks every chunk saved in `backup` can be read. Returns how many there are
+pub fn verify(backup: &Path) -> io::Result<usize> {
+    if !backup.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("there's no backup at {}", backup.display())));
+    }
+    let mut dirs = vec![backup.to_owned()];
+    let mut chunks = 0;
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            }
+        }
+        if dir.file_name().is_none_or(|name| name != "region") {
+            continue;
+        }
+        for chunk in saved_chunks(&dir)? {
+            let broken = |e: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {e}", dir.display()));
+            let nbt = read_region(&dir, chunk).map_err(|e| broken(e.to_string()))?;
+            if let Some(nbt) = nbt {
+                fastnbt::from_bytes::<fastnbt::Value>(&nbt).map_err(|e| broken(format!("chunk {chunk:?} isn't valid NBT: {e}")))?;
+            }
+            chunks += 1;
+        }
+    }
+    Ok(chunks)
+}
+
+/// Puts the world at `world` back how it was in `backup`. Whatever it's replacing is moved
+/// aside rather than deleted, in case the backup was the wrong one
+pub fn restore(backup: &Path, world: &Path) -> io::Result<()> {
+    let chunks = verify(backup)?;
+    let aside = world.join(format!("replaced-{}", timestamp(time::SystemTime::now())));
+    for entry in std::fs::read_dir(backup)? {
+        let name = entry?.file_name();
+        if world.join(&name).exists() {
+            std::fs::create_dir_all(&aside)?;
+            std::fs::rename(world.join(&name), aside.join(&name))?;
+        }
+    }
+    std::fs::create_dir_all(world)?;
+    match copy_missing(backup, world) {
+        0 => {}
+        missed => return Err(io::Error::other(format!("{missed} files couldn't be restored (see the log)"))),
+    }
+    log::info!("restored {chunks} chunks from {}", backup.display());
+    if aside.exists() {
+        log::info!("what was there before is in {}", aside.display());
+    }
+    Ok(())
+}
+
+/// The time in UTC, like 2022-07-30_18-04-05, which sorts the same as the times do
+fn timestamp(at: time::SystemTime) -> String {
+    let secs = at.duration_since(time::UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // days to a date, from http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+fn is_timestamp(name: &str) -> bool {
+    name.len() == 19 && name.chars().enumerate().all(|(i, c)| match i {
+        4 | 7 | 13 | 16 => c == '-',
+        10 => c == '_',
+        _ => c.is_ascii_digit(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Level;
+
+    #[test]
+    fn timestamps() {
+        let at = |secs| timestamp(time::UNIX_EPOCH + time::Duration::from_secs(secs));
+        assert_eq!(at(0), "1970-01-01_00-00-00");
+        assert_eq!(at(1659204245), "2022-07-30_18-04-05");
+        assert_eq!(at(951868799), "2000-02-29_23-59-59");
+        assert!(is_timestamp(&at(1659204245)));
+        assert!(!is_timestamp("2022-07-30"));
+        assert!(!is_timestamp("2022-07-30 18-04-05"));
+        assert!(!is_timestamp("world-backups-extra"));
+    }
+
+    /// A directory full of backups, taken a minute apart, and something that isn't one
+    fn backup_dir(name: &str, taken: usize) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcserv-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for minute in 0..taken {
+            std::fs::create_dir_all(dir.join(timestamp(time::UNIX_EPOCH + time::Duration::from_secs(minute as u64 * 60)))).unwrap();
+        }
+        std::fs::create_dir_all(dir.join("notes")).unwrap();
+        dir
+    }
+    fn prune(dir: &Path, keep: usize, max_age: Option<time::Duration>) -> Vec<String> {
+        let mut network = crate::network::Network::local();
+        let mut world = World::new(Level::empty(), network.notifier());
+        world.set_backups(Backups { dir: Some(dir.to_owned()), keep, max_age, ..Backups::default() });
+        world.prune_backups();
+        let left = backups(dir).unwrap();
+        assert!(dir.join("notes").exists());
+        left
+    }
+
+    #[test]
+    fn prune_keeps_the_newest() {
+        let dir = backup_dir("prune-keep", 5);
+        assert_eq!(prune(&dir, 2, None), ["1970-01-01_00-03-00", "1970-01-01_00-04-00"]);
+        assert_eq!(prune(&dir, 0, None), ["1970-01-01_00-04-00"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prune_throws_away_old_ones() {
+        let dir = backup_dir("prune-age", 3);
+        let old = time::SystemTime::now() - time::Duration::from_secs(2 * 60 * 60);
+        for name in ["1970-01-01_00-00-00", "1970-01-01_00-02-00"] {
+            std::fs::File::open(dir.join(name)).unwrap().set_modified(old).unwrap();
+        }
+        // the newest stays, however old it is
+        assert_eq!(prune(&dir, 5, Some(time::Duration::from_secs(60 * 60))), ["1970-01-01_00-01-00", "1970-01-01_00-02-00"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,75 @@
+//! Biomes, as far as the client cares: the colours of grass, foliage, water and sky,
+//! what falls when it rains, and the ambient sounds and particles.
+//!
+//! The definitions are the `worldgen/biome` json from a datapack, see [super::codec].
+//! Chunks store a biome as its place in that registry.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precipitation {
+    None,
+    Rain,
+    Snow,
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Biome {
+    pub precipitation: Precipitation,
+    pub temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub temperature_modifier: Option<String>,
+    pub downfall: f32,
+    pub effects: BiomeEffects,
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BiomeEffects {
+    pub sky_color: i32,
+    pub fog_color: i32,
+    pub water_color: i32,
+    pub water_fog_color: i32,
+    /// Worked out from the temperature and downfall if it isn't given
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub grass_color: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub foliage_color: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub grass_color_modifier: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub particle: Option<Particle>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub ambient_sound: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub mood_sound: Option<MoodSound>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub additions_sound: Option<AdditionsSound>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub music: Option<Music>,
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Particle {
+    pub probability: f32,
+    pub options: ParticleOptions,
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ParticleOptions {
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MoodSound {
+    pub sound: String,
+    pub tick_delay: i32,
+    pub block_search_extent: i32,
+    pub offset: f64,
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AdditionsSound {
+    pub sound: String,
+    pub tick_chance: f64,
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Music {
+    pub sound: String,
+    pub min_delay: i32,
+    pub max_delay: i32,
+    pub replace_current_music: bool,
+}
@@ -0,0 +1,254 @@
+//! Staying inside the memory the admin's given us, like the notes in lib.rs plan out.
+//!
+//! We don't count every allocation, just the things that grow with the number of players:
+//! chunks, the players themselves, and whatever's waiting to be sent to them. When that goes
+//! over budget we first drop chunks nobody can see, and then give up view distance or player
+//! slots, whichever the admin cares about less. If that would take us below the lower bounds
+//! they set, we shut down rather than let it get worse.
+use crate::prelude::*;
+use super::{queue::MAX_PID, Chunk, ChunkContent, Dimension, LightSection, Player, World};
+use std::ops::RangeInclusive;
+
+/// Which limit to keep when memory's short
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prefer {
+    Players,
+    ViewDistance,
+}
+/// What the admin lets the server use
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// In bytes
+    pub max_memory: usize,
+    pub view_distance: RangeInclusive<u8>,
+    pub max_players: RangeInclusive<usize>,
+    pub prefer: Prefer,
+    /// How many can wait for a slot once the server's full
+    pub max_queue: usize,
+    /// Slots only ops can have, on top of `max_players`
+    pub priority_slots: usize,
+}
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_memory: usize::MAX,
+            view_distance: 2..=32,
+            max_players: 1..=MAX_PID,
+            prefer: Prefer::Players,
+            max_queue: 64,
+            priority_slots: 0,
+        }
+    }
+}
+impl Limits {
+    /// Reads bounds like `7..` or `2..12` (or just `8`), which have to fit inside `bounds`
+    pub fn parse_range<T: std::str::FromStr + Copy + PartialOrd>(s: &str, bounds: RangeInclusive<T>) -> Option<RangeInclusive<T>> {
+        let (min, max) = match s.split_once("..") {
+            Some((min, max)) => {
+                let min = if min.is_empty() { *bounds.start() } else { min.parse().ok()? };
+                let max = if max.is_empty() { *bounds.end() } else { max.strip_prefix('=').unwrap_or(max).parse().ok()? };
+                (min, max)
+            }
+            None => s.parse().ok().map(|n| (n, n))?,
+        };
+        (bounds.contains(&min) && bounds.contains(&max) && min <= max).then_some(min..=max)
+    }
+    /// Whether the limits make sense together, which `parse_range` can't tell on its own
+    pub fn check(&self) -> Result<(), String> {
+        // chunks keep track of who can see them in a u32, ops' slots included
+        if self.max_players.end() + self.priority_slots > MAX_PID {
+            return Err(format!("only {MAX_PID} can play at once, counting priority slots"));
+        }
+        Ok(())
+    }
+}
+
+/// Roughly how much memory each part of the server is using, in bytes
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Usage {
+    pub chunks: usize,
+    pub players: usize,
+    /// Responses waiting to go out, and the buffers they're written into
+    pub buffers: usize,
+}
+impl Usage {
+    pub fn total(&self) -> usize {
+        self.chunks + self.players + self.buffers
+    }
+}
+
+pub(super) struct Budget {
+    pub limits: Limits,
+    /// How far players can see and how many can join right now, somewhere within the limits
+    pub view_distance: u8,
+    pub max_players: usize,
+    pub usage: Usage,
+    /// Why we're shutting down, once we've had to
+    pub shutdown: Option<String>,
+}
+impl Budget {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            view_distance: *limits.view_distance.end(),
+            max_players: *limits.max_players.end(),
+            limits,
+            usage: Usage::default(),
+            shutdown: None,
+        }
+    }
+}
+/// We start giving things back once usage is below this fraction of the budget
+const RECOVERED: f64 = 0.75;
+/// What's sent to a player is written into this much buffer at a time
+const SEND_BUFFER: usize = 2 * 1024 * 1024;
+
+impl ChunkContent {
+    fn bytes(&self) -> usize {
+        match self {
+            ChunkContent::OneToOne { nonaircounts, blocks, biomes, .. } => nonaircounts.len() * 2 + blocks.len() * 8 + biomes.len() * 2,
+        }
+    }
+}
+impl Chunk {
+    fn bytes(&self) -> usize {
+        let light = self.light.sky.iter().chain(&*self.light.block).map(|section| match section {
+            LightSection::Varied(levels) => levels.len(),
+            LightSection::Uniform(_) => 0,
+        }).sum::<usize>();
+        std::mem::size_of::<Chunk>() + self.content.bytes() + light + std::mem::size_of_val(&*self.light)
+    }
+}
+impl Dimension {
+    /// What a chunk with nothing special about it takes up
+    fn chunk_bytes(&self) -> usize {
+        std::mem::size_of::<Chunk>() + self.sections() * (4096 / 4 * 8 + 64 * 2 + 2)
+    }
+    fn bytes(&self) -> usize {
+        let chunks: usize = self.index.values().map(|&idx| self.chunks[idx as usize].bytes()).sum();
+        // and the ones that are being loaded have been allocated somewhere
+        chunks + self.loading.len() * self.chunk_bytes() + (self.chunks.capacity() - self.index.len()) * std::mem::size_of::<Chunk>()
+    }
+}
+
+impl World {
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.budget = Budget::new(limits);
+    }
+    pub fn usage(&self) -> Usage {
+        self.budget.usage
+    }
+    /// Set once we've run out of memory and can't keep to the limits, with why
+    pub fn shutdown_reason(&self) -> Option<&str> {
+        self.budget.shutdown.as_deref()
+    }
+    fn measure(&self, inboxes: &Inboxes) -> Usage {
+        let player = std::mem::size_of::<Player>() + std::mem::size_of::<super::Inventory>();
+        Usage {
+            chunks: self.dimensions.iter().map(Dimension::bytes).sum(),
+            players: self.players.len() * player,
+            buffers: inboxes.buffered_bytes(),
+        }
+    }
+    /// How much a new player could need, if they can't see anyone else's chunks
+    pub(super) fn player_bytes(&self) -> usize {
+        let view = 2 * self.budget.view_distance as usize + 1;
+        view * view * self.dimensions[super::OVERWORLD].chunk_bytes() + SEND_BUFFER + std::mem::size_of::<Player>()
+    }
+    /// Whether someone can join, or why not
+    pub(super) fn admit(&self, op: bool) -> Result<(), String> {
+        let slots = self.budget.max_players + if op { self.budget.limits.priority_slots } else { 0 };
+        if self.recovering {
+            Err("The server can't save the world right now".to_owned())
+        } else if self.online().count() >= slots {
+            Err("The server is full".to_owned())
+        } else if self.budget.usage.total().saturating_add(self.player_bytes()) > self.budget.limits.max_memory {
+            Err("The server doesn't have the memory for anyone else right now".to_owned())
+        } else {
+            Ok(())
+        }
+    }
+    /// Checks how much memory we're using, and gives some back if it's too much
+    pub(super) fn enforce_budget(&mut self, mut inboxes: Inboxes) {
+        if self.budget.shutdown.is_some() {
+            return;
+        }
+        self.budget.usage = self.measure(&inboxes);
+        let max = self.budget.limits.max_memory;
+        if self.budget.usage.total() <= max {
+            if (self.budget.usage.total() as f64) < max as f64 * RECOVERED {
+                self.recover(inboxes);
+            }
+            return;
+        }
+        // chunks that were loaded for something other than being seen are the easiest to lose
+        self.evict_unseen();
+        self.budget.usage = self.measure(&inboxes);
+        if self.budget.usage.total() <= max {
+            return;
+        }
+        log::warn!("using {:?} of {max} bytes", self.budget.usage);
+        let limits = &self.budget.limits;
+        let can_shrink_view = self.budget.view_distance > *limits.view_distance.start();
+        let can_shrink_players = self.budget.max_players > *limits.max_players.start();
+        let shrink_view = match limits.prefer {
+            Prefer::Players => can_shrink_view,
+            Prefer::ViewDistance => can_shrink_view && !can_shrink_players,
+        };
+        if shrink_view {
+            self.budget.view_distance -= 1;
+            log::warn!("out of memory, so view distance is down to {}", self.budget.view_distance);
+            self.apply_view_distance(inboxes);
+        } else if can_shrink_players {
+            // nobody else gets in
+            self.budget.max_players = self.online().count().clamp(*limits.max_players.start(), self.budget.max_players - 1);
+            log::warn!("out of memory, so only {} can play", self.budget.max_players);
+        } else if self.online().count() > self.budget.max_players {
+            let newest = self.online().max_by_key(|&pid| self.players[pid].eid).unwrap();
+            self.kick(newest, inboxes.reborrow(), "The server ran out of memory");
+        } else {
+            log::error!("out of memory with {} players at view distance {}, shutting down", self.online().count(), self.budget.view_distance);
+            self.budget.shutdown = Some("The server ran out of memory".to_owned());
+        }
+    }
+    /// Gives back what we took away when memory was short, a step at a time
+    fn recover(&mut self, inboxes: Inboxes) {
+        let limits = &self.budget.limits;
+        let can_grow_view = self.budget.view_distance < *limits.view_distance.end();
+        let can_grow_players = self.budget.max_players < *limits.max_players.end();
+        let grow_view = match limits.prefer {
+            Prefer::Players => can_grow_view && !can_grow_players,
+            Prefer::ViewDistance => can_grow_view,
+        };
+        if grow_view {
+            self.budget.view_distance += 1;
+            self.apply_view_distance(inboxes);
+        } else if can_grow_players {
+            self.budget.max_players += 1;
+        }
+    }
+    fn apply_view_distance(&mut self, mut inboxes: Inboxes) {
+        for pid in self.online().filter(|&pid| !self.players[pid].lobby).collect::<Vec<_>>() {
+            let view_distance = self.players[pid].wanted_view_distance.min(self.budget.view_distance);
+            if view_distance != self.players[pid].view_distance {
+                if let Some(mut inbox) = inboxes.get(pid) {
+                    self.players[pid].view_distance = view_distance;
+                    inbox.submit(Response::SetRenderDistance(view_distance));
+                    self.update_view(pid, inbox);
+                }
+            }
+        }
+    }
+    /// Forgets the chunks nobody can see. Ones that have changed go once they're saved
+    fn evict_unseen(&mut self) {
+        for d in 0..self.dimensions.len() {
+            let dimension = &self.dimensions[d];
+            let unseen: Vec<_> = dimension.index.iter()
+                .filter(|(_, &idx)| dimension.chunks[idx as usize].visible_to == 0)
+                .map(|(&pos, _)| pos)
+                .collect();
+            for pos in unseen {
+                self.unload_chunk(d, pos);
+            }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! The registries the client gets in Join Game: dimension types, biomes and chat types.
+//!
+//! They're read from datapack json, so a world can change or add to them the same way it
+//! would on a vanilla server: `<world>/datapacks/<pack>/data/<namespace>/<registry>/<name>.json`.
+//! What vanilla ships is built in from `data/minecraft`.
+use crate::prelude::*;
+use crate::registry::BUILTIN_DATAPACK;
+use super::biome::Biome;
+use std::path::Path;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DimensionType {
+    /// The time of day is stuck at this, if it's set
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub fixed_time: Option<i64>,
+    pub has_skylight: bool,
+    pub has_ceiling: bool,
+    pub ultrawarm: bool,
+    pub natural: bool,
+    pub coordinate_scale: f64,
+    pub bed_works: bool,
+    pub respawn_anchor_works: bool,
+    pub min_y: i32,
+    pub height: i32,
+    pub logical_height: i32,
+    pub infiniburn: String,
+    pub effects: String,
+    pub ambient_light: f32,
+    pub piglin_safe: bool,
+    pub has_raids: bool,
+    pub monster_spawn_light_level: IntProvider,
+    pub monster_spawn_block_light_limit: i32,
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum IntProvider {
+    Constant(i32),
+    Ranged {
+        #[serde(rename = "type")]
+        kind: String,
+        value: IntRange,
+    },
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IntRange {
+    pub min_inclusive: i32,
+    pub max_inclusive: i32,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ChatType {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub chat: Option<ChatDisplay>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub overlay: Option<ChatDisplay>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub narration: Option<Narration>,
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ChatDisplay {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub decoration: Option<Decoration>,
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Narration {
+    pub priority: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub decoration: Option<Decoration>,
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Decoration {
+    pub translation_key: String,
+    pub parameters: Vec<String>,
+    #[serde(default)]
+    pub style: Style,
+}
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Style {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub bold: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub italic: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub underlined: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub strikethrough: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub obfuscated: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub font: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub insertion: Option<String>,
+}
+
+pub struct Codec {
+    pub dimension_types: Vec<(String, DimensionType)>,
+    pub biomes: Vec<(String, Biome)>,
+    pub chat_types: Vec<(String, ChatType)>,
+    /// The whole thing as NBT, ready to go in Join Game
+    encoded: Vec<u8>,
+}
+
+fn invalid(name: &str, e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{name}: {e}"))
+}
+/// Adds an entry, replacing any that has the same name
+fn insert<T: serde::de::DeserializeOwned>(entries: &mut Vec<(String, T)>, name: String, json: &str) -> io::Result<()> {
+    let value = serde_json::from_str(json).map_err(|e| invalid(&name, e))?;
+    match entries.iter_mut().find(|(n, _)| *n == name) {
+        Some(entry) => entry.1 = value,
+        None => entries.push((name, value)),
+    }
+    Ok(())
+}
+
+impl Codec {
+    /// Just what vanilla has
+    pub fn builtin() -> Self {
+        let mut codec = Self { dimension_types: vec![], biomes: vec![], chat_types: vec![], encoded: vec![] };
+        for (kind, name, json) in BUILTIN_DATAPACK {
+            codec.add(kind, name.to_string(), json).expect("the builtin datapack is broken");
+        }
+        codec.encode();
+        codec
+    }
+    /// The builtin registries, plus whatever the datapacks in `dir` add
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let mut codec = Self::builtin();
+        if !dir.exists() {
+            return Ok(codec);
+        }
+        let mut packs: Vec<_> = std::fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+        packs.sort_by_key(|p| p.file_name());
+        for pack in packs {
+            if !pack.file_type()?.is_dir() {
+                log::warn!("skipping datapack {:?}, only unzipped ones are supported", pack.file_name());
+                continue;
+            }
+            let data = pack.path().join("data");
+            if !data.exists() {
+                continue;
+            }
+            for namespace in std::fs::read_dir(data)? {
+                let namespace = namespace?;
+                let ns = namespace.file_name().to_string_lossy().into_owned();
+                for kind in ["dimension_type", "worldgen/biome", "chat_type"] {
+                    codec.add_dir(kind, &ns, &namespace.path().join(kind), "")?;
+                }
+            }
+            log::info!("loaded datapack {:?}", pack.file_name());
+        }
+        codec.dimension_types.sort_by(|a, b| a.0.cmp(&b.0));
+        codec.biomes.sort_by(|a, b| a.0.cmp(&b.0));
+        codec.chat_types.sort_by(|a, b| a.0.cmp(&b.0));
+        codec.encode();
+        Ok(codec)
+    }
+    /// Adds every json file in `dir`, and in the directories inside it
+    fn add_dir(&mut self, kind: &str, namespace: &str, dir: &Path, prefix: &str) -> io::Result<()> {
+        if !dir.exists() {
+            return Ok(());
+        }
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            if entry.file_type()?.is_dir() {
+                self.add_dir(kind, namespace, &path, &format!("{prefix}{stem}/"))?;
+            } else if path.extension().is_some_and(|e| e == "json") {
+                self.add(kind, format!("{namespace}:{prefix}{stem}"), &std::fs::read_to_string(&path)?)?;
+            }
+        }
+        Ok(())
+    }
+    fn add(&mut self, kind: &str, name: String, json: &str) -> io::Result<()> {
+        match kind {
+            "dimension_type" => insert(&mut self.dimension_types, name, json),
+            "worldgen/biome" => insert(&mut self.biomes, name, json),
+            "chat_type" => insert(&mut self.chat_types, name, json),
+            _ => unreachable!(),
+        }
+    }
+    fn encode(&mut self) {
+        #[derive(serde::Serialize)]
+        struct Registry<'a, T> {
+            #[serde(rename = "type")]
+            kind: &'static str,
+            value: Vec<Entry<'a, T>>,
+        }
+        #[derive(serde::Serialize)]
+        struct Entry<'a, T> {
+            name: &'a str,
+            id: i32,
+            element: &'a T,
+        }
+        fn registry<'a, T>(kind: &'static str, entries: &'a [(String, T)]) -> Registry<'a, T> {
+            let value = entries.iter().enumerate()
+                .map(|(id, (name, element))| Entry { name, id: id as i32, element })
+                .collect();
+            Registry { kind, value }
+        }
+        #[derive(serde::Serialize)]
+        struct Codec<'a> {
+            #[serde(rename = "minecraft:dimension_type")]
+            dimension_types: Registry<'a, DimensionType>,
+            #[serde(rename = "minecraft:worldgen/biome")]
+            biomes: Registry<'a, Biome>,
+            #[serde(rename = "minecraft:chat_type")]
+            chat_types: Registry<'a, ChatType>,
+        }
+        self.encoded = fastnbt::to_bytes(&Codec {
+            dimension_types: registry("minecraft:dimension_type", &self.dimension_types),
+            biomes: registry("minecraft:worldgen/biome", &self.biomes),
+            chat_types: registry("minecraft:chat_type", &self.chat_types),
+        }).unwrap();
+    }
+    pub fn encoded(&self) -> &[u8] {
+        &self.encoded
+    }
+    pub fn biome_id(&self, name: &str) -> Option<u16> {
+        self.biomes.iter().position(|(n, _)| n == name).map(|id| id as u16)
+    }
+    pub fn biome_name(&self, id: u16) -> &str {
+        &self.biomes[id as usize].0
+    }
+    /// What new chunks are made of, and what we fall back on for biomes we don't know
+    pub fn plains(&self) -> u16 {
+        self.biome_id("minecraft:plains").expect("there's no plains biome")
+    }
+    pub fn dimension_type(&self, name: &str) -> Option<&DimensionType> {
+        self.dimension_types.iter().find(|(n, _)| n == name).map(|(_, t)| t)
+    }
+    pub fn chat_type_id(&self, name: &str) -> Option<i32> {
+        self.chat_types.iter().position(|(n, _)| n == name).map(|id| id as i32)
+    }
+}
@@ -0,0 +1,119 @@
+//! Players hitting things.
+//!
+//! Whether they could reach what they hit is judged by where they saw it, not where it is
+//! now. Every tick ends with a ping numbered by that tick, so the newest one they've answered
+//! says which positions they'd had when they swung. They can hold off answering to make it
+//! look like they saw something further back, so that's only believed for a second or so,
+//! unless it's our fault: if the next ping hasn't even gone out to them yet, they really
+//! can't have seen anything newer. Not if it's stuck because they've stopped reading, though.
+use crate::prelude::*;
+use super::entity::{self, Kind};
+use super::vitals::Cause;
+use super::World;
+
+/// How far from their eyes a player can reach, with a little slack for rounding
+const REACH: f64 = 3.0;
+const CREATIVE_REACH: f64 = 5.0;
+const REACH_SLACK: f64 = 0.3;
+const EYE_HEIGHT: f64 = 1.62;
+/// How far back we'll believe they saw something, when the delay's theirs
+const REWIND: u32 = 20;
+/// Clients spread a move over this many ticks, so they're seeing somewhere in between
+const INTERPOLATION: u32 = 3;
+const KNOCKBACK: f64 = 0.4;
+
+impl World {
+    pub(crate) fn attack(&mut self, pid: usize, mut inboxes: Inboxes, eid: i32) {
+        let attacker = &self.players[pid];
+        if attacker.vitals.dead() {
+            return;
+        }
+        let target = match self.entities.get_ref(eid) {
+            Some(target) => target,
+            None => return,
+        };
+        let victim = match target.kind {
+            Kind::Player { pid: victim, .. } if victim != pid => victim,
+            // vanilla kicks people for hitting items, but it's more likely we're out of date
+            _ => return,
+        };
+        if target.dimension != attacker.dimension || !target.seen_by(pid) {
+            return;
+        }
+        let seen = self.seen_tick(pid, inboxes.get(pid).unwrap());
+        let reach = if attacker.gamemode == GameMode::Creative { CREATIVE_REACH } else { REACH };
+        let (x, y, z) = attacker.position;
+        let eye = (x, y + EYE_HEIGHT, z);
+        let closest = (seen.saturating_sub(INTERPOLATION)..=seen)
+            .map(|tick| distance_to(eye, target.position_at(tick), target.hitbox().unwrap()))
+            .fold(f64::MAX, f64::min);
+        if closest > reach + REACH_SLACK {
+            log::warn!(
+                "{} hit {} from {closest:.2} blocks away, as they saw it {} ticks ago",
+                attacker.name.as_str(), self.players[victim].name.as_str(), self.tick - seen
+            );
+            return;
+        }
+        let from = target.position_at(seen);
+        let damage = self.hit_damage(pid);
+        let player = &mut self.players[pid];
+        player.last_attack = self.tick;
+        player.vitals.exhaust(0.1);
+        if self.hurt(victim, inboxes.reborrow(), damage, Cause::Attack(pid)) {
+            self.knock_back(victim, inboxes, from, (x, z));
+        }
+    }
+    /// The tick whose positions they were seeing, as far as we'll believe
+    fn seen_tick(&self, pid: usize, inbox: Inbox) -> u32 {
+        let acked = self.players[pid].movement.acked();
+        let next = inbox.queued().find_map(|response| match response {
+            Response::Ping(id) => Some(*id as u32),
+            _ => None,
+        });
+        let ours = !inbox.backed_up() && next.is_some_and(|next| next <= acked + 1);
+        let rewind = if ours { entity::HISTORY } else { REWIND };
+        acked.clamp(self.tick.saturating_sub(rewind), self.tick)
+    }
+    /// What their next hit does, with what they're holding and how long they've waited since the last one
+    fn hit_damage(&self, pid: usize) -> f32 {
+        let player = &self.players[pid];
+        let held = player.inventory[super::HOTBAR + player.selected_item as usize].as_ref();
+        let (damage, speed) = weapon(held.and_then(|stack| stack.item.tool()));
+        let charge = (((self.tick - player.last_attack) as f32 + 0.5) * speed / 20.0).min(1.0);
+        let damage = damage * (0.2 + charge * charge * 0.8);
+        // a crit, from falling onto them
+        if charge > 0.9 && player.movement.falling() {
+            damage * 1.5
+        } else {
+            damage
+        }
+    }
+    /// Pushes them away from `(x, z)`, as if they were at `from`
+    fn knock_back(&mut self, pid: usize, mut inboxes: Inboxes, from: (f64, f64, f64), (x, z): (f64, f64)) {
+        let player = &mut self.players[pid];
+        let (dx, dz) = (from.0 - x, from.2 - z);
+        let length = dx.hypot(dz).max(1e-4);
+        let up = if player.on_ground { KNOCKBACK } else { 0.0 };
+        let velocity = (dx / length * KNOCKBACK, up, dz / length * KNOCKBACK);
+        player.movement.push(self.tick, velocity);
+        inboxes.get(pid).unwrap().submit(Response::EntityVelocity(player.eid, velocity));
+    }
+}
+/// How far `point` is from the nearest part of a hitbox standing at `position`
+fn distance_to(point: (f64, f64, f64), position: (f64, f64, f64), (half, height): (f64, f64)) -> f64 {
+    let dx = (point.0 - position.0).abs() - half;
+    let dy = (point.1 - position.1 - height / 2.0).abs() - height / 2.0;
+    let dz = (point.2 - position.2).abs() - half;
+    (dx.max(0.0).powi(2) + dy.max(0.0).powi(2) + dz.max(0.0).powi(2)).sqrt()
+}
+/// How much a hit does with a tool, and how many times a second it can swing at full strength
+fn weapon(tool: Option<(Tool, Tier)>) -> (f32, f32) {
+    match tool {
+        Some((Tool::Sword, tier)) => ([4.0, 4.0, 5.0, 6.0, 7.0, 8.0][tier as usize], 1.6),
+        Some((Tool::Axe, tier)) => ([7.0, 7.0, 9.0, 9.0, 9.0, 10.0][tier as usize], [0.8, 1.0, 0.8, 0.9, 1.0, 1.0][tier as usize]),
+        Some((Tool::Pickaxe, tier)) => ([2.0, 2.0, 3.0, 4.0, 5.0, 6.0][tier as usize], 1.2),
+        Some((Tool::Shovel, tier)) => ([2.5, 2.5, 3.5, 4.5, 5.5, 6.5][tier as usize], 1.0),
+        Some((Tool::Hoe, tier)) => (1.0, [1.0, 1.0, 2.0, 3.0, 4.0, 4.0][tier as usize]),
+        Some((Tool::Shears, _)) | None => (1.0, 4.0),
+    }
+}
@@ -0,0 +1,161 @@
+//! Survival mode block breaking.
+//!
+//! The client works out how long a block takes to break and tells us when it's done,
+//! so all we can do is come to the same figure and refuse any finish that arrives too early.
+use crate::prelude::*;
+use super::{movement, World, HOTBAR};
+
+#[derive(Debug, Clone)]
+pub(super) struct Digging {
+    pos: V3<i32>,
+    started: u32,
+    ticks: u32,
+    stage: u8,
+}
+/// Vanilla accepts a finish once the block is 70% broken, to make up for latency
+const EARLIEST_FINISH: f32 = 0.7;
+/// Any stage outside 0..=9 removes the cracks
+const NO_CRACKS: u8 = 10;
+/// How far from their eyes to the middle of a block vanilla lets anyone dig or build, squared
+const REACH_SQUARED: f64 = 64.0;
+const EYE_HEIGHT: f64 = 1.62;
+
+fn matching_tool(block: Block, held: Option<&ItemStack>) -> Option<Tier> {
+    held.and_then(|s| s.item.tool())
+        .filter(|(tool, _)| Some(*tool) == block.info().tool)
+        .map(|(_, tier)| tier)
+}
+/// Whether breaking `block` with `held` will drop anything
+fn harvests(block: Block, held: Option<&ItemStack>) -> bool {
+    block.info().needs.is_none_or(|needs| matching_tool(block, held).is_some_and(|tier| tier >= needs))
+}
+/// How many ticks it takes to break `block`, or `None` if it can't be broken
+fn break_ticks(block: Block, held: Option<&ItemStack>, on_ground: bool) -> Option<u32> {
+    let info = block.info();
+    if info.hardness < 0.0 {
+        return None;
+    } else if info.hardness == 0.0 {
+        return Some(0);
+    }
+    let mut speed = match (info.tool, matching_tool(block, held)) {
+        (_, None) => 1.0,
+        (Some(Tool::Sword), Some(_)) => 15.0,
+        (Some(Tool::Shears), Some(_)) => 5.0,
+        (_, Some(tier)) => tier.speed(),
+    };
+    let efficiency = held.map_or(0, |s| s.enchantment_level("minecraft:efficiency")) as f32;
+    if speed > 1.0 && efficiency > 0.0 {
+        speed += efficiency * efficiency + 1.0;
+    }
+    if !on_ground {
+        speed /= 5.0;
+    }
+    let damage = speed / info.hardness / if harvests(block, held) { 30.0 } else { 100.0 };
+    Some(if damage >= 1.0 { 0 } else { (1.0 / damage).ceil() as u32 })
+}
+impl World {
+    fn held_item(&self, pid: usize) -> Option<&ItemStack> {
+        let player = &self.players[pid];
+        player.inventory[HOTBAR + player.selected_item as usize].as_ref()
+    }
+    /// Whether a block's close enough for them to touch
+    pub(super) fn within_reach(&self, pid: usize, pos: V3<i32>) -> bool {
+        let (x, y, z) = self.players[pid].position;
+        let (dx, dy, dz) = (pos.x as f64 + 0.5 - x, pos.y as f64 + 0.5 - (y + EYE_HEIGHT), pos.z as f64 + 0.5 - z);
+        dx * dx + dy * dy + dz * dz <= REACH_SQUARED
+    }
+    /// Tells them what's really at `pos`, when they've guessed wrong about what they could do to it
+    pub(super) fn resend_block(&mut self, pid: usize, mut inboxes: Inboxes, pos: V3<i32>) {
+        let block = self.dimension(pid).block_at(pos);
+        inboxes.get(pid).unwrap().submit(Response::SetBlock(pos, block));
+        self.blocks_changed(1 << pid, movement::Changed::Block(pos));
+    }
+    pub(crate) fn start_digging(&mut self, pid: usize, mut inboxes: Inboxes, pos: V3<i32>) {
+        if !self.within_reach(pid, pos) {
+            log::warn!("{} tried to dig {pos:?}, out of reach", self.players[pid].name.as_str());
+            self.resend_block(pid, inboxes, pos);
+            return;
+        }
+        let block = match self.dimension(pid).block_at(pos) {
+            Some(block) => block,
+            None => return,
+        };
+        let player = &self.players[pid];
+        if player.gamemode == GameMode::Creative {
+            let dimension = player.dimension;
+            self.set_block(dimension, pos, None, inboxes.reborrow());
+            self.update_neighbours(dimension, inboxes, pos);
+            return;
+        }
+        match break_ticks(block, self.held_item(pid), player.on_ground) {
+            None => {}
+            Some(0) => self.break_block(pid, inboxes, pos),
+            Some(ticks) => self.players[pid].digging = Some(Digging { pos, started: self.tick, ticks, stage: NO_CRACKS }),
+        }
+    }
+    pub(crate) fn cancel_digging(&mut self, pid: usize, inboxes: Inboxes) {
+        if let Some(digging) = self.players[pid].digging.take() {
+            self.broadcast_cracks(pid, inboxes, digging.pos, NO_CRACKS);
+        }
+    }
+    pub(crate) fn finish_digging(&mut self, pid: usize, mut inboxes: Inboxes, pos: V3<i32>) {
+        match self.players[pid].digging.take() {
+            Some(digging) if digging.pos == pos => {
+                self.broadcast_cracks(pid, inboxes.reborrow(), pos, NO_CRACKS);
+                let elapsed = self.tick - digging.started;
+                if elapsed as f32 >= digging.ticks as f32 * EARLIEST_FINISH {
+                    self.break_block(pid, inboxes, pos);
+                    return;
+                }
+                log::warn!(
+                    "{} broke a block at {pos:?} after {elapsed} ticks, but it takes {}",
+                    self.players[pid].name.as_str(), digging.ticks
+                );
+            }
+            _ => {}
+        }
+        // the client thinks the block is gone. put it back
+        self.resend_block(pid, inboxes, pos);
+    }
+    fn break_block(&mut self, pid: usize, mut inboxes: Inboxes, pos: V3<i32>) {
+        let block = match self.dimension(pid).block_at(pos) {
+            Some(block) => block,
+            None => return,
+        };
+        let dimension = self.players[pid].dimension;
+        self.set_block(dimension, pos, None, inboxes.reborrow());
+        self.players[pid].vitals.exhaust(0.005);
+        self.update_neighbours(dimension, inboxes.reborrow(), pos);
+        if self.players[pid].gamemode == GameMode::Survival && harvests(block, self.held_item(pid)) {
+            if let Some(item) = block.info().drop.and_then(Item::new) {
+                let position = (pos.x as f64 + 0.5, pos.y as f64 + 0.25, pos.z as f64 + 0.5);
+                self.drop_item(dimension, position, ItemStack::new(item, 1));
+            }
+        }
+    }
+    fn broadcast_cracks(&self, digger: usize, inboxes: Inboxes, pos: V3<i32>, stage: u8) {
+        let viewers = self.dimension(digger).viewers((pos.x.div_euclid(16), pos.z.div_euclid(16)));
+        let eid = self.players[digger].eid;
+        inboxes.retain(|pid, mut inbox| {
+            // the digger draws their own cracks
+            if pid != digger && viewers & 1 << pid != 0 {
+                inbox.submit(Response::DestroyStage(eid, pos, stage));
+            }
+            true
+        });
+    }
+    pub(super) fn tick_digging(&mut self, mut inboxes: Inboxes) {
+        for pid in 0..self.players.len() {
+            let digging = match &mut self.players[pid].digging {
+                Some(digging) => digging,
+                None => continue,
+            };
+            let stage = ((self.tick - digging.started) * 10 / digging.ticks).min(9) as u8;
+            if stage != digging.stage {
+                digging.stage = stage;
+                let pos = digging.pos;
+                self.broadcast_cracks(pid, inboxes.reborrow(), pos, stage);
+            }
+        }
+    }
+}
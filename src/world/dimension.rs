@@ -0,0 +1,128 @@
+//! The overworld, the nether and the end: each is a world of its own, with its own chunks,
+//! and a dimension type from the codec that decides how tall it is and whether it has a sky.
+use crate::prelude::*;
+use super::{codec, Chunk, ChunkContent, ChunkLight, Heightmap, Heightmaps};
+use super::{generator::ChunkGenerator, loader};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub const OVERWORLD: usize = 0;
+pub const NETHER: usize = 1;
+pub const END: usize = 2;
+
+pub struct Dimension {
+    /// What it's called in commands and on the wire, like `minecraft:the_nether`
+    pub name: String,
+    /// The name of its dimension type in the codec
+    pub kind: String,
+    pub dimension_type: codec::DimensionType,
+    /// What chunks that have never been saved are made of
+    pub generator: Arc<dyn ChunkGenerator>,
+
+    pub(super) first_free_chunk: u32,
+    pub(super) chunks: Vec<Chunk>,
+    pub(super) index: HashMap<(i32, i32), u32>,
+    /// Chunks that are off being loaded or generated
+    pub(super) loading: HashMap<(i32, i32), loader::Loading>,
+}
+impl Dimension {
+    pub fn new(name: &str, kind: &str, codec: &codec::Codec, generator: Arc<dyn ChunkGenerator>) -> Self {
+        let dimension_type = codec.dimension_type(kind)
+            .unwrap_or_else(|| panic!("there's no dimension type {kind}"))
+            .clone();
+        Self {
+            name: name.to_owned(),
+            kind: kind.to_owned(),
+            dimension_type,
+            generator,
+            first_free_chunk: u32::MAX,
+            chunks: vec![],
+            index: Default::default(),
+            loading: Default::default(),
+        }
+    }
+    pub fn min_y(&self) -> i32 {
+        self.dimension_type.min_y
+    }
+    /// One above the highest block
+    pub fn top(&self) -> i32 {
+        self.dimension_type.min_y + self.dimension_type.height
+    }
+    pub fn sections(&self) -> usize {
+        self.dimension_type.height as usize / 16
+    }
+    pub fn has_skylight(&self) -> bool {
+        self.dimension_type.has_skylight
+    }
+    /// Bitmask of the players that can see the chunk
+    pub(super) fn viewers(&self, chunk: (i32, i32)) -> u32 {
+        self.index.get(&chunk).map_or(0, |i| self.chunks[*i as usize].visible_to)
+    }
+    pub fn chunk_at(&self, x: i32, z: i32) -> &Chunk {
+        let chunk = &self.chunks[self.index[&(x, z)] as usize];
+        assert_ne!(chunk.visible_to, 0);
+        chunk
+    }
+    pub(crate) fn block_at(&self, pos: V3<i32>) -> Option<Block> {
+        let x = pos.x.div_euclid(16);
+        let z = pos.z.div_euclid(16);
+        let chunk = &self.chunks[*self.index.get(&(x, z))? as usize];
+        if !(self.min_y()..self.top()).contains(&pos.y) {
+            return None;
+        }
+        chunk.content.block(pos.x.rem_euclid(16) as usize, pos.y, pos.z.rem_euclid(16) as usize)
+    }
+    pub(super) fn in_world(&self, pos: V3<i32>) -> bool {
+        (self.min_y()..self.top()).contains(&pos.y) && self.index.contains_key(&(pos.x.div_euclid(16), pos.z.div_euclid(16)))
+    }
+    /// Changes a block without telling anyone, and returns the one that was there
+    pub(super) fn set_block(&mut self, pos: V3<i32>, block: Option<Block>) -> Option<Block> {
+        let chunk = &mut self.chunks[self.index[&(pos.x.div_euclid(16), pos.z.div_euclid(16))] as usize];
+        let (x, z) = (pos.x.rem_euclid(16) as usize, pos.z.rem_euclid(16) as usize);
+        let old = chunk.content.set_block(x, pos.y, z, block);
+        chunk.heightmaps.update(&chunk.content, x, pos.y, z, block);
+        chunk.dirty = true;
+        self.relight(pos, old, block);
+        old
+    }
+    /// Makes sure a chunk's saved again, for changes that aren't to its blocks
+    pub(super) fn mark_dirty(&mut self, chunk: (i32, i32)) {
+        if let Some(&idx) = self.index.get(&chunk) {
+            self.chunks[idx as usize].dirty = true;
+        }
+    }
+    /// The y of the first block above the ground, which has to be loaded
+    pub(super) fn surface(&self, x: i32, z: i32) -> i32 {
+        let chunk = &self.chunks[self.index[&(x.div_euclid(16), z.div_euclid(16))] as usize];
+        self.min_y() + chunk.heightmaps.get(Heightmap::MotionBlocking, x.rem_euclid(16) as usize, z.rem_euclid(16) as usize) as i32
+    }
+    /// Puts a chunk in the index, and lets its light out into its neighbours
+    pub(super) fn insert_chunk(&mut self, pos: (i32, i32), content: ChunkContent, heightmaps: Heightmaps, light: ChunkLight, visible_to: u32) {
+        let light = Box::new(light);
+        let chunk = Chunk { next_free_chunk: u32::MAX, visible_to, content, light, heightmaps, dirty: false };
+        let idx = self.first_free_chunk;
+        if idx == u32::MAX {
+            self.index.insert(pos, self.chunks.len() as u32);
+            self.chunks.push(chunk);
+        } else {
+            self.first_free_chunk = self.chunks[idx as usize].next_free_chunk;
+            self.chunks[idx as usize] = chunk;
+            self.index.insert(pos, idx);
+        }
+        self.light_chunk(pos);
+    }
+    /// Forgets about a chunk, unless it's changed since it was saved. Those wait for the
+    /// autosave to write them, so unloading a lot at once can't hold up a tick.
+    /// Returns whether it's gone
+    pub(super) fn evict(&mut self, pos: (i32, i32)) -> bool {
+        let idx = self.index[&pos];
+        let chunk = &mut self.chunks[idx as usize];
+        if chunk.dirty {
+            return false;
+        }
+        chunk.next_free_chunk = self.first_free_chunk;
+        self.first_free_chunk = idx;
+        self.index.remove(&pos);
+        true
+    }
+}
@@ -0,0 +1,294 @@
+//! Everything in the world that isn't a block: players, and the items lying around.
+//!
+//! They're kept in one arena, bucketed by the chunk they're in, so finding what's near
+//! somewhere only looks at the chunks around it. Nobody's told about an entity directly.
+//! Once a tick, `sync_entities` compares who can see each one's chunk with who's been sent
+//! it, and sends the spawns, moves, metadata and removals that make up the difference.
+use crate::prelude::*;
+use super::{items, World};
+use std::collections::{HashMap, VecDeque};
+
+/// How many ticks back we remember where things were
+pub(super) const HISTORY: u32 = 5 * 20;
+
+pub(super) enum Kind {
+    Player { pid: usize, name: Name },
+    Item(items::DroppedItem),
+}
+pub(super) struct Entity {
+    pub eid: i32,
+    pub dimension: usize,
+    pub position: (f64, f64, f64),
+    /// Blocks a tick
+    pub velocity: (f64, f64, f64),
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+    pub kind: Kind,
+    /// Where it's been, and the tick it got there, oldest first. Goes back to the first place
+    /// it was before `HISTORY` ticks ago
+    history: VecDeque<(u32, (f64, f64, f64))>,
+    /// The players it's been spawned for
+    seen_by: u32,
+    moved: bool,
+    /// Set when its metadata needs sending again
+    pub changed: bool,
+}
+impl Entity {
+    pub fn new(eid: i32, dimension: usize, position: (f64, f64, f64), kind: Kind) -> Self {
+        Self {
+            eid, dimension, position, velocity: (0.0, 0.0, 0.0), yaw: 0.0, pitch: 0.0, on_ground: false, kind,
+            history: VecDeque::from([(0, position)]), seen_by: 0, moved: false, changed: false,
+        }
+    }
+    pub fn chunk(&self) -> (i32, i32) {
+        chunk_of(self.position)
+    }
+    /// Where everyone was told it was at the end of `tick`
+    pub fn position_at(&self, tick: u32) -> (f64, f64, f64) {
+        self.history.iter().rev().find(|(t, _)| *t <= tick).or(self.history.front()).map_or(self.position, |(_, p)| *p)
+    }
+    pub fn seen_by(&self, pid: usize) -> bool {
+        self.seen_by & 1 << pid != 0
+    }
+    /// Half its width, and its height. Items don't get in anyone's way
+    pub fn hitbox(&self) -> Option<(f64, f64)> {
+        match self.kind {
+            Kind::Player { .. } => Some((0.3, 1.8)),
+            Kind::Item(_) => None,
+        }
+    }
+    /// Whether it's in the way of a block going at `pos`
+    pub fn collides(&self, pos: V3<i32>) -> bool {
+        let (half, height) = match self.hitbox() {
+            Some(hitbox) => hitbox,
+            None => return false,
+        };
+        let (x, y, z) = self.position;
+        x - half < (pos.x + 1) as f64 && x + half > pos.x as f64
+            && y < (pos.y + 1) as f64 && y + height > pos.y as f64
+            && z - half < (pos.z + 1) as f64 && z + half > pos.z as f64
+    }
+}
+pub(super) fn chunk_of(position: (f64, f64, f64)) -> (i32, i32) {
+    ((position.0 / 16.0).floor() as i32, (position.2 / 16.0).floor() as i32)
+}
+
+pub(super) struct Entities {
+    arena: SlotMap<Entity>,
+    /// Where each eid is in the arena
+    slots: HashMap<i32, usize>,
+    /// The arena slots in each chunk, by dimension and chunk
+    buckets: HashMap<(usize, (i32, i32)), Vec<usize>>,
+    /// Gone, but still to be removed from whoever saw them
+    removed: Vec<Entity>,
+}
+impl Entities {
+    pub fn new() -> Self {
+        Self { arena: SlotMap::new(), slots: HashMap::new(), buckets: HashMap::new(), removed: vec![] }
+    }
+    pub fn add(&mut self, entity: Entity) {
+        let (eid, bucket) = (entity.eid, (entity.dimension, entity.chunk()));
+        let slot = self.arena.insert(entity);
+        self.slots.insert(eid, slot);
+        self.buckets.entry(bucket).or_default().push(slot);
+    }
+    pub fn get(&mut self, eid: i32) -> Option<&mut Entity> {
+        let slot = *self.slots.get(&eid)?;
+        self.arena.get(slot)
+    }
+    pub fn get_ref(&self, eid: i32) -> Option<&Entity> {
+        self.arena.get_ref(*self.slots.get(&eid)?)
+    }
+    /// Takes it out of the world. Whoever could see it is told at the end of the tick
+    pub fn remove(&mut self, eid: i32) {
+        let slot = match self.slots.remove(&eid) {
+            Some(slot) => slot,
+            None => return,
+        };
+        let entity = self.arena.release(slot).unwrap();
+        self.unbucket(slot, (entity.dimension, entity.chunk()));
+        self.removed.push(entity);
+    }
+    fn unbucket(&mut self, slot: usize, bucket: (usize, (i32, i32))) {
+        let slots = self.buckets.get_mut(&bucket).unwrap();
+        slots.retain(|&s| s != slot);
+        if slots.is_empty() {
+            self.buckets.remove(&bucket);
+        }
+    }
+    /// Puts it somewhere else, which might be in another dimension
+    pub fn move_to(&mut self, eid: i32, dimension: usize, position: (f64, f64, f64)) {
+        let slot = match self.slots.get(&eid) {
+            Some(&slot) => slot,
+            None => return,
+        };
+        let entity = self.arena.get(slot).unwrap();
+        if entity.dimension == dimension && entity.position == position {
+            return;
+        }
+        let (old, new) = ((entity.dimension, entity.chunk()), (dimension, chunk_of(position)));
+        entity.dimension = dimension;
+        entity.position = position;
+        entity.moved = true;
+        if old != new {
+            self.unbucket(slot, old);
+            self.buckets.entry(new).or_default().push(slot);
+        }
+    }
+    pub fn in_chunk(&self, dimension: usize, chunk: (i32, i32)) -> impl Iterator<Item = &Entity> + '_ {
+        self.buckets.get(&(dimension, chunk)).into_iter().flatten().map(|&slot| self.arena.get_ref(slot).unwrap())
+    }
+    /// Everything within `radius` blocks of `centre` along each axis
+    pub fn near(&self, dimension: usize, centre: (f64, f64, f64), radius: f64) -> impl Iterator<Item = &Entity> + '_ {
+        let (min, max) = (chunk_of((centre.0 - radius, 0.0, centre.2 - radius)), chunk_of((centre.0 + radius, 0.0, centre.2 + radius)));
+        (min.0..=max.0)
+            .flat_map(move |x| (min.1..=max.1).map(move |z| (x, z)))
+            .flat_map(move |chunk| self.in_chunk(dimension, chunk))
+            .filter(move |e| {
+                (e.position.0 - centre.0).abs() <= radius
+                    && (e.position.1 - centre.1).abs() <= radius
+                    && (e.position.2 - centre.2).abs() <= radius
+            })
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> + '_ {
+        self.arena.iter().map(|(_, e)| e)
+    }
+    /// For when their client's thrown away every entity it knew about, like after a respawn
+    pub fn forget_viewer(&mut self, pid: usize) {
+        for (_, entity) in self.arena.iter_mut() {
+            entity.seen_by &= !(1 << pid);
+        }
+        for entity in &mut self.removed {
+            entity.seen_by &= !(1 << pid);
+        }
+    }
+}
+
+impl World {
+    /// Brings a player's entity up to date with where they are
+    pub(super) fn move_player_entity(&mut self, pid: usize) {
+        let player = &self.players[pid];
+        self.entities.move_to(player.eid, player.dimension, player.position);
+        if let Some(entity) = self.entities.get(player.eid) {
+            if (entity.yaw, entity.pitch) != (player.yaw, player.pitch) || entity.on_ground != player.on_ground {
+                (entity.yaw, entity.pitch, entity.on_ground) = (player.yaw, player.pitch, player.on_ground);
+                entity.moved = true;
+            }
+        }
+    }
+    /// Whether anything's in the way of a block going at `pos`
+    pub(super) fn does_entity_collide(&self, dimension: usize, pos: V3<i32>) -> bool {
+        let centre = (pos.x as f64 + 0.5, pos.y as f64, pos.z as f64 + 0.5);
+        // nothing's taller than 2 blocks, so that's as far away as something can be and still reach in
+        self.entities.near(dimension, centre, 2.0).any(|e| e.collides(pos))
+    }
+    /// Runs whatever each kind of entity does every tick
+    pub(super) fn tick_entities(&mut self, inboxes: Inboxes) {
+        // anything left in a chunk that's been unloaded goes with it. players keep their place
+        // while the chunks around them load
+        let unloaded: Vec<_> = self.entities.buckets.iter()
+            .filter(|((dimension, chunk), _)| !self.dimensions[*dimension].index.contains_key(chunk))
+            .flat_map(|(_, slots)| slots.iter().map(|&slot| self.entities.arena.get_ref(slot).unwrap()))
+            .filter(|e| !matches!(e.kind, Kind::Player { .. }))
+            .map(|e| e.eid)
+            .collect();
+        for eid in unloaded {
+            self.entities.remove(eid);
+        }
+        self.tick_items(inboxes);
+    }
+    /// Tells everyone about the entities that have come into or gone out of their view, and
+    /// the ones they can see that have moved or changed
+    pub(super) fn sync_entities(&mut self, inboxes: Inboxes) {
+        let mut spawned = vec![];
+        let mut despawned = vec![];
+        let mut updated = vec![];
+        for (slot, entity) in self.entities.arena.iter_mut() {
+            let mut want = self.dimensions[entity.dimension].viewers(entity.chunk());
+            if let Kind::Player { pid, .. } = entity.kind {
+                want &= !(1 << pid);
+            }
+            let (new, gone, kept) = (want & !entity.seen_by, entity.seen_by & !want, want & entity.seen_by);
+            if new != 0 {
+                spawned.push((new, slot));
+            }
+            if gone != 0 {
+                despawned.push((gone, entity.eid, player_name(entity)));
+            }
+            if kept != 0 && (entity.moved || entity.changed) {
+                updated.push((kept, slot, entity.moved, entity.changed));
+            }
+            if entity.moved {
+                entity.history.push_back((self.tick, entity.position));
+                while entity.history.len() > 1 && entity.history[1].0 + HISTORY <= self.tick {
+                    entity.history.pop_front();
+                }
+            }
+            entity.seen_by = want;
+            entity.moved = false;
+            entity.changed = false;
+        }
+        for entity in self.entities.removed.drain(..).filter(|e| e.seen_by != 0) {
+            despawned.push((entity.seen_by, entity.eid, player_name(&entity)));
+        }
+        if spawned.is_empty() && despawned.is_empty() && updated.is_empty() {
+            return;
+        }
+        let entities = &self.entities;
+        inboxes.retain(|pid, mut inbox| {
+            let eids: Vec<_> = despawned.iter().filter(|(mask, ..)| mask & 1 << pid != 0).map(|(_, eid, _)| *eid).collect();
+            if !eids.is_empty() {
+                inbox.submit(Response::RemoveEntities(eids));
+            }
+            for (_, _, name) in despawned.iter().filter(|(mask, ..)| mask & 1 << pid != 0) {
+                if let Some(name) = name {
+                    inbox.submit(Response::RemovePlayerInfo(*name));
+                }
+            }
+            for &(_, slot) in spawned.iter().filter(|(mask, _)| mask & 1 << pid != 0) {
+                self.spawn_entity(entities.arena.get_ref(slot).unwrap(), inbox.reborrow());
+            }
+            for &(_, slot, moved, changed) in updated.iter().filter(|(mask, ..)| mask & 1 << pid != 0) {
+                let entity = entities.arena.get_ref(slot).unwrap();
+                if moved {
+                    inbox.submit(Response::TeleportEntity(entity.eid, entity.position, entity.yaw, entity.pitch, entity.on_ground));
+                    match entity.kind {
+                        Kind::Player { .. } => inbox.submit(Response::HeadRotation(entity.eid, entity.yaw)),
+                        // so their client can carry on moving it until the next update
+                        _ => inbox.submit(Response::EntityVelocity(entity.eid, entity.velocity)),
+                    }
+                }
+                if changed {
+                    send_metadata(entity, inbox.reborrow());
+                }
+            }
+            true
+        });
+    }
+    fn spawn_entity(&self, entity: &Entity, mut inbox: Inbox) {
+        match &entity.kind {
+            Kind::Player { pid, name } => {
+                // the client won't spawn a player it hasn't got in its tab list, so they're
+                // only listed while they're in view
+                inbox.submit(Response::AddPlayerInfo(*name, self.players[*pid].gamemode));
+                inbox.submit(Response::SpawnPlayer(entity.eid, *name, entity.position, entity.yaw, entity.pitch));
+                inbox.submit(Response::HeadRotation(entity.eid, entity.yaw));
+            }
+            Kind::Item(_) => inbox.submit(Response::SpawnItem(entity.eid, entity.position, entity.velocity)),
+        }
+        send_metadata(entity, inbox);
+    }
+}
+fn send_metadata(entity: &Entity, mut inbox: Inbox) {
+    match &entity.kind {
+        Kind::Player { .. } => {}
+        Kind::Item(item) => inbox.submit(Response::ItemMetadata(entity.eid, item.stack.clone())),
+    }
+}
+fn player_name(entity: &Entity) -> Option<Name> {
+    match entity.kind {
+        Kind::Player { name, .. } => Some(name),
+        _ => None,
+    }
+}
@@ -0,0 +1,282 @@
+//! Making chunks that have never been saved.
+//!
+//! Each dimension has a `ChunkGenerator`, which the chunk loaders run when they can't find
+//! a chunk on disk.
+use crate::prelude::*;
+use super::{codec, ChunkContent};
+use std::sync::Arc;
+
+pub trait ChunkGenerator: Send + Sync {
+    /// The blocks and biomes of a chunk, `sections` sections tall starting at `min_y`
+    fn generate(&self, chunk: (i32, i32), min_y: i32, sections: usize) -> ChunkContent;
+    /// Whether clients should draw the flat world horizon, rather than fog down to the void
+    fn superflat(&self) -> bool {
+        false
+    }
+}
+
+/// The same layers of blocks everywhere, like vanilla's superflat worlds
+pub struct Superflat {
+    /// From the bottom up
+    layers: Vec<Option<Block>>,
+    biome: u16,
+}
+impl Superflat {
+    /// Reads vanilla's layer syntax, like `minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains`
+    pub fn parse(s: &str, codec: &codec::Codec) -> Result<Self, String> {
+        let mut parts = s.split(';');
+        let mut layers = vec![];
+        for layer in parts.next().unwrap_or("").split(',').map(str::trim).filter(|l| !l.is_empty()) {
+            let (count, name) = match layer.split_once('*') {
+                Some((count, name)) => (count.trim().parse::<usize>().map_err(|_| format!("bad layer count in {layer:?}"))?, name.trim()),
+                None => (1, layer),
+            };
+            let block = match name {
+                "air" | "minecraft:air" => None,
+                _ => Some(Block::parse(name).ok_or_else(|| format!("unknown block {name:?}"))?),
+            };
+            layers.extend(std::iter::repeat_n(block, count));
+        }
+        let biome = match parts.next().map(str::trim).filter(|b| !b.is_empty()) {
+            Some(name) => {
+                let name = if name.contains(':') { name.to_owned() } else { format!("minecraft:{name}") };
+                codec.biome_id(&name).ok_or_else(|| format!("unknown biome {name:?}"))?
+            }
+            None => codec.plains(),
+        };
+        Ok(Self { layers, biome })
+    }
+}
+impl ChunkGenerator for Superflat {
+    fn generate(&self, _: (i32, i32), min_y: i32, sections: usize) -> ChunkContent {
+        let mut content = ChunkContent::empty(min_y, sections, self.biome);
+        for (y, block) in self.layers.iter().take(sections * 16).enumerate() {
+            if block.is_none() {
+                continue;
+            }
+            for x in 0..16 {
+                for z in 0..16 {
+                    content.set_block(x, min_y + y as i32, z, *block);
+                }
+            }
+        }
+        content
+    }
+    fn superflat(&self) -> bool {
+        true
+    }
+}
+
+/// Which world the noise generator is making terrain for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Terrain {
+    /// Hills and oceans of stone, with grass on top
+    Overworld,
+    /// Netherrack caverns between a bedrock floor and roof, with lava lakes
+    Nether,
+    /// One big island of end stone in the middle, and little ones far out from it
+    End,
+}
+
+/// Terrain shaped by Perlin noise, which comes out the same every time for the same seed
+pub struct Noise {
+    terrain: Terrain,
+    seed: u64,
+    perlin: Perlin,
+    biome: u16,
+}
+const SEA_LEVEL: i32 = 62;
+const NETHER_LAVA_LEVEL: i32 = 31;
+impl Noise {
+    pub fn new(seed: i64, terrain: Terrain, codec: &codec::Codec) -> Self {
+        let biome = match terrain {
+            Terrain::Overworld => "minecraft:plains",
+            Terrain::Nether => "minecraft:nether_wastes",
+            Terrain::End => "minecraft:the_end",
+        };
+        Self {
+            terrain,
+            seed: seed as u64,
+            perlin: Perlin::new(seed as u64),
+            biome: codec.biome_id(biome).unwrap_or_else(|| codec.plains()),
+        }
+    }
+    /// Whether there's bedrock at some height above the floor (or below the roof), which
+    /// gets less likely the further away it is, like vanilla's
+    fn bedrock(&self, x: i32, z: i32, above: i32) -> bool {
+        above == 0 || (above < 5 && (hash(self.seed, x, above, z) % 5) as i32 >= above)
+    }
+    fn overworld(&self, content: &mut ChunkContent, (cx, cz): (i32, i32), min_y: i32, top: i32) {
+        for x in 0..16 {
+            for z in 0..16 {
+                let (bx, bz) = (cx * 16 + x as i32, cz * 16 + z as i32);
+                let hills = self.perlin.octaves(bx as f64 / 256.0, 0.0, bz as f64 / 256.0, 5);
+                let bumps = self.perlin.get(bx as f64 / 32.0, 100.0, bz as f64 / 32.0);
+                let height = ((SEA_LEVEL + 2) as f64 + hills * 40.0 + bumps * 3.0) as i32;
+                let height = height.clamp(min_y + 1, top - 1);
+                let underwater = height < SEA_LEVEL;
+                for y in min_y..=height.max(SEA_LEVEL) {
+                    let block = if y > height {
+                        Block::WATER
+                    } else if self.bedrock(bx, bz, y - min_y) {
+                        Block::BEDROCK
+                    } else if y == height {
+                        if underwater { Block::SAND } else { Block::GRASS_BLOCK }
+                    } else if y > height - 4 {
+                        if underwater { Block::SAND } else { Block::DIRT }
+                    } else {
+                        Block::STONE
+                    };
+                    content.set_block(x, y, z, Some(block));
+                }
+            }
+        }
+    }
+    fn nether(&self, content: &mut ChunkContent, (cx, cz): (i32, i32), min_y: i32) {
+        // the roof's at 128, even though the dimension goes higher
+        let roof = min_y + 127;
+        for x in 0..16 {
+            for z in 0..16 {
+                let (bx, bz) = (cx * 16 + x as i32, cz * 16 + z as i32);
+                for y in min_y..=roof {
+                    let block = if self.bedrock(bx, bz, y - min_y) || self.bedrock(bx, bz, roof - y) {
+                        Some(Block::BEDROCK)
+                    } else {
+                        let caves = self.perlin.octaves(bx as f64 / 96.0, y as f64 / 48.0, bz as f64 / 96.0, 4);
+                        // solid towards the floor and roof, and open in between
+                        let middle = (y - min_y - 64) as f64 / 64.0;
+                        if caves + middle * middle * 0.8 - 0.2 > 0.0 {
+                            Some(Block::NETHERRACK)
+                        } else if y <= min_y + NETHER_LAVA_LEVEL {
+                            Some(Block::LAVA)
+                        } else {
+                            None
+                        }
+                    };
+                    if block.is_some() {
+                        content.set_block(x, y, z, block);
+                    }
+                }
+            }
+        }
+    }
+    fn end(&self, content: &mut ChunkContent, (cx, cz): (i32, i32), min_y: i32, top: i32) {
+        for x in 0..16 {
+            for z in 0..16 {
+                let (bx, bz) = (cx * 16 + x as i32, cz * 16 + z as i32);
+                let (fx, fz) = (bx as f64, bz as f64);
+                let edge = self.perlin.get(fx / 32.0, 0.0, fz / 32.0);
+                let distance = (fx * fx + fz * fz).sqrt();
+                let thickness = if distance < 400.0 {
+                    // the main island, which tapers off around 80 blocks out
+                    (1.0 - distance / (80.0 + edge * 16.0)) * 40.0
+                } else {
+                    (self.perlin.octaves(fx / 128.0, 50.0, fz / 128.0, 3) - 0.35) * 60.0
+                };
+                if thickness <= 0.0 {
+                    continue;
+                }
+                let surface = (min_y + 60 + (edge * 4.0) as i32).min(top - 1);
+                for y in (surface - thickness as i32).max(min_y)..=surface {
+                    content.set_block(x, y, z, Some(Block::END_STONE));
+                }
+            }
+        }
+    }
+}
+impl ChunkGenerator for Noise {
+    fn generate(&self, chunk: (i32, i32), min_y: i32, sections: usize) -> ChunkContent {
+        let mut content = ChunkContent::empty(min_y, sections, self.biome);
+        let top = min_y + sections as i32 * 16;
+        match self.terrain {
+            Terrain::Overworld => self.overworld(&mut content, chunk, min_y, top),
+            Terrain::Nether => self.nether(&mut content, chunk, min_y),
+            Terrain::End => self.end(&mut content, chunk, min_y, top),
+        }
+        content
+    }
+}
+
+/// What each dimension's made of when nobody says otherwise: the flat stone the overworld
+/// and nether have always been, and an end with an island to land on
+pub fn default_generator(dimension: &str, codec: &codec::Codec) -> Arc<dyn ChunkGenerator> {
+    let layers = match dimension {
+        "minecraft:the_end" => return Arc::new(Noise::new(0, Terrain::End, codec)),
+        "minecraft:the_nether" => "64*minecraft:netherrack;minecraft:nether_wastes",
+        _ => "64*minecraft:stone;minecraft:plains",
+    };
+    Arc::new(Superflat::parse(layers, codec).unwrap())
+}
+
+/// Vanilla's way of turning a seed someone typed in into a number: it's used as is if it
+/// is one, and hashed like a java string otherwise
+pub fn parse_seed(s: &str) -> i64 {
+    s.trim().parse().unwrap_or_else(|_| {
+        s.encode_utf16().fold(0i32, |h, c| h.wrapping_mul(31).wrapping_add(c as i32)) as i64
+    })
+}
+
+pub(super) fn splitmix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+/// A random number for a block position, the same each time
+fn hash(seed: u64, x: i32, y: i32, z: i32) -> u64 {
+    let mut state = seed ^ (x as u64).wrapping_mul(0x2545F4914F6CDD1D) ^ (y as u64) << 24 ^ (z as u64).wrapping_mul(0x9E3779B97F4A7C15);
+    splitmix(&mut state)
+}
+
+/// Ken Perlin's improved noise, with the permutation shuffled by the seed
+struct Perlin {
+    perm: [u8; 512],
+}
+impl Perlin {
+    fn new(seed: u64) -> Self {
+        let mut state = seed;
+        let mut table: [u8; 256] = std::array::from_fn(|i| i as u8);
+        for i in (1..256).rev() {
+            table.swap(i, (splitmix(&mut state) % (i as u64 + 1)) as usize);
+        }
+        Self { perm: std::array::from_fn(|i| table[i % 256]) }
+    }
+    /// Smooth noise, roughly between -1 and 1
+    fn get(&self, x: f64, y: f64, z: f64) -> f64 {
+        fn fade(t: f64) -> f64 {
+            t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+        }
+        fn lerp(t: f64, a: f64, b: f64) -> f64 {
+            a + t * (b - a)
+        }
+        fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+            let h = hash & 15;
+            let u = if h < 8 { x } else { y };
+            let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+            (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+        }
+        let p = &self.perm;
+        let (xi, yi, zi) = (x.floor() as i64 as u8 as usize, y.floor() as i64 as u8 as usize, z.floor() as i64 as u8 as usize);
+        let (x, y, z) = (x - x.floor(), y - y.floor(), z - z.floor());
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        let a = p[xi] as usize + yi;
+        let (aa, ab) = (p[a] as usize + zi, p[a + 1] as usize + zi);
+        let b = p[xi + 1] as usize + yi;
+        let (ba, bb) = (p[b] as usize + zi, p[b + 1] as usize + zi);
+        lerp(w,
+            lerp(v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1.0, y, z)),
+                lerp(u, grad(p[ab], x, y - 1.0, z), grad(p[bb], x - 1.0, y - 1.0, z))),
+            lerp(v,
+                lerp(u, grad(p[aa + 1], x, y, z - 1.0), grad(p[ba + 1], x - 1.0, y, z - 1.0)),
+                lerp(u, grad(p[ab + 1], x, y - 1.0, z - 1.0), grad(p[bb + 1], x - 1.0, y - 1.0, z - 1.0))))
+    }
+    /// Layers of noise at doubling frequencies and halving strengths, for detail on top of the shape
+    fn octaves(&self, x: f64, y: f64, z: f64, octaves: u32) -> f64 {
+        (0..octaves).map(|i| {
+            let scale = (1 << i) as f64;
+            self.get(x * scale, y * scale, z * scale) / scale
+        }).sum()
+    }
+}
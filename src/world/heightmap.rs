@@ -0,0 +1,146 @@
+//! The height of the highest interesting block in each column of a chunk.
+//!
+//! Heights are stored the way vanilla does: one above the highest block, counted from
+//! the bottom of the world, so an empty column is 0.
+use crate::prelude::*;
+use super::ChunkContent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heightmap {
+    /// Anything that isn't air
+    WorldSurface,
+    /// Anything with a collision box, or with water or lava in it
+    MotionBlocking,
+    /// Anything with a collision box
+    OceanFloor,
+}
+impl Heightmap {
+    pub const ALL: [Heightmap; 3] = [Heightmap::WorldSurface, Heightmap::MotionBlocking, Heightmap::OceanFloor];
+    pub fn name(self) -> &'static str {
+        match self {
+            Heightmap::WorldSurface => "WORLD_SURFACE",
+            Heightmap::MotionBlocking => "MOTION_BLOCKING",
+            Heightmap::OceanFloor => "OCEAN_FLOOR",
+        }
+    }
+    fn counts(self, block: Option<Block>) -> bool {
+        let block = match block {
+            Some(block) if !block.is_air() => block,
+            _ => return false,
+        };
+        match self {
+            Heightmap::WorldSurface => true,
+            Heightmap::MotionBlocking => !block.collision_boxes().is_empty() || has_fluid(block),
+            Heightmap::OceanFloor => !block.collision_boxes().is_empty(),
+        }
+    }
+}
+fn has_fluid(block: Block) -> bool {
+    use BlockKind::*;
+    matches!(block.kind(), Water | Lava | BubbleColumn | Kelp | KelpPlant | Seagrass | TallSeagrass)
+        || block.get() == Some(props::Waterlogged(true))
+}
+
+#[derive(Clone)]
+pub struct Heightmaps {
+    heights: [[u16; 256]; 3],
+    /// Bits per packed height, enough for the whole height of the world (9 in the overworld)
+    bits: usize,
+}
+impl Heightmaps {
+    pub fn empty(sections: usize) -> Self {
+        Self { heights: [[0; 256]; 3], bits: (usize::BITS - (sections * 16).leading_zeros()) as usize }
+    }
+    /// Works out every heightmap for a chunk from scratch
+    pub fn compute(content: &ChunkContent) -> Self {
+        let mut heights = Self::empty(content.sections());
+        for (i, kind) in Heightmap::ALL.into_iter().enumerate() {
+            for column in 0..256 {
+                heights.heights[i][column] = Self::scan(content, kind, column, content.min_y() + content.sections() as i32 * 16);
+            }
+        }
+        heights
+    }
+    /// The height of the column when looking down from below `from`
+    fn scan(content: &ChunkContent, kind: Heightmap, column: usize, from: i32) -> u16 {
+        (content.min_y()..from).rev()
+            .find(|&y| kind.counts(content.block(column % 16, y, column / 16)))
+            .map_or(0, |y| (y - content.min_y() + 1) as u16)
+    }
+    pub fn get(&self, kind: Heightmap, x: usize, z: usize) -> u16 {
+        self.heights[kind as usize][z * 16 + x]
+    }
+    /// Keeps the heightmaps right after the block at `y` in the column changed to `block`.
+    /// `content` should already have the new block in it
+    pub fn update(&mut self, content: &ChunkContent, x: usize, y: i32, z: usize, block: Option<Block>) {
+        let column = z * 16 + x;
+        let height = (y - content.min_y() + 1) as u16;
+        for (i, kind) in Heightmap::ALL.into_iter().enumerate() {
+            let top = &mut self.heights[i][column];
+            if kind.counts(block) {
+                *top = (*top).max(height);
+            } else if *top == height {
+                *top = Self::scan(content, kind, column, y);
+            }
+        }
+    }
+    /// The heightmap as longs, like the chunk packet and region files want it
+    pub fn pack(&self, kind: Heightmap) -> Vec<i64> {
+        let per_long = 64 / self.bits;
+        let mut packed = vec![0; 256usize.div_ceil(per_long)];
+        for (i, height) in self.heights[kind as usize].iter().enumerate() {
+            packed[i / per_long] |= (*height as i64) << (i % per_long * self.bits);
+        }
+        packed
+    }
+    /// Reads back a heightmap from [Self::pack]. None if it's the wrong size
+    pub fn unpack(&mut self, kind: Heightmap, packed: &[i64]) -> Option<()> {
+        let per_long = 64 / self.bits;
+        if packed.len() != 256usize.div_ceil(per_long) {
+            return None;
+        }
+        for (i, height) in self.heights[kind as usize].iter_mut().enumerate() {
+            *height = (packed[i / per_long] as u64 >> (i % per_long * self.bits) & ((1 << self.bits) - 1)) as u16;
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_round_trips() {
+        // the overworld's 384 blocks tall, the nether 256, and both need 9 bits
+        for sections in [24, 16] {
+            let mut heights = Heightmaps::empty(sections);
+            for (i, height) in heights.heights[0].iter_mut().enumerate() {
+                *height = (i * 37 % (sections * 16 + 1)) as u16;
+            }
+            heights.heights[0][255] = sections as u16 * 16;
+            let packed = heights.pack(Heightmap::WorldSurface);
+            assert_eq!(packed.len(), 37);
+            let mut unpacked = Heightmaps::empty(sections);
+            unpacked.unpack(Heightmap::WorldSurface, &packed).unwrap();
+            assert_eq!(unpacked.heights[0], heights.heights[0]);
+            assert!(unpacked.unpack(Heightmap::WorldSurface, &packed[1..]).is_none());
+        }
+    }
+
+    #[test]
+    fn update_agrees_with_compute() {
+        let stone = Some(crate::registry::BlockInfo::from_name("stone").unwrap().default_state());
+        let mut content = ChunkContent::empty(-64, 24, 0);
+        let mut heights = Heightmaps::compute(&content);
+        for (y, block) in [(10, stone), (40, stone), (40, None), (-64, stone), (10, None)] {
+            content.set_block(3, y, 5, block);
+            heights.update(&content, 3, y, 5, block);
+        }
+        assert_eq!(heights.get(Heightmap::WorldSurface, 3, 5), 1);
+        let computed = Heightmaps::compute(&content);
+        for kind in Heightmap::ALL {
+            assert_eq!(heights.pack(kind), computed.pack(kind));
+        }
+    }
+}
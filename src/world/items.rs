@@ -0,0 +1,305 @@
+//! Items lying around, whether a player threw them or a block dropped them.
+//!
+//! They fall and slide like vanilla's, clump together with identical stacks close by, and
+//! go to whoever walks over them once they've been around a moment. Five minutes after
+//! they're dropped they're gone. They're saved with the chunk they're in.
+use crate::prelude::*;
+use super::entity::{chunk_of, Entities, Entity, Kind};
+use super::generator::splitmix;
+use super::physics::{self, Body};
+use super::{World, Inventory, HOTBAR, OFFHAND};
+
+const BODY: Body = Body { half_width: 0.125, height: 0.25 };
+/// Ticks before someone can pick up what they've just thrown
+const THROWN_DELAY: i16 = 40;
+/// and what a block's just dropped
+const DROPPED_DELAY: i16 = 10;
+/// Ticks an item lasts, which is five minutes
+const LIFETIME: i16 = 6000;
+const GRAVITY: f64 = 0.04;
+/// How much speed an item keeps each tick in the air
+const DRAG: f64 = 0.98;
+/// How far below the bottom of the world things fall before they're gone
+const VOID_DEPTH: f64 = 64.0;
+
+pub(super) struct DroppedItem {
+    pub stack: ItemStack,
+    /// Ticks until it can be picked up
+    pickup_delay: i16,
+    /// Ticks it's been around
+    age: i16,
+}
+impl DroppedItem {
+    /// Whether it can take in any more items
+    fn can_merge(&self) -> bool {
+        self.age < LIFETIME && self.stack.count < self.stack.item.max_stack()
+    }
+}
+/// An item as it's saved in a chunk, like vanilla's item entities
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct SavedItem {
+    id: String,
+    #[serde(rename = "Pos")]
+    position: [f64; 3],
+    #[serde(rename = "Motion")]
+    motion: [f64; 3],
+    #[serde(rename = "OnGround", default)]
+    on_ground: bool,
+    #[serde(rename = "Age", default)]
+    age: i16,
+    #[serde(rename = "PickupDelay", default)]
+    pickup_delay: i16,
+    #[serde(rename = "Item")]
+    stack: ItemStack,
+}
+impl SavedItem {
+    pub const ID: &'static str = "minecraft:item";
+}
+impl Entities {
+    /// The items in a chunk, ready to be saved along with it
+    pub fn saved_items(&self, dimension: usize, chunk: (i32, i32)) -> Vec<SavedItem> {
+        self.in_chunk(dimension, chunk).filter_map(|entity| match &entity.kind {
+            Kind::Item(item) => Some(SavedItem {
+                id: SavedItem::ID.to_owned(),
+                position: [entity.position.0, entity.position.1, entity.position.2],
+                motion: [entity.velocity.0, entity.velocity.1, entity.velocity.2],
+                on_ground: entity.on_ground,
+                age: item.age,
+                pickup_delay: item.pickup_delay,
+                stack: item.stack.clone(),
+            }),
+            _ => None,
+        }).collect()
+    }
+}
+
+/// Puts as much of `stack` as fits into the inventory, returning the slots that changed
+pub(super) fn give(inventory: &mut Inventory, stack: &mut ItemStack) -> Vec<usize> {
+    let order = (HOTBAR..OFFHAND).chain(9..HOTBAR);
+    let max = stack.item.max_stack();
+    let mut changed = vec![];
+    for slot in order.clone() {
+        match &mut inventory[slot] {
+            Some(existing) if stack.count > 0 && existing.stacks_with(stack) && existing.count < max => {
+                let n = stack.count.min(max - existing.count);
+                existing.count += n;
+                stack.count -= n;
+                changed.push(slot);
+            }
+            _ => {}
+        }
+    }
+    for slot in order {
+        if stack.count > 0 && inventory[slot].is_none() {
+            let n = stack.count.min(max);
+            inventory[slot] = Some(ItemStack { count: n, ..stack.clone() });
+            stack.count -= n;
+            changed.push(slot);
+        }
+    }
+    changed
+}
+fn in_pickup_range(player: (f64, f64, f64), item: (f64, f64, f64)) -> bool {
+    (player.0 - item.0).abs() < 1.3
+        && (-0.5..2.3).contains(&(item.1 - player.1))
+        && (player.2 - item.2).abs() < 1.3
+}
+/// Somewhere in `-spread..spread`
+fn jitter(rng: &mut u64, spread: f64) -> f64 {
+    (splitmix(rng) >> 11) as f64 / (1u64 << 53) as f64 * 2.0 * spread - spread
+}
+
+impl World {
+    /// Drops an item out of a block at `position`, popping it up and off to one side
+    pub(super) fn drop_item(&mut self, dimension: usize, position: (f64, f64, f64), stack: ItemStack) {
+        let mut rng = self.next_eid as u64 ^ ((self.tick as u64) << 32);
+        let position = (position.0 + jitter(&mut rng, 0.25), position.1 + jitter(&mut rng, 0.25), position.2 + jitter(&mut rng, 0.25));
+        let motion = (jitter(&mut rng, 0.1), 0.2, jitter(&mut rng, 0.1));
+        self.spawn_item(dimension, position, motion, stack, DROPPED_DELAY);
+    }
+    /// Throws an item from a player's eyes the way they're facing, like vanilla does
+    pub(super) fn throw_item(&mut self, pid: usize, stack: ItemStack) {
+        let player = &self.players[pid];
+        let (yaw, pitch) = (player.yaw.to_radians() as f64, player.pitch.to_radians() as f64);
+        let mut rng = self.next_eid as u64 ^ ((self.tick as u64) << 32);
+        let (angle, spread) = (jitter(&mut rng, std::f64::consts::PI), (jitter(&mut rng, 0.5) + 0.5) * 0.02);
+        let motion = (
+            -yaw.sin() * pitch.cos() * 0.3 + angle.cos() * spread,
+            -pitch.sin() * 0.3 + 0.1 + jitter(&mut rng, 0.1),
+            yaw.cos() * pitch.cos() * 0.3 + angle.sin() * spread,
+        );
+        let position = (player.position.0, player.position.1 + 1.62 - 0.3, player.position.2);
+        self.spawn_item(player.dimension, position, motion, stack, THROWN_DELAY);
+    }
+    fn spawn_item(&mut self, dimension: usize, position: (f64, f64, f64), motion: (f64, f64, f64), stack: ItemStack, pickup_delay: i16) {
+        let eid = self.new_eid();
+        let mut entity = Entity::new(eid, dimension, position, Kind::Item(DroppedItem { stack, pickup_delay, age: 0 }));
+        entity.velocity = motion;
+        self.dimensions[dimension].mark_dirty(entity.chunk());
+        self.entities.add(entity);
+    }
+    /// Puts the items that were saved in a chunk back in the world
+    pub(super) fn load_items(&mut self, dimension: usize, items: Vec<SavedItem>) {
+        for SavedItem { position: [x, y, z], motion: [dx, dy, dz], on_ground, age, pickup_delay, stack, .. } in items {
+            let eid = self.new_eid();
+            let mut entity = Entity::new(eid, dimension, (x, y, z), Kind::Item(DroppedItem { stack, pickup_delay, age }));
+            entity.velocity = (dx, dy, dz);
+            entity.on_ground = on_ground;
+            self.entities.add(entity);
+        }
+    }
+    /// Q, or ctrl+Q for the whole stack
+    pub(crate) fn drop_held(&mut self, pid: usize, mut inboxes: Inboxes, whole_stack: bool) {
+        let player = &mut self.players[pid];
+        let slot = HOTBAR + player.selected_item as usize;
+        let held = match &mut player.inventory[slot] {
+            Some(held) => held,
+            None => return,
+        };
+        let count = if whole_stack { held.count } else { 1 };
+        let thrown = ItemStack { count, ..held.clone() };
+        held.count -= count;
+        if held.count == 0 {
+            player.inventory[slot] = None;
+        }
+        let left = player.inventory[slot].clone();
+        inboxes.get(pid).unwrap().submit(Response::SetInventorySlot(slot as u16, left, 0));
+        self.throw_item(pid, thrown);
+    }
+    pub(super) fn tick_items(&mut self, inboxes: Inboxes) {
+        let eids: Vec<_> = self.entities.iter().filter(|e| matches!(e.kind, Kind::Item(_))).map(|e| e.eid).collect();
+        let mut moved = vec![];
+        for &eid in &eids {
+            let entity = self.entities.get(eid).unwrap();
+            let item = match &mut entity.kind {
+                Kind::Item(item) => item,
+                _ => continue,
+            };
+            item.pickup_delay = (item.pickup_delay - 1).max(0);
+            item.age += 1;
+            let dimension = &self.dimensions[entity.dimension];
+            if item.age >= LIFETIME || entity.position.1 < dimension.min_y() as f64 - VOID_DEPTH {
+                moved.push((eid, entity.dimension, entity.position, None));
+                continue;
+            }
+            let mut motion = entity.velocity;
+            motion.1 -= GRAVITY;
+            // items that have come to rest only check they're still on something every few ticks
+            let resting = entity.on_ground && motion.0 * motion.0 + motion.2 * motion.2 <= 1e-5;
+            if resting && !self.tick.wrapping_add(eid as u32).is_multiple_of(4) {
+                entity.velocity = motion;
+                continue;
+            }
+            let travelled = BODY.travel(dimension, entity.position, motion);
+            let friction = match travelled.on_ground {
+                true => physics::slipperiness(physics::ground_under(dimension, travelled.position)) * DRAG,
+                false => DRAG,
+            };
+            let (dx, dy, dz) = travelled.motion;
+            entity.velocity = (dx * friction, dy * DRAG * if travelled.on_ground { -0.5 } else { 1.0 }, dz * friction);
+            entity.on_ground = travelled.on_ground;
+            if travelled.position != entity.position {
+                moved.push((eid, entity.dimension, entity.position, Some(travelled.position)));
+            }
+        }
+        for (eid, dimension, from, to) in moved {
+            self.dimensions[dimension].mark_dirty(chunk_of(from));
+            match to {
+                Some(to) => {
+                    self.dimensions[dimension].mark_dirty(chunk_of(to));
+                    self.entities.move_to(eid, dimension, to);
+                }
+                None => self.entities.remove(eid),
+            }
+        }
+        // moving items look for others to join every other tick, and still ones every other second
+        for &eid in &eids {
+            let still = self.entities.get(eid).is_some_and(|e| e.on_ground && e.velocity.0.abs() + e.velocity.2.abs() < 1e-3);
+            if self.tick.wrapping_add(eid as u32).is_multiple_of(if still { 40 } else { 2 }) {
+                self.merge_item(eid);
+            }
+        }
+        self.pick_up_items(inboxes);
+    }
+    /// Joins an item with an identical one close by, the smaller going into the bigger
+    fn merge_item(&mut self, eid: i32) {
+        let (dimension, position, stack) = match self.entities.get(eid) {
+            Some(Entity { kind: Kind::Item(item), dimension, position, .. }) if item.can_merge() => (*dimension, *position, item.stack.clone()),
+            _ => return,
+        };
+        let other = self.entities.near(dimension, position, 0.75).find(|e| {
+            let fits = |item: &DroppedItem| item.can_merge() && item.stack.stacks_with(&stack)
+                && item.stack.count + stack.count <= stack.item.max_stack();
+            e.eid != eid && (e.position.1 - position.1).abs() <= BODY.height && matches!(&e.kind, Kind::Item(item) if fits(item))
+        });
+        let (other, other_count) = match other {
+            Some(Entity { eid, kind: Kind::Item(item), .. }) => (*eid, item.stack.count),
+            _ => return,
+        };
+        let (into, from) = if other_count < stack.count { (eid, other) } else { (other, eid) };
+        let from_entity = self.entities.get(from).unwrap();
+        let chunk = from_entity.chunk();
+        let (count, pickup_delay, age) = match &from_entity.kind {
+            Kind::Item(item) => (item.stack.count, item.pickup_delay, item.age),
+            _ => return,
+        };
+        let entity = self.entities.get(into).unwrap();
+        if let Kind::Item(item) = &mut entity.kind {
+            item.stack.count += count;
+            item.pickup_delay = item.pickup_delay.max(pickup_delay);
+            item.age = item.age.min(age);
+            entity.changed = true;
+        }
+        self.entities.remove(from);
+        self.dimensions[dimension].mark_dirty(chunk);
+    }
+    fn pick_up_items(&mut self, mut inboxes: Inboxes) {
+        let (players, entities) = (&mut self.players, &mut self.entities);
+        let mut picked_up = vec![];
+        inboxes.reborrow().retain(|pid, mut inbox| {
+            let player = &mut players[pid];
+            // the dead can't pick their things back up from the death screen
+            if player.lobby || player.vitals.dead() {
+                return true;
+            }
+            let nearby: Vec<_> = entities.near(player.dimension, player.position, 2.5)
+                .filter(|e| matches!(e.kind, Kind::Item(_)))
+                .map(|e| e.eid)
+                .collect();
+            for eid in nearby {
+                let entity = entities.get(eid).unwrap();
+                let (dimension, chunk, position) = (entity.dimension, entity.chunk(), entity.position);
+                let item = match &mut entity.kind {
+                    Kind::Item(item) if item.pickup_delay == 0 && item.stack.count > 0 => item,
+                    _ => continue,
+                };
+                if !in_pickup_range(player.position, position) {
+                    continue;
+                }
+                let count = item.stack.count;
+                for slot in give(&mut player.inventory, &mut item.stack) {
+                    inbox.submit(Response::SetInventorySlot(slot as u16, player.inventory[slot].clone(), 0));
+                }
+                if item.stack.count != count {
+                    picked_up.push((dimension, chunk, eid, player.eid, count - item.stack.count, item.stack.count == 0));
+                    entity.changed = true;
+                }
+            }
+            true
+        });
+        for (dimension, chunk, eid, collector, count, empty) in picked_up {
+            let viewers = self.dimensions[dimension].viewers(chunk);
+            inboxes.reborrow().retain(|pid, mut inbox| {
+                if viewers & 1 << pid != 0 {
+                    inbox.submit(Response::PickupItem(eid, collector, count));
+                }
+                true
+            });
+            self.dimensions[dimension].mark_dirty(chunk);
+            // whoever could see it is told it's gone once the tick's done
+            if empty {
+                self.entities.remove(eid);
+            }
+        }
+    }
+}
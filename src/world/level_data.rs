@@ -0,0 +1,251 @@
+//! `level.dat`: everything about a world that isn't in its chunks or its players.
+//!
+//! It's kept in vanilla's layout, and whatever we don't use is carried along untouched, so
+//! vanilla can still open worlds we've saved.
+use crate::prelude::*;
+use super::{Level, World, OVERWORLD};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// The version 1.19 writes
+const DATA_VERSION: i32 = 3105;
+/// The rules vanilla 1.19 has, and what they start out as
+const GAME_RULES: &[(&str, &str)] = &[
+    ("announceAdvancements", "true"),
+    ("commandBlockOutput", "true"),
+    ("disableElytraMovementCheck", "false"),
+    ("disableRaids", "false"),
+    ("doDaylightCycle", "true"),
+    ("doEntityDrops", "true"),
+    ("doFireTick", "true"),
+    ("doImmediateRespawn", "false"),
+    ("doInsomnia", "true"),
+    ("doLimitedCrafting", "false"),
+    ("doMobLoot", "true"),
+    ("doMobSpawning", "true"),
+    ("doPatrolSpawning", "true"),
+    ("doTileDrops", "true"),
+    ("doTraderSpawning", "true"),
+    ("doWardenSpawning", "true"),
+    ("doWeatherCycle", "true"),
+    ("drowningDamage", "true"),
+    ("fallDamage", "true"),
+    ("fireDamage", "true"),
+    ("forgiveDeadPlayers", "true"),
+    ("freezeDamage", "true"),
+    ("keepInventory", "false"),
+    ("logAdminCommands", "true"),
+    ("maxCommandChainLength", "65536"),
+    ("maxEntityCramming", "24"),
+    ("mobGriefing", "true"),
+    ("naturalRegeneration", "true"),
+    ("playersSleepingPercentage", "100"),
+    ("randomTickSpeed", "3"),
+    ("reducedDebugInfo", "false"),
+    ("sendCommandFeedback", "true"),
+    ("showDeathMessages", "true"),
+    ("spawnRadius", "10"),
+    ("spectatorsGenerateChunks", "true"),
+    ("universalAnger", "false"),
+];
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct LevelDat {
+    #[serde(rename = "Data")]
+    data: LevelData,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LevelData {
+    #[serde(rename = "SpawnX")]
+    pub spawn_x: i32,
+    #[serde(rename = "SpawnY")]
+    pub spawn_y: i32,
+    #[serde(rename = "SpawnZ")]
+    pub spawn_z: i32,
+    #[serde(rename = "SpawnAngle", default)]
+    pub spawn_angle: f32,
+    /// Ticks since the world was made
+    #[serde(rename = "Time", default)]
+    pub time: i64,
+    /// Where we are in the day, which `/time set` can change
+    #[serde(rename = "DayTime", default)]
+    pub day_time: i64,
+    #[serde(default)]
+    pub raining: bool,
+    /// Ticks until it starts or stops raining
+    #[serde(rename = "rainTime", default)]
+    pub rain_time: i32,
+    #[serde(default)]
+    pub thundering: bool,
+    #[serde(rename = "thunderTime", default)]
+    pub thunder_time: i32,
+    /// Set by `/weather clear`, to keep it clear for a while
+    #[serde(rename = "clearWeatherTime", default)]
+    pub clear_weather_time: i32,
+    /// Only the ones that aren't the default are saved
+    #[serde(rename = "GameRules", default)]
+    game_rules: BTreeMap<String, String>,
+    /// 0 is peaceful, up to 3 for hard
+    #[serde(rename = "Difficulty", default = "normal")]
+    pub difficulty: i8,
+    #[serde(rename = "DifficultyLocked", default)]
+    pub difficulty_locked: bool,
+    #[serde(rename = "hardcore", default)]
+    pub hardcore: bool,
+    #[serde(rename = "WorldGenSettings")]
+    world_gen: WorldGenSettings,
+    #[serde(rename = "DataVersion", default)]
+    data_version: i32,
+    #[serde(flatten)]
+    rest: HashMap<String, fastnbt::Value>,
+}
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct WorldGenSettings {
+    seed: i64,
+    #[serde(flatten)]
+    rest: HashMap<String, fastnbt::Value>,
+}
+fn normal() -> i8 {
+    2
+}
+impl LevelData {
+    /// A world that's only just been made, with its spawn still to be found
+    pub(super) fn new(seed: i64) -> Self {
+        Self {
+            spawn_x: 0,
+            spawn_y: 0,
+            spawn_z: 0,
+            spawn_angle: 0.0,
+            time: 0,
+            day_time: 0,
+            raining: false,
+            rain_time: 0,
+            thundering: false,
+            thunder_time: 0,
+            clear_weather_time: 0,
+            game_rules: Default::default(),
+            difficulty: normal(),
+            difficulty_locked: false,
+            hardcore: false,
+            world_gen: WorldGenSettings { seed, rest: Default::default() },
+            data_version: DATA_VERSION,
+            rest: Default::default(),
+        }
+    }
+    pub fn seed(&self) -> i64 {
+        self.world_gen.seed
+    }
+    /// What the client gets instead of the seed, so it can vary biome colours without knowing it
+    pub fn hashed_seed(&self) -> i64 {
+        use sha2::Digest;
+        let hash = sha2::Sha256::digest(self.seed().to_le_bytes());
+        i64::from_le_bytes(hash[..8].try_into().unwrap())
+    }
+    pub fn game_rule(&self, name: &str) -> &str {
+        match self.game_rules.get(name) {
+            Some(value) => value,
+            None => GAME_RULES.iter().find(|(rule, _)| *rule == name).map_or("", |(_, default)| default),
+        }
+    }
+    /// For rules that are either on or off
+    pub fn rule_enabled(&self, name: &str) -> bool {
+        self.game_rule(name) == "true"
+    }
+    /// Changes a rule, if there is one by that name and the value makes sense for it
+    fn set_game_rule(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let default = match GAME_RULES.iter().find(|(rule, _)| *rule == name) {
+            Some((_, default)) => *default,
+            None => return Err(format!("there's no game rule called {name}")),
+        };
+        let fits = match default {
+            "true" | "false" => value == "true" || value == "false",
+            _ => value.parse::<i32>().is_ok(),
+        };
+        if !fits {
+            return Err(format!("{name} can't be {value:?}"));
+        }
+        self.game_rules.insert(name.to_owned(), value.to_owned());
+        Ok(())
+    }
+}
+
+impl Level {
+    /// Reads `level.dat` from a world directory, if it has one
+    pub(super) fn read_level_data(dir: &Path) -> io::Result<Option<LevelData>> {
+        use std::io::Read;
+        let path = dir.join("level.dat");
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut nbt = vec![];
+        flate2::read::GzDecoder::new(std::fs::File::open(&path)?).read_to_end(&mut nbt)?;
+        match fastnbt::from_bytes::<LevelDat>(&nbt) {
+            Ok(LevelDat { data }) => {
+                if data.data_version > DATA_VERSION {
+                    log::warn!("{} is from a newer version of minecraft, so parts of it might not work", path.display());
+                }
+                Ok(Some(data))
+            }
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("couldn't read {}: {e}", path.display()))),
+        }
+    }
+    /// Picks the seed for a new world. One that's already been saved keeps its own, which is returned
+    pub fn set_seed(&mut self, seed: i64) -> i64 {
+        match &self.data {
+            Some(data) if data.seed() != seed => {
+                log::warn!("the world's seed is {}, so that's being used instead", data.seed());
+                data.seed()
+            }
+            _ => {
+                self.seed = seed;
+                seed
+            }
+        }
+    }
+    pub(super) fn save_level_data(&self, data: &LevelData) -> io::Result<()> {
+        let path = match self.path() {
+            Some(path) => path.join("level.dat"),
+            None => return Ok(()),
+        };
+        let nbt = fastnbt::to_bytes(&LevelDat { data: data.clone() }).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.storage.attempt(|| super::write_gzipped(&path, &nbt))
+    }
+}
+
+impl World {
+    pub fn level_data(&self) -> &LevelData {
+        &self.data
+    }
+    /// New worlds start on the ground in the middle of the overworld
+    pub(super) fn find_spawn(&mut self) {
+        self.load_now(OVERWORLD, (0, 0));
+        self.data.spawn_y = self.dimensions[OVERWORLD].surface(0, 0);
+        log::info!("the world's spawn is at 0 {} 0", self.data.spawn_y);
+    }
+    /// Returns whether it worked
+    pub(super) fn save_level_data(&self) -> bool {
+        match self.level.save_level_data(&self.data) {
+            Ok(()) => true,
+            Err(e) => {
+                log::error!("couldn't save level.dat: {e}");
+                false
+            }
+        }
+    }
+    pub(super) fn gamerule_command(&mut self, args: &str) -> String {
+        match args.split_whitespace().collect::<Vec<_>>()[..] {
+            [] => GAME_RULES.iter().map(|(rule, _)| format!("{rule} = {}", self.data.game_rule(rule))).collect::<Vec<_>>().join("\n"),
+            [rule] if GAME_RULES.iter().any(|(name, _)| *name == rule) => format!("{rule} = {}", self.data.game_rule(rule)),
+            [rule] => format!("there's no game rule called {rule}"),
+            [rule, value] => match self.data.set_game_rule(rule, value) {
+                Ok(()) => {
+                    log::info!("set {rule} to {value}");
+                    format!("{rule} is now {value}")
+                }
+                Err(e) => e,
+            },
+            _ => "usage: gamerule [<rule> [<value>]]".to_owned(),
+        }
+    }
+}
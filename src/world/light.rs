@@ -0,0 +1,507 @@
+//! Sky and block light.
+//!
+//! Light lives next to the blocks in each chunk, and gets patched up with a flood fill
+//! whenever a block changes. Sky light is 15 straight down from the sky through anything
+//! transparent, and like block light loses at least one level per block everywhere else.
+//! The client would work all this out itself, but we tell it to trust us.
+use crate::prelude::*;
+use super::{World, Dimension, ChunkContent};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightKind {
+    Sky,
+    Block,
+}
+#[derive(Clone)]
+pub enum LightSection {
+    /// Every block in the section has this level
+    Uniform(u8),
+    /// Two levels per byte, indexed like the blocks in the section
+    Varied(Box<[u8; 2048]>),
+}
+static UNIFORM: [[u8; 2048]; 16] = {
+    let mut uniform = [[0; 2048]; 16];
+    let mut level = 0;
+    while level < 16 {
+        uniform[level] = [level as u8 * 0x11; 2048];
+        level += 1;
+    }
+    uniform
+};
+impl LightSection {
+    fn get(&self, idx: usize) -> u8 {
+        match self {
+            Self::Uniform(level) => *level,
+            Self::Varied(levels) => levels[idx / 2] >> (idx % 2 * 4) & 0xF,
+        }
+    }
+    /// Returns whether the level changed
+    fn set(&mut self, idx: usize, level: u8) -> bool {
+        if let Self::Uniform(old) = *self {
+            if old == level {
+                return false;
+            }
+            *self = Self::Varied(Box::new(UNIFORM[old as usize]));
+        }
+        match self {
+            Self::Varied(levels) => {
+                let byte = &mut levels[idx / 2];
+                let old = *byte >> (idx % 2 * 4) & 0xF;
+                *byte = *byte & !(0xF << (idx % 2 * 4)) | level << (idx % 2 * 4);
+                old != level
+            }
+            Self::Uniform(_) => unreachable!(),
+        }
+    }
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Self::Uniform(level) => &UNIFORM[*level as usize],
+            Self::Varied(levels) => &levels[..],
+        }
+    }
+    /// Goes back to one level for the whole section if it can
+    fn compact(&mut self) {
+        if let Self::Varied(levels) = self {
+            if levels.iter().all(|b| *b == levels[0]) && levels[0] >> 4 == levels[0] & 0xF {
+                *self = Self::Uniform(levels[0] & 0xF);
+            }
+        }
+    }
+}
+/// A bit for each of a chunk's light sections, in longs the way light packets want them
+#[derive(Debug, Clone)]
+pub struct SectionSet(Box<[u64]>);
+impl SectionSet {
+    pub fn none(sections: usize) -> Self {
+        Self(vec![0; sections.div_ceil(64)].into())
+    }
+    pub fn all(sections: usize) -> Self {
+        let mut set = Self::none(sections);
+        (0..sections).for_each(|i| set.insert(i));
+        set
+    }
+    fn insert(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+    fn contains(&self, i: usize) -> bool {
+        self.0[i / 64] & 1 << (i % 64) != 0
+    }
+    fn is_empty(&self) -> bool {
+        self.0.iter().all(|&bits| bits == 0)
+    }
+}
+/// Light for each section of a chunk, plus one below and one above it. The client wants light for those too
+pub struct ChunkLight {
+    /// Empty in dimensions without a sky
+    pub sky: Box<[LightSection]>,
+    pub block: Box<[LightSection]>,
+    /// The sections that changed since viewers were last told
+    pub(super) changed: SectionSet,
+}
+impl ChunkLight {
+    /// Darkness, for a chunk `sections` tall
+    pub fn new(sections: usize, skylight: bool) -> Self {
+        let mut sky: Box<[_]> = (0..if skylight { sections + 2 } else { 0 }).map(|_| LightSection::Uniform(0)).collect();
+        if let Some(top) = sky.last_mut() {
+            *top = LightSection::Uniform(15);
+        }
+        Self { sky, block: (0..sections + 2).map(|_| LightSection::Uniform(0)).collect(), changed: SectionSet::none(sections + 2) }
+    }
+    fn sections(&mut self, kind: LightKind) -> &mut [LightSection] {
+        match kind {
+            LightKind::Sky => &mut self.sky,
+            LightKind::Block => &mut self.block,
+        }
+    }
+    /// Every section, for sending the whole chunk
+    pub fn all(&self) -> SectionSet {
+        SectionSet::all(self.block.len())
+    }
+    /// The sections in `mask` the way light packets want them: the sections with light,
+    /// the ones that are completely dark, and the light itself
+    pub fn packet(&self, kind: LightKind, mask: &SectionSet) -> (Vec<u64>, Vec<u64>, Vec<&[u8]>) {
+        let sections = match kind {
+            LightKind::Sky => &self.sky,
+            LightKind::Block => &self.block,
+        };
+        let (mut lit, mut dark, mut arrays) = (SectionSet::none(sections.len()), SectionSet::none(sections.len()), vec![]);
+        for (i, section) in sections.iter().enumerate().filter(|(i, _)| mask.contains(*i)) {
+            if let LightSection::Uniform(0) = section {
+                dark.insert(i);
+            } else {
+                lit.insert(i);
+                arrays.push(section.bytes());
+            }
+        }
+        (lit.0.into_vec(), dark.0.into_vec(), arrays)
+    }
+}
+/// Where a block's light is kept: its light section, and its index in there
+fn light_index(pos: V3<i32>, min_y: i32) -> (usize, usize) {
+    ((pos.y - min_y).div_euclid(16) as usize + 1, (pos.y.rem_euclid(16) * 256 + pos.z.rem_euclid(16) * 16 + pos.x.rem_euclid(16)) as usize)
+}
+/// How much light `level` leaves for the block next door in `direction`
+fn spread(kind: LightKind, level: u8, direction: BlockFace, opacity: u8) -> u8 {
+    if kind == LightKind::Sky && direction == BlockFace::Bottom && level == 15 && opacity == 0 {
+        15
+    } else {
+        level.saturating_sub(opacity.max(1))
+    }
+}
+const DIRECTIONS: [BlockFace; 6] = [BlockFace::Top, BlockFace::Bottom, BlockFace::North, BlockFace::South, BlockFace::East, BlockFace::West];
+
+impl Dimension {
+    fn light_kinds(&self) -> &'static [LightKind] {
+        if self.has_skylight() { &[LightKind::Sky, LightKind::Block] } else { &[LightKind::Block] }
+    }
+    pub fn light_at(&self, pos: V3<i32>, kind: LightKind) -> u8 {
+        if pos.y >= self.top() {
+            return if kind == LightKind::Sky && self.has_skylight() { 15 } else { 0 };
+        } else if pos.y < self.min_y() {
+            return 0;
+        }
+        match self.index.get(&(pos.x.div_euclid(16), pos.z.div_euclid(16))) {
+            Some(&chunk) => {
+                let (section, idx) = light_index(pos, self.min_y());
+                let light = &self.chunks[chunk as usize].light;
+                match kind {
+                    LightKind::Sky => light.sky.get(section).map_or(0, |s| s.get(idx)),
+                    LightKind::Block => light.block[section].get(idx),
+                }
+            }
+            None => 0,
+        }
+    }
+    fn set_light(&mut self, pos: V3<i32>, kind: LightKind, level: u8) {
+        let chunk = self.index[&(pos.x.div_euclid(16), pos.z.div_euclid(16))];
+        let (section, idx) = light_index(pos, self.min_y());
+        let light = &mut self.chunks[chunk as usize].light;
+        if light.sections(kind)[section].set(idx, level) {
+            light.changed.insert(section);
+        }
+    }
+    fn opacity(&self, pos: V3<i32>) -> u8 {
+        self.block_at(pos).map_or(0, |b| b.info().opacity)
+    }
+    /// Floods light out from `queue`, brightening anything that's darker than it should be
+    fn spread_light(&mut self, kind: LightKind, mut queue: VecDeque<V3<i32>>) {
+        while let Some(pos) = queue.pop_front() {
+            let level = self.light_at(pos, kind);
+            if level <= 1 {
+                continue;
+            }
+            for direction in DIRECTIONS {
+                let next = pos.step(direction);
+                if !self.in_world(next) {
+                    continue;
+                }
+                let level = spread(kind, level, direction, self.opacity(next));
+                if level > self.light_at(next, kind) {
+                    self.set_light(next, kind, level);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    /// Darkens everything that was lit by `start`, which used to have light `level`.
+    /// Returns the blocks that still have light of their own, to spread it back in
+    fn unspread_light(&mut self, kind: LightKind, start: V3<i32>, level: u8) -> VecDeque<V3<i32>> {
+        let mut queue = VecDeque::from([(start, level)]);
+        let mut relight = VecDeque::new();
+        while let Some((pos, level)) = queue.pop_front() {
+            for direction in DIRECTIONS {
+                let next = pos.step(direction);
+                if !self.in_world(next) {
+                    continue;
+                }
+                let old = self.light_at(next, kind);
+                if old == 0 {
+                    continue;
+                }
+                let lit_by_us = old < level || kind == LightKind::Sky && direction == BlockFace::Bottom && level == 15 && old == 15;
+                if !lit_by_us {
+                    relight.push_back(next);
+                    continue;
+                }
+                self.set_light(next, kind, 0);
+                queue.push_back((next, old));
+                let own = match kind {
+                    LightKind::Block => self.block_at(next).map_or(0, |b| b.light_emission()),
+                    LightKind::Sky if next.y == self.top() - 1 => spread(kind, 15, BlockFace::Bottom, self.opacity(next)),
+                    LightKind::Sky => 0,
+                };
+                if own > 0 {
+                    self.set_light(next, kind, own);
+                    relight.push_back(next);
+                }
+            }
+        }
+        relight
+    }
+    /// Fixes up the light around a block that changed from `old` to `new`
+    pub(super) fn relight(&mut self, pos: V3<i32>, old: Option<Block>, new: Option<Block>) {
+        let opacity = |b: Option<Block>| b.map_or(0, |b| b.info().opacity);
+        let emission = |b: Option<Block>| b.map_or(0, |b| b.light_emission());
+        if opacity(old) == opacity(new) && emission(old) == emission(new) {
+            return;
+        }
+        for &kind in self.light_kinds() {
+            let level = self.light_at(pos, kind);
+            self.set_light(pos, kind, 0);
+            let mut relight = self.unspread_light(kind, pos, level);
+            let own = match kind {
+                LightKind::Block => emission(new),
+                LightKind::Sky if pos.y == self.top() - 1 => spread(kind, 15, BlockFace::Bottom, opacity(new)),
+                LightKind::Sky => 0,
+            };
+            if own > 0 {
+                self.set_light(pos, kind, own);
+                relight.push_back(pos);
+            }
+            // the neighbours shine back in, including into `pos`
+            relight.extend(DIRECTIONS.iter().map(|d| pos.step(*d)).filter(|p| self.in_world(*p)));
+            self.spread_light(kind, relight);
+        }
+    }
+    /// The lowest y from which a column sees the sky at full brightness
+    fn sky_top(&self, x: i32, z: i32) -> i32 {
+        let mut y = self.top() - 1;
+        while y >= self.min_y() && self.light_at(V3(x, y, z), LightKind::Sky) == 15 {
+            y -= 1;
+        }
+        y + 1
+    }
+    /// Lets the light of a chunk that's just been put in place (and lit on its own, by
+    /// [ChunkLight::compute]) into its neighbours, and theirs into it
+    pub(super) fn light_chunk(&mut self, chunk: (i32, i32)) {
+        let idx = self.index[&chunk] as usize;
+        let (cx, cz) = (chunk.0 * 16, chunk.1 * 16);
+        let (min_y, end_y) = (self.min_y(), self.top());
+        let mut sky_queue = VecDeque::new();
+        let mut block_queue = VecDeque::new();
+        // each column along the edge, and the one across the border from it
+        let borders = (0..16).flat_map(|i| [
+            ((cx, cz + i), (cx - 1, cz + i)),
+            ((cx + 15, cz + i), (cx + 16, cz + i)),
+            ((cx + i, cz), (cx + i, cz - 1)),
+            ((cx + i, cz + 15), (cx + i, cz + 16)),
+        ]);
+        for ((x, z), (nx, nz)) in borders {
+            if !self.in_world(V3(nx, min_y, nz)) {
+                continue;
+            }
+            // full brightness spills sideways into wherever the column across is shaded
+            if self.has_skylight() {
+                let (top, next) = (self.sky_top(x, z), self.sky_top(nx, nz));
+                sky_queue.extend((top..next).map(|y| V3(x, y, z)));
+                sky_queue.extend((next..top).map(|y| V3(nx, y, nz)));
+            }
+            for y in min_y..end_y {
+                for pos in [V3(x, y, z), V3(nx, y, nz)] {
+                    if (2..15).contains(&self.light_at(pos, LightKind::Sky)) {
+                        sky_queue.push_back(pos);
+                    }
+                    if self.light_at(pos, LightKind::Block) > 1 {
+                        block_queue.push_back(pos);
+                    }
+                }
+            }
+        }
+        self.spread_light(LightKind::Sky, sky_queue);
+        self.spread_light(LightKind::Block, block_queue);
+        // the chunk packet brings all of this chunk's light with it
+        let light = &mut self.chunks[idx].light;
+        light.changed = SectionSet::none(light.block.len());
+    }
+}
+impl ChunkLight {
+    /// Lights a chunk as if there was nothing around it. That's most of the work, and it
+    /// doesn't need the rest of the world, so the chunk loaders do it
+    pub fn compute(content: &ChunkContent, skylight: bool) -> Self {
+        let (min_y, sections) = (content.min_y(), content.sections());
+        let end_y = min_y + sections as i32 * 16;
+        let mut light = Self::new(sections, skylight);
+        let mut sky_queue = VecDeque::new();
+        let mut block_queue = VecDeque::new();
+        // the lowest y at full brightness, for each column
+        let mut tops = [[min_y; 16]; 16];
+        if skylight {
+            light.sky[1..sections + 1].iter_mut().for_each(|s| *s = LightSection::Varied(Box::new(UNIFORM[0])));
+            for (x, tops) in tops.iter_mut().enumerate() {
+                for (z, top) in tops.iter_mut().enumerate() {
+                    let mut level = 15;
+                    for y in (min_y..end_y).rev() {
+                        let block = content.block(x, y, z);
+                        level = spread(LightKind::Sky, level, BlockFace::Bottom, block.map_or(0, |b| b.info().opacity));
+                        if level == 0 {
+                            break;
+                        }
+                        let (section, i) = light_index(V3(x as i32, y, z as i32), min_y);
+                        light.sky[section].set(i, level);
+                        if level == 15 {
+                            *top = y;
+                        } else {
+                            sky_queue.push_back(V3(x as i32, y, z as i32));
+                        }
+                    }
+                }
+            }
+            light.sky.iter_mut().for_each(LightSection::compact);
+            // full brightness spills sideways into wherever the next column over is shaded
+            for x in 0..16 {
+                for z in 0..16 {
+                    let shaded = [(x - 1, z), (x + 1, z), (x, z - 1), (x, z + 1)].into_iter()
+                        .filter(|&(x, z)| (0..16).contains(&x) && (0..16).contains(&z))
+                        .map(|(x, z)| tops[x as usize][z as usize])
+                        .max()
+                        .unwrap_or(min_y);
+                    sky_queue.extend((tops[x as usize][z as usize]..shaded).map(|y| V3(x, y, z)));
+                }
+            }
+        }
+        let ChunkContent::OneToOne { nonaircounts, .. } = content;
+        for (section, _) in nonaircounts.iter().enumerate().filter(|(_, n)| **n > 0) {
+            for i in 0..4096 {
+                let (x, y, z) = (i % 16, section as i32 * 16 + min_y + i as i32 / 256, i / 16 % 16);
+                let emission = content.block(x, y, z).map_or(0, |b| b.light_emission());
+                if emission > 0 {
+                    light.block[section + 1].set(i, emission);
+                    block_queue.push_back(V3(x as i32, y, z as i32));
+                }
+            }
+        }
+        light.spread(content, LightKind::Sky, sky_queue);
+        light.spread(content, LightKind::Block, block_queue);
+        light
+    }
+    /// Like [Dimension::spread_light], but only as far as the edges of the chunk
+    fn spread(&mut self, content: &ChunkContent, kind: LightKind, mut queue: VecDeque<V3<i32>>) {
+        let min_y = content.min_y();
+        let end_y = min_y + content.sections() as i32 * 16;
+        let level_at = |light: &mut Self, pos: V3<i32>| {
+            let (section, idx) = light_index(pos, min_y);
+            light.sections(kind)[section].get(idx)
+        };
+        while let Some(pos) = queue.pop_front() {
+            let level = level_at(self, pos);
+            if level <= 1 {
+                continue;
+            }
+            for direction in DIRECTIONS {
+                let next = pos.step(direction);
+                if !(0..16).contains(&next.x) || !(0..16).contains(&next.z) || !(min_y..end_y).contains(&next.y) {
+                    continue;
+                }
+                let opacity = content.block(next.x as usize, next.y, next.z as usize).map_or(0, |b| b.info().opacity);
+                let level = spread(kind, level, direction, opacity);
+                if level > level_at(self, next) {
+                    let (section, idx) = light_index(next, min_y);
+                    self.sections(kind)[section].set(idx, level);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+}
+impl World {
+    /// Tells viewers about the light that changed since last tick
+    pub(super) fn send_light_updates(&mut self, mut inboxes: Inboxes) {
+        for dimension in &mut self.dimensions {
+            for (&(x, z), &idx) in &dimension.index {
+                let chunk = &mut dimension.chunks[idx as usize];
+                if chunk.light.changed.is_empty() || chunk.visible_to == 0 {
+                    continue;
+                }
+                let sections = std::mem::replace(&mut chunk.light.changed, SectionSet::none(chunk.light.block.len()));
+                let viewers = chunk.visible_to;
+                inboxes.reborrow().retain(|pid, mut inbox| {
+                    if viewers & 1 << pid != 0 {
+                        inbox.submit(Response::UpdateLight(x, z, sections.clone()));
+                    }
+                    true
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Heightmaps, Level, OVERWORLD};
+    use crate::registry::BlockInfo;
+
+    fn block(name: &str) -> Option<Block> {
+        Some(BlockInfo::from_name(name).unwrap().default_state())
+    }
+    fn level(light: &ChunkLight, kind: LightKind, pos: V3<i32>, min_y: i32) -> u8 {
+        let (section, idx) = light_index(pos, min_y);
+        match kind {
+            LightKind::Sky => light.sky[section].get(idx),
+            LightKind::Block => light.block[section].get(idx),
+        }
+    }
+
+    #[test]
+    fn compute_lights_a_chunk_on_its_own() {
+        let mut content = ChunkContent::empty(-64, 24, 0);
+        // a roof with a hole in one corner, and a torch under it
+        for i in 0..256 {
+            content.set_block(i % 16, 10, i / 16, block("stone"));
+        }
+        content.set_block(0, 10, 0, None);
+        content.set_block(8, 0, 8, block("torch"));
+        let light = ChunkLight::compute(&content, true);
+        let sky = |x, y, z| level(&light, LightKind::Sky, V3(x, y, z), -64);
+        assert_eq!(sky(5, 11, 5), 15);
+        assert_eq!(sky(5, 10, 5), 0);
+        // straight down through the hole, then out sideways
+        assert_eq!(sky(0, -64, 0), 15);
+        assert_eq!(sky(1, 9, 0), 14);
+        assert_eq!(sky(5, -30, 5), 5);
+        assert_eq!(sky(15, 9, 15), 0);
+        let glow = |x, y, z| level(&light, LightKind::Block, V3(x, y, z), -64);
+        assert_eq!(glow(8, 0, 8), 14);
+        assert_eq!(glow(8, 0, 11), 11);
+        assert_eq!(glow(6, 3, 8), 9);
+        assert_eq!(glow(8, 11, 8), 0);
+    }
+
+    #[test]
+    fn relight_agrees_with_compute() {
+        let mut network = crate::network::Network::local();
+        let mut world = World::new(Level::empty(), network.notifier());
+        let dimension = &mut world.dimensions[OVERWORLD];
+        let (min_y, sections) = (dimension.min_y(), dimension.sections());
+        let content = ChunkContent::empty(min_y, sections, 0);
+        let (heightmaps, light) = (Heightmaps::compute(&content), ChunkLight::compute(&content, true));
+        dimension.insert_chunk((0, 0), content, heightmaps, light, 0);
+        // low down, so there isn't much shade to work out again with every block of roof
+        let y = min_y + 8;
+        for i in 0..256 {
+            dimension.set_block(V3(i % 16, y, i / 16), block("stone"));
+        }
+        dimension.set_block(V3(4, y - 4, 4), block("glowstone"));
+        dimension.set_block(V3(12, y - 6, 3), block("torch"));
+        // opening the roof back up, and closing some of it again
+        for i in 0..16 {
+            dimension.set_block(V3(i, y, 7), None);
+        }
+        dimension.set_block(V3(9, y, 7), block("stone"));
+        dimension.set_block(V3(4, y - 4, 4), None);
+        dimension.set_block(V3(12, y - 5, 3), block("glass"));
+        dimension.set_block(V3(11, y - 6, 3), block("stone"));
+
+        let content = &dimension.chunks[dimension.index[&(0, 0)] as usize].content;
+        let expected = ChunkLight::compute(content, true);
+        for kind in [LightKind::Sky, LightKind::Block] {
+            for y in min_y..dimension.top() {
+                for i in 0..256 {
+                    let pos = V3(i % 16, y, i / 16);
+                    assert_eq!(dimension.light_at(pos, kind), level(&expected, kind, pos, min_y), "{kind:?} light at {pos:?}");
+                }
+            }
+        }
+    }
+}
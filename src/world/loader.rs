@@ -0,0 +1,119 @@
+//! Reading chunks off disk, or generating them, on a handful of worker threads.
+//!
+//! The world hands out jobs and carries on with the tick. The workers light the chunk too,
+//! as far as they can without its neighbours. When a job's done they wake the network up, and the world puts the chunk in place and sends it to whoever's
+//! still waiting. Jobs for chunks nobody's waiting for any more get cancelled.
+use super::{generator::ChunkGenerator, items::SavedItem, ChunkContent, ChunkLight, Dimension, Heightmaps, Level};
+use crate::network::Notifier;
+use std::sync::{atomic::{AtomicBool, Ordering}, mpsc, Arc, Mutex};
+
+/// The most chunks we'll load at once
+const MAX_WORKERS: usize = 4;
+
+/// A chunk that's being loaded, and the players waiting to see it
+pub(super) struct Loading {
+    pub waiting: u32,
+    cancelled: Arc<AtomicBool>,
+}
+impl Loading {
+    /// Lets the worker know not to bother with it, if it hasn't got to it yet
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Job {
+    dimension: usize,
+    name: String,
+    chunk: (i32, i32),
+    generator: Arc<dyn ChunkGenerator>,
+    min_y: i32,
+    sections: usize,
+    skylight: bool,
+    cancelled: Arc<AtomicBool>,
+}
+pub(super) struct Loaded {
+    pub dimension: usize,
+    pub chunk: (i32, i32),
+    pub content: ChunkContent,
+    pub heightmaps: Heightmaps,
+    pub light: ChunkLight,
+    pub items: Vec<SavedItem>,
+    cancelled: Arc<AtomicBool>,
+}
+impl Loaded {
+    /// Whether it was cancelled after the worker had already finished it
+    pub fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+pub(super) struct Loader {
+    jobs: mpsc::Sender<Job>,
+    done: mpsc::Receiver<Loaded>,
+}
+impl Loader {
+    pub fn spawn(level: Arc<Level>, notifier: Notifier) -> Self {
+        let (jobs, incoming) = mpsc::channel::<Job>();
+        let incoming = Arc::new(Mutex::new(incoming));
+        let (finished, done) = mpsc::channel();
+        let workers = std::thread::available_parallelism().map_or(1, |n| n.get()).clamp(1, MAX_WORKERS);
+        for i in 0..workers {
+            let (level, incoming, finished, notifier) = (level.clone(), incoming.clone(), finished.clone(), notifier.clone());
+            std::thread::Builder::new().name(format!("chunk loader {i}")).spawn(move || loop {
+                // stops once the world's gone
+                let job = match incoming.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => return,
+                };
+                if let Some(loaded) = job.run(&level) {
+                    if finished.send(loaded).is_err() {
+                        return;
+                    }
+                    notifier.notify();
+                }
+            }).expect("couldn't start a chunk loader");
+        }
+        Self { jobs, done }
+    }
+    /// Starts loading a chunk from the `dimension`th dimension, generating it if it's never been saved
+    pub fn load(&self, dimension: usize, blocks: &Dimension, chunk: (i32, i32), waiting: u32) -> Loading {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let job = Job {
+            dimension,
+            name: blocks.name.clone(),
+            chunk,
+            generator: blocks.generator.clone(),
+            min_y: blocks.min_y(),
+            sections: blocks.sections(),
+            skylight: blocks.has_skylight(),
+            cancelled: cancelled.clone(),
+        };
+        self.jobs.send(job).expect("the chunk loaders died");
+        Loading { waiting, cancelled }
+    }
+    /// Chunks that have loaded since we last asked
+    pub fn finished(&self) -> impl Iterator<Item = Loaded> + '_ {
+        self.done.try_iter()
+    }
+}
+impl Job {
+    fn run(self, level: &Level) -> Option<Loaded> {
+        let cancelled = || self.cancelled.load(Ordering::Relaxed);
+        if cancelled() {
+            return None;
+        }
+        let (content, heightmaps, items) = match level.load_chunk_or_log(&self.name, self.chunk, self.min_y, self.sections) {
+            Some(saved) => saved,
+            // generating takes a while, so it's worth checking again
+            None if cancelled() => return None,
+            None => {
+                let content = self.generator.generate(self.chunk, self.min_y, self.sections);
+                let heightmaps = Heightmaps::compute(&content);
+                (content, heightmaps, vec![])
+            }
+        };
+        let light = ChunkLight::compute(&content, self.skylight);
+        Some(Loaded { dimension: self.dimension, chunk: self.chunk, content, heightmaps, light, items, cancelled: self.cancelled })
+    }
+}
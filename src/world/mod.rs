@@ -0,0 +1,1027 @@
+use crate::prelude::*;
+
+mod digging;
+mod entity;
+mod combat;
+mod items;
+mod movement;
+mod physics;
+mod placement;
+mod light;
+mod heightmap;
+mod storage;
+mod dimension;
+mod portal;
+mod loader;
+mod budget;
+mod queue;
+mod recovery;
+mod template;
+mod autosave;
+mod backup;
+mod level_data;
+mod time;
+mod vitals;
+mod weather;
+pub mod generator;
+pub mod biome;
+pub mod codec;
+
+pub use light::{ChunkLight, LightKind, LightSection, SectionSet};
+pub use heightmap::{Heightmap, Heightmaps};
+pub use dimension::Dimension;
+pub use budget::{Limits, Prefer, Usage};
+pub use backup::{restore, verify, Backups};
+pub use level_data::LevelData;
+pub use queue::Admission;
+use dimension::{OVERWORLD, NETHER, END};
+
+// What's the role of world state?
+// Sometimes, there's a fixed template world that is readonly
+//   If these are too large for memory (min 4 * 25*25 chunks I guess),
+//   we want a way to stream the chunk data in.
+//
+//   Ah, that'd also mean that ephemeral edits need somewhere to live
+//   if they're too big for memory
+//
+// What can the server do about IO failure?
+//   It must keep trying to sync "true" worldstate to disk,
+//   if that's not possible, we've got a fatal error in the system
+//   and it'll need to shutdown.
+//   Can probably have a "recovery mode" that will boot players to a lobby
+//   and allow an admin to attempt to recover the servers' data.
+//   stuff like mounting a new drive and pointing it at that.
+//   (or, more easily, freeing up space by deleting files)
+//   Of course, in this scenario, hotswapping the impl wont work.
+// That is to say, we have to handle errors at the server layer. They need to be fed back to players,
+// otherwise more and more stale edits would pile up, and we'd exhaust memory.
+// Also, don't want to persist untouched chunks. They will be regenerated.
+// 
+pub struct Level {
+    /// Can change if the admin moves the world somewhere that works, in recovery mode
+    path: std::sync::RwLock<Option<std::path::PathBuf>>,
+    /// A world we only read chunks from, under the ones that have been saved in `path`
+    template: Option<std::path::PathBuf>,
+    codec: codec::Codec,
+    /// Generators for dimensions that shouldn't use their default one
+    generators: std::collections::HashMap<String, std::sync::Arc<dyn generator::ChunkGenerator>>,
+    /// Names from `ops.json`
+    ops: Vec<String>,
+    storage: recovery::StorageHealth,
+    /// What was in `level.dat`, until the world takes it
+    data: Option<LevelData>,
+    /// For when there wasn't one
+    seed: i64,
+}
+impl Level {
+    pub fn empty() -> Self {
+        Self {
+            path: Default::default(),
+            template: None,
+            codec: codec::Codec::builtin(),
+            generators: Default::default(),
+            ops: vec![],
+            storage: Default::default(),
+            data: None,
+            seed: 0,
+        }
+    }
+    pub fn from_path(p: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        let path = p.as_ref().to_owned();
+        std::fs::create_dir_all(path.join("playerdata"))?;
+        let codec = codec::Codec::load(&path.join("datapacks"))?;
+        let ops = Self::load_ops(&path.join("ops.json"));
+        let data = Self::read_level_data(&path)?;
+        Ok(Self {
+            path: std::sync::RwLock::new(Some(path)),
+            template: None,
+            codec,
+            generators: Default::default(),
+            ops,
+            storage: Default::default(),
+            data,
+            seed: 0,
+        })
+    }
+    /// Reads the names out of a vanilla `ops.json`. A broken one just means no ops
+    fn load_ops(path: &std::path::Path) -> Vec<String> {
+        #[derive(serde::Deserialize)]
+        struct Op {
+            name: String,
+        }
+        let json = match std::fs::read(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return vec![],
+            Err(e) => {
+                log::error!("couldn't read {}: {e}", path.display());
+                return vec![];
+            }
+        };
+        match serde_json::from_slice::<Vec<Op>>(&json) {
+            Ok(ops) => ops.into_iter().map(|op| op.name).collect(),
+            Err(e) => {
+                log::error!("couldn't read {}: {e}", path.display());
+                vec![]
+            }
+        }
+    }
+    pub fn is_op(&self, name: Name) -> bool {
+        self.ops.iter().any(|op| op.eq_ignore_ascii_case(name.as_str()))
+    }
+    pub fn codec(&self) -> &codec::Codec {
+        &self.codec
+    }
+    /// Makes the new chunks in a dimension (like `minecraft:overworld`) with `generator`
+    pub fn set_generator(&mut self, dimension: &str, generator: std::sync::Arc<dyn generator::ChunkGenerator>) {
+        self.generators.insert(dimension.to_owned(), generator);
+    }
+    fn path(&self) -> Option<std::path::PathBuf> {
+        self.path.read().unwrap().clone()
+    }
+    fn player_path(&self, name: Name) -> Option<std::path::PathBuf> {
+        self.path().map(|p| p.join("playerdata").join(format!("{}.dat", name.as_str())))
+    }
+    fn load_player(&self, name: Name) -> io::Result<Option<PlayerData>> {
+        use std::io::Read;
+        let path = match self.player_path(name) {
+            Some(path) if path.exists() => path,
+            _ => return Ok(None),
+        };
+        let mut nbt = vec![];
+        flate2::read::GzDecoder::new(std::fs::File::open(path)?).read_to_end(&mut nbt)?;
+        fastnbt::from_bytes(&nbt).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+    fn save_player(&self, name: Name, data: &PlayerData) -> io::Result<()> {
+        self.storage.attempt(|| self.write_player(name, data))
+    }
+    fn write_player(&self, name: Name, data: &PlayerData) -> io::Result<()> {
+        let path = match self.player_path(name) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let nbt = fastnbt::to_bytes(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        write_gzipped(&path, &nbt)
+    }
+}
+/// Writes a `.dat` file. It's written then renamed, so a crash mid-save doesnt lose the old one
+fn write_gzipped(path: &std::path::Path, nbt: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("dat_new");
+    let mut file = flate2::write::GzEncoder::new(std::fs::File::create(&tmp)?, Default::default());
+    std::io::Write::write_all(&mut file, nbt)?;
+    file.finish()?.sync_all()?;
+    std::fs::rename(tmp, path)
+}
+
+/// A player's inventory, as laid out in the player inventory window.
+/// 0 is the crafting output, 1-4 the crafting grid, 5-8 armor,
+/// 9-35 the main inventory, 36-44 the hotbar and 45 the offhand.
+pub type Inventory = [Option<ItemStack>; 46];
+const HOTBAR: usize = 36;
+const OFFHAND: usize = 45;
+
+/// Player state that outlives a connection, in the layout vanilla uses for `playerdata`
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PlayerData {
+    #[serde(rename = "Dimension", default = "overworld")]
+    dimension: String,
+    #[serde(rename = "Pos")]
+    position: [f64; 3],
+    #[serde(rename = "SelectedItemSlot")]
+    selected_item: i32,
+    #[serde(rename = "Inventory")]
+    inventory: Vec<SavedSlot>,
+    #[serde(flatten)]
+    vitals: vitals::SavedVitals,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SavedSlot {
+    #[serde(rename = "Slot")]
+    slot: i8,
+    id: Item,
+    #[serde(rename = "Count")]
+    count: u8,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    tag: Option<Box<Compound>>,
+}
+fn overworld() -> String {
+    "minecraft:overworld".to_owned()
+}
+impl PlayerData {
+    // vanilla numbers its saved slots differently to the inventory window
+    fn saved_slot(window_slot: usize) -> Option<i8> {
+        match window_slot {
+            5..=8 => Some(103 - (window_slot as i8 - 5)),
+            9..=35 => Some(window_slot as i8),
+            HOTBAR..=44 => Some((window_slot - HOTBAR) as i8),
+            OFFHAND => Some(-106),
+            _ => None,
+        }
+    }
+    fn window_slot(saved_slot: i8) -> Option<usize> {
+        match saved_slot {
+            100..=103 => Some((103 - saved_slot) as usize + 5),
+            9..=35 => Some(saved_slot as usize),
+            0..=8 => Some(saved_slot as usize + HOTBAR),
+            -106 => Some(OFFHAND),
+            _ => None,
+        }
+    }
+}
+
+pub struct Chunk {
+    next_free_chunk: u32,
+    visible_to: u32,
+
+    pub content: ChunkContent,
+    pub light: Box<ChunkLight>,
+    pub heightmaps: Heightmaps,
+    /// Whether it's been changed since it was last saved
+    dirty: bool,
+}
+pub enum ChunkContent {
+    OneToOne {
+        /// The y of the bottom of the chunk
+        min_y: i32,
+        nonaircounts: Box<[u16]>,
+        /// 15 bits for each block, 4 to a long
+        blocks: Box<[u64]>,
+        /// A biome for each 4x4x4 cube, indexed like the blocks
+        biomes: Box<[u16]>,
+    }
+}
+impl ChunkContent {
+    /// A chunk of nothing but air, `sections` sections tall
+    pub fn empty(min_y: i32, sections: usize, biome: u16) -> Self {
+        ChunkContent::OneToOne {
+            min_y,
+            nonaircounts: vec![0; sections].into_boxed_slice(),
+            blocks: vec![0; sections * 4096 / 4].into_boxed_slice(),
+            biomes: vec![biome; sections * 64].into_boxed_slice(),
+        }
+    }
+    pub fn min_y(&self) -> i32 {
+        match self {
+            ChunkContent::OneToOne { min_y, .. } => *min_y,
+        }
+    }
+    pub fn sections(&self) -> usize {
+        match self {
+            ChunkContent::OneToOne { nonaircounts, .. } => nonaircounts.len(),
+        }
+    }
+    /// The block at a position within the chunk
+    pub fn block(&self, x: usize, y: i32, z: usize) -> Option<Block> {
+        match self {
+            ChunkContent::OneToOne { min_y, blocks, .. } => {
+                let idx = (y - min_y) as usize * 16 * 16 + z * 16 + x;
+                let id = blocks[idx / 4] >> (idx % 4 * 15) & 0b11111_11111_11111;
+                Block::new(id as u16)
+            }
+        }
+    }
+    /// Returns the block that was there before
+    pub fn set_block(&mut self, x: usize, y: i32, z: usize, block: Option<Block>) -> Option<Block> {
+        match self {
+            ChunkContent::OneToOne { min_y, nonaircounts, blocks, .. } => {
+                let id = block.map_or(0, |b| b.net_id());
+                let idx = (y - *min_y) as usize * 16 * 16 + z * 16 + x;
+                let long = &mut blocks[idx / 4];
+                let mask = 0b11111_11111_11111;
+                let old = (*long >> (idx % 4 * 15)) & mask;
+                *long = (*long & !(mask << (idx % 4 * 15))) | (id as u64) << (idx % 4 * 15);
+                let section = (y - *min_y) as usize / 16;
+                if old == 0 && id != 0 {
+                    nonaircounts[section] += 1;
+                } else if old != 0 && id == 0 {
+                    nonaircounts[section] -= 1;
+                }
+                Block::new(old as u16)
+            }
+        }
+    }
+    /// The biome at a position within the chunk
+    pub fn biome(&self, x: usize, y: i32, z: usize) -> u16 {
+        match self {
+            ChunkContent::OneToOne { min_y, biomes, .. } => biomes[(y - min_y) as usize / 4 * 16 + z / 4 * 4 + x / 4],
+        }
+    }
+}
+#[derive(Debug, Clone)]
+struct Player {
+    name: Name,
+    eid: i32,
+    gamemode: GameMode,
+    dimension: usize,
+    position: (f64, f64, f64),
+    yaw: f32,
+    pitch: f32,
+    on_ground: bool,
+    view_distance: u8,
+    /// What their client asked for, which `view_distance` is kept under when memory's short
+    wanted_view_distance: u8,
+    online: bool,
+    /// Waiting for the world to be saveable again, somewhere other than where they really are
+    lobby: bool,
+    inventory: Box<Inventory>,
+    selected_item: u8,
+    digging: Option<digging::Digging>,
+    /// The tick they'll go through the portal they're standing in
+    portal: Option<u32>,
+    movement: movement::Movement,
+    vitals: vitals::Vitals,
+    sprinting: bool,
+    sneaking: bool,
+    /// The tick they last hit something, which their next hit charges up from
+    last_attack: u32,
+
+    // tick on which we acked
+    // this means server lag causes timeouts. not sure about that...
+    last_ping_ack: u32, 
+}
+pub struct World {
+    level: std::sync::Arc<Level>,
+    dimensions: Vec<Dimension>,
+    loader: loader::Loader,
+    budget: budget::Budget,
+    queue: std::collections::VecDeque<queue::Queued>,
+    /// Whether we've given up on saving until the admin fixes it
+    recovering: bool,
+    autosave: autosave::Autosave,
+    backup: backup::Backup,
+    data: LevelData,
+    weather: weather::Weather,
+    entities: entity::Entities,
+
+    players: Vec<Player>,
+    next_eid: i32,
+    tick: u32,
+}
+impl World {
+    pub fn new(mut level: Level, notifier: crate::network::Notifier) -> Self {
+        let dimensions = ["minecraft:overworld", "minecraft:the_nether", "minecraft:the_end"].map(|name| {
+            let generator = level.generators.get(name).cloned()
+                .unwrap_or_else(|| generator::default_generator(name, &level.codec));
+            Dimension::new(name, name, &level.codec, generator)
+        });
+        let saved = level.data.take();
+        let fresh = saved.is_none();
+        let data = saved.unwrap_or_else(|| LevelData::new(level.seed));
+        let level = std::sync::Arc::new(level);
+        let mut world = Self {
+            loader: loader::Loader::spawn(level.clone(), notifier),
+            budget: budget::Budget::new(Limits::default()),
+            queue: Default::default(),
+            recovering: false,
+            autosave: autosave::Autosave::new(),
+            backup: backup::Backup::new(Default::default(), 0),
+            weather: weather::Weather::new(&data),
+            data,
+            level,
+            dimensions: dimensions.into(),
+            entities: entity::Entities::new(),
+            players: vec![], 
+            next_eid: 1,
+            tick: 0,
+        };
+        if fresh {
+            world.find_spawn();
+        }
+        world
+    }
+    pub fn codec(&self) -> &codec::Codec {
+        &self.level.codec
+    }
+    pub fn player_pos(&self, pid: usize) -> (f64, f64, f64) {
+        self.players[pid].position
+    }
+    pub fn dimensions(&self) -> &[Dimension] {
+        &self.dimensions
+    }
+    /// The dimension the player is in
+    pub fn dimension(&self, pid: usize) -> &Dimension {
+        &self.dimensions[self.players[pid].dimension]
+    }
+    fn find_dimension(&self, name: &str) -> Option<usize> {
+        let name = if name.contains(':') { name.to_owned() } else { format!("minecraft:{name}") };
+        self.dimensions.iter().position(|d| d.name == name)
+    }
+    /// The players that are connected
+    fn online(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.players.len()).filter(|&pid| self.players[pid].online)
+    }
+    fn new_eid(&mut self) -> i32 {
+        self.next_eid += 1;
+        self.next_eid - 1
+    }
+    /// A chunk in the player's dimension
+    pub fn chunk_at(&self, pid: usize, x: i32, z: i32) -> &Chunk {
+        self.dimension(pid).chunk_at(x, z)
+    }
+}
+impl World {
+    pub fn tick_until(&mut self, tickn: u32, mut inboxes: Inboxes) {
+        while self.tick < tickn {
+            self.tick(inboxes.reborrow());
+            self.tick += 1;
+        }
+    }
+    pub fn next_tick(&self) -> u32 {
+        self.tick + 1
+    }
+    /// Puts a player in the world. Players coming out of the queue are already in limbo, so they respawn
+    /// instead of logging in
+    fn join(&mut self, pid: usize, name: Name, eid: i32, view_distance: u8, mut inbox: Inbox, from_limbo: bool) {
+        let mut new_player = Player {
+            name,
+            eid,
+            gamemode: GameMode::Survival,
+            dimension: OVERWORLD,
+            position: (0.0, 0.0, 0.0),
+            yaw: 0.0,
+            pitch: 0.0,
+            on_ground: false,
+            view_distance: view_distance.min(self.budget.view_distance),
+            wanted_view_distance: view_distance,
+            online: true,
+            lobby: false,
+            last_ping_ack: self.tick,
+            inventory: Box::new(std::array::from_fn(|_| None)),
+            selected_item: 0,
+            digging: None,
+            portal: None,
+            movement: movement::Movement::new(),
+            vitals: vitals::Vitals::new(),
+            sprinting: false,
+            sneaking: false,
+            last_attack: self.tick,
+        };
+        match self.level.load_player(name) {
+            Ok(Some(data)) => {
+                let [x, y, z] = data.position;
+                new_player.position = (x, y, z);
+                new_player.dimension = self.find_dimension(&data.dimension).unwrap_or_else(|| {
+                    log::warn!("{} was in {}, which doesn't exist", name.as_str(), data.dimension);
+                    OVERWORLD
+                });
+                new_player.selected_item = data.selected_item.clamp(0, 8) as u8;
+                new_player.vitals = self.load_vitals(data.vitals);
+                for SavedSlot { slot, id, count, tag } in data.inventory {
+                    if let Some(slot) = PlayerData::window_slot(slot) {
+                        new_player.inventory[slot] = Some(ItemStack { item: id, count, tag });
+                    }
+                }
+            }
+            Ok(None) => new_player.position = self.spawn_point(),
+            Err(e) => {
+                log::error!("couldn't load {}'s player data: {e}", name.as_str());
+                new_player.position = self.spawn_point();
+            }
+        }
+        let position = new_player.position;
+        if self.players.len() <= pid {
+            self.players.resize(pid + 1, new_player);
+        } else {
+            self.players[pid] = new_player;
+        }
+        let player = &self.players[pid];
+        let dead = player.vitals.dead();
+        // the dead aren't anywhere until they respawn
+        if !dead {
+            self.entities.add(entity::Entity::new(eid, player.dimension, position, entity::Kind::Player { pid, name }));
+        }
+        if from_limbo {
+            inbox.submit(Response::Respawn(player.gamemode, player.dimension, player.vitals.death));
+        } else {
+            inbox.submit(Response::Login(name));
+            inbox.submit(Response::Play(player.eid, player.gamemode, player.dimension, player.vitals.death));
+        }
+        let LevelData { spawn_x, spawn_y, spawn_z, spawn_angle, difficulty, difficulty_locked, .. } = self.data;
+        inbox.submit(Response::SpawnPosition(V3 { x: spawn_x, y: spawn_y, z: spawn_z }, spawn_angle));
+        inbox.submit(Response::Difficulty(difficulty as u8, difficulty_locked));
+        inbox.submit(self.time_packet());
+        self.send_weather(pid, inbox.reborrow());
+        self.teleport(pid, inbox.reborrow(), position);
+        // inbox.submit(Response::MoveFast());
+        inbox.submit(Response::Chat(format!("server says hi {}", name.as_str())));
+        if dead {
+            inbox.submit(Response::CombatDeath(eid, -1, String::new()));
+        }
+        self.send_inventory(pid, inbox);
+    }
+    fn send_inventory(&self, pid: usize, mut inbox: Inbox) {
+        for (slot, stack) in self.players[pid].inventory.iter().enumerate() {
+            if stack.is_some() {
+                inbox.submit(Response::SetInventorySlot(slot as u16, stack.clone(), 0));
+            }
+        }
+    }
+    /// Moves a player within their dimension, swapping the chunks they can see for
+    /// the ones around where they end up
+    pub(super) fn teleport(&mut self, pid: usize, mut inbox: Inbox, position: (f64, f64, f64)) {
+        self.players[pid].position = position;
+        self.players[pid].vitals.stop_falling();
+        self.move_player_entity(pid);
+        self.update_view(pid, inbox.reborrow());
+        let id = self.players[pid].movement.teleported(position);
+        inbox.submit(Response::Position(position, id));
+    }
+    /// Unloads the chunks that are too far from the player now, and loads the ones that have come into view
+    fn update_view(&mut self, pid: usize, mut inbox: Inbox) {
+        let player = &self.players[pid];
+        let view_distance = player.view_distance as i32;
+        let chunkx = (player.position.0 / 16.0).floor() as i32;
+        let chunkz = (player.position.2 / 16.0).floor() as i32;
+        let in_view = |(x, z): (i32, i32)| (x - chunkx).abs() <= view_distance && (z - chunkz).abs() <= view_distance;
+        let released = self.release_chunks(pid, |chunk| !in_view(chunk));
+        inbox.cancel(|response| match response {
+            Response::LoadChunk(x, z) | Response::UpdateLight(x, z, _) => released.contains(&(*x, *z)),
+            _ => false,
+        });
+        for (x, z) in released {
+            inbox.submit(Response::UnloadChunk(x, z));
+        }
+        inbox.submit(Response::CenterChunk(chunkx, chunkz));
+        for x in chunkx - view_distance..=chunkx + view_distance {
+            for z in chunkz - view_distance..=chunkz + view_distance {
+                if self.dimension(pid).viewers((x, z)) & 1 << pid == 0 {
+                    self.load_chunk(pid, inbox.reborrow(), (x, z));
+                }
+            }
+        }
+    }
+    /// Sends a player to another dimension (or respawns them in the same one)
+    pub(super) fn change_dimension(&mut self, pid: usize, mut inbox: Inbox, dimension: usize, position: (f64, f64, f64)) {
+        let released = self.release_chunks(pid, |_| true);
+        inbox.cancel(|response| matches!(response, Response::LoadChunk(..) | Response::UpdateLight(..)));
+        for (x, z) in released {
+            inbox.submit(Response::UnloadChunk(x, z));
+        }
+        let player = &mut self.players[pid];
+        player.dimension = dimension;
+        player.digging = None;
+        inbox.submit(Response::Respawn(player.gamemode, player.dimension, player.vitals.death));
+        // which forgets every entity it had
+        self.entities.forget_viewer(pid);
+        self.send_weather(pid, inbox.reborrow());
+        self.teleport(pid, inbox.reborrow(), position);
+        // the client starts over with an empty inventory
+        self.send_inventory(pid, inbox);
+    }
+    /// Stops sending a player the chunks they can see in their dimension, unless `keep`
+    /// says otherwise. Chunks nobody can see any more are forgotten, once they're saved.
+    /// Returns the chunks they can't see any more
+    fn release_chunks(&mut self, pid: usize, keep: impl Fn((i32, i32)) -> bool) -> Vec<(i32, i32)> {
+        let d = self.players[pid].dimension;
+        let dimension = &mut self.dimensions[d];
+        let mut released = vec![];
+        for (&pos, &idx) in &dimension.index {
+            let chunk = &mut dimension.chunks[idx as usize];
+            if chunk.visible_to & 1 << pid != 0 && !keep(pos) {
+                chunk.visible_to &= !(1 << pid);
+                released.push(pos);
+            }
+        }
+        // and stop waiting on the ones that haven't loaded yet
+        dimension.loading.retain(|pos, loading| {
+            if keep(*pos) {
+                return true;
+            }
+            loading.waiting &= !(1 << pid);
+            if loading.waiting == 0 {
+                loading.cancel();
+            }
+            loading.waiting != 0
+        });
+        for &pos in &released {
+            let dimension = &self.dimensions[d];
+            if dimension.chunks[dimension.index[&pos] as usize].visible_to == 0 {
+                self.unload_chunk(d, pos);
+            }
+        }
+        released
+    }
+    /// Where new players start out. If something's been built there since, they go on top of it
+    fn spawn_point(&mut self) -> (f64, f64, f64) {
+        let LevelData { spawn_x: x, spawn_y: y, spawn_z: z, .. } = self.data;
+        self.load_now(OVERWORLD, (x.div_euclid(16), z.div_euclid(16)));
+        let overworld = &self.dimensions[OVERWORLD];
+        let blocked = (0..2).any(|dy| overworld.block_at(V3 { x, y: y + dy, z }).is_some());
+        let y = if blocked { overworld.surface(x, z) } else { y };
+        (x as f64 + 0.5, y as f64, z as f64 + 0.5)
+    }
+    pub(crate) fn request_move(&mut self, pid: usize, mut inboxes: Inboxes, x: f64, y: f64, z: f64) {
+        if !self.check_move(pid, inboxes.reborrow(), (x, y, z)) {
+            return;
+        }
+        let player = &mut self.players[pid];
+        let inbox = inboxes.get(pid).unwrap();
+        let old_position = core::mem::replace(&mut player.position, (x, y, z));
+        let chunkx = (x / 16.0).floor() as i32;
+        let chunkz = (z / 16.0).floor() as i32;
+        let oldchunkx = (old_position.0 / 16.0).floor() as i32;
+        let oldchunkz = (old_position.2 / 16.0).floor() as i32;
+        if oldchunkx != chunkx || oldchunkz != chunkz {
+            self.update_view(pid, inbox);
+        }
+        self.move_player_entity(pid);
+        let blockx = x.floor() as i32;
+        let blockz = z.floor() as i32;
+        let oldblockx = old_position.0.floor() as i32;
+        let oldblockz = old_position.2.floor() as i32;
+struct Line {
+    current: (i32, i32),
+    end: (i32, i32),
+    diff: (i32, i32),
+    inc: (i32, i32),
+
+    error: i32,
+}
+impl Iterator for Line {
+    type Item = (i32, i32);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current == self.end {
+            return None;
+        }
+        let error = self.error;
+        if error > -self.diff.0 {
+            self.error -= self.diff.1;
+            self.current.0 += self.inc.0;
+        }
+        if error < self.diff.1 {
+            self.error += self.diff.0;
+            self.current.1 += self.inc.1;
+        }
+        Some(self.current)
+    }
+}
+impl Line {
+    fn new(start: (i32, i32), end: (i32, i32)) -> Self {
+        let diff = ((end.0 - start.0).abs(), (end.1 - start.1).abs());
+        Self {
+            current: start,
+            end,
+            diff,
+            inc: ([-1, 1][(start.0 < end.0) as usize], [-1, 1][(start.1 < end.1) as usize]),
+
+            error: if diff.0 > diff.1 { diff.0 } else { -diff.1 } / 2
+        }
+    }
+}
+        let dimension = self.players[pid].dimension;
+        for (x, z) in Line::new((oldblockx, oldblockz), (blockx, blockz)) {
+            if dimension == OVERWORLD && (0.0..10.0).contains(&y) && self.dimensions[dimension].in_world(V3(x, -1, z)) {
+                self.set_block(dimension, V3(x, -1, z), Some(Block::ANDESITE), inboxes.reborrow());
+                // inbox.submit(Response::SetBlock(V3(x, -1, z), BlockId(4)));
+            }
+        }
+        self.enter_portal(pid);
+    }
+    /// The things they tell us about how they're moving: 0 and 1 are sneaking, 3 and 4 sprinting
+    pub(crate) fn player_command(&mut self, pid: usize, action: i32) {
+        let player = &mut self.players[pid];
+        match action {
+            0 | 1 => player.sneaking = action == 0,
+            3 | 4 => player.sprinting = action == 3,
+            _ => {}
+        }
+    }
+    pub(crate) fn set_on_ground(&mut self, pid: usize, on_ground: bool) {
+        self.players[pid].on_ground = on_ground;
+        self.move_player_entity(pid);
+    }
+    pub(crate) fn run_command(&mut self, pid: usize, mut inboxes: Inboxes, cmd: &[u8]) {
+        let op = self.level.is_op(self.players[pid].name);
+        match cmd {
+            b"kill" => {
+                self.hurt(pid, inboxes, f32::MAX, vitals::Cause::Void);
+            }
+            _ if !op && matches!(cmd.split(|&c| c == b' ').next(), Some(b"gamemode" | b"setblock" | b"tp" | b"weather" | b"time")) => {
+                inboxes.get(pid).unwrap().submit(Response::Chat(format!("only ops can use {:?}", String::from_utf8_lossy(cmd))));
+            }
+            b"gamemode survival" => self.set_gamemode(pid, inboxes, GameMode::Survival),
+            b"gamemode creative" => self.set_gamemode(pid, inboxes, GameMode::Creative),
+            _ if cmd.starts_with(b"setblock ") => {
+                let args = String::from_utf8_lossy(&cmd[9..]);
+                let mut args = args.split_whitespace();
+                let mut coord = || args.next().and_then(|c| c.parse::<i32>().ok());
+                let pos = coord().zip(coord()).zip(coord()).map(|((x, y), z)| V3(x, y, z));
+                let block = args.next().map(|b| if b == "air" || b == "minecraft:air" { Ok(None) } else { Block::parse(b).map(Some).ok_or(b) });
+                let msg = match (pos, block) {
+                    (Some(pos), Some(Ok(block))) if self.dimension(pid).in_world(pos) => {
+                        self.set_block(self.players[pid].dimension, pos, block, inboxes.reborrow());
+                        format!("set {pos:?} to {}", block.map_or("minecraft:air".to_owned(), |b| b.to_string()))
+                    }
+                    (Some(pos), Some(Ok(_))) => format!("{pos:?} isn't loaded"),
+                    (_, Some(Err(b))) => format!("unknown block state {b:?}"),
+                    _ => "usage: setblock <x> <y> <z> <block state>".to_owned(),
+                };
+                inboxes.get(pid).unwrap().submit(Response::Chat(msg));
+            }
+            _ if cmd.starts_with(b"tp ") => {
+                let args = String::from_utf8_lossy(&cmd[3..]);
+                let mut args = args.split_whitespace();
+                let mut coord = || args.next().and_then(|c| c.parse::<f64>().ok());
+                let position = coord().zip(coord()).zip(coord()).map(|((x, y), z)| (x, y, z));
+                let dimension = match args.next() {
+                    Some(name) => self.find_dimension(name).ok_or(name),
+                    None => Ok(self.players[pid].dimension),
+                };
+                let mut inbox = inboxes.get(pid).unwrap();
+                match (position, dimension) {
+                    (Some(position), Ok(dimension)) => {
+                        if dimension == self.players[pid].dimension {
+                            self.teleport(pid, inbox.reborrow(), position);
+                        } else {
+                            self.change_dimension(pid, inbox.reborrow(), dimension, position);
+                        }
+                        inbox.submit(Response::Chat(format!("teleported to {position:?} in {}", self.dimensions[dimension].name)));
+                    }
+                    (_, Err(name)) => inbox.submit(Response::Chat(format!("unknown dimension {name:?}"))),
+                    _ => inbox.submit(Response::Chat("usage: tp <x> <y> <z> [dimension]".to_owned())),
+                }
+            }
+            _ if cmd == b"weather" || cmd.starts_with(b"weather ") => {
+                let msg = self.weather_command(&String::from_utf8_lossy(&cmd[7..]));
+                inboxes.get(pid).unwrap().submit(Response::Chat(msg));
+            }
+            _ if cmd == b"time" || cmd.starts_with(b"time ") => {
+                let msg = self.time_command(inboxes.reborrow(), &String::from_utf8_lossy(&cmd[4..]));
+                inboxes.get(pid).unwrap().submit(Response::Chat(msg));
+            }
+            _ => inboxes.get(pid).unwrap().submit(Response::Chat(format!("unknown command {:?}", String::from_utf8_lossy(cmd)))),
+        }
+    }
+    /// Runs a command from the server's console (or RCON), returning what to tell whoever sent it
+    pub fn console_command(&mut self, inboxes: Inboxes, cmd: &str) -> String {
+        let (name, args) = cmd.trim().split_once(' ').unwrap_or((cmd.trim(), ""));
+        match (name, args.trim()) {
+            ("status", "") => self.status(),
+            // these all write to the world while it's being copied
+            ("retry" | "save-path" | "overlay" | "save-all", _) if self.backup_running() => {
+                "a backup's being taken, so try again once it's done".to_owned()
+            }
+            ("retry", "") => self.retry_saving(inboxes),
+            ("save-path", "") => "usage: save-path <directory>".to_owned(),
+            ("save-path", path) => self.set_save_path(inboxes, path),
+            ("overlay", args) => self.overlay_command(inboxes, args),
+            ("save-all", "") => self.save_all(),
+            ("save-off", "") => self.set_saving(false),
+            ("save-on", "") => self.set_saving(true),
+            ("backup", args) => self.backup_command(args),
+            ("gamerule", args) => self.gamerule_command(args),
+            ("time", args) => self.time_command(inboxes, args),
+            ("weather", args) => self.weather_command(args),
+            _ => format!("unknown command {cmd:?}. try status, save-all, save-off, save-on, backup, gamerule, time, weather, retry, save-path <directory>, overlay or stop"),
+        }
+    }
+    fn set_gamemode(&mut self, pid: usize, mut inboxes: Inboxes, gamemode: GameMode) {
+        self.players[pid].gamemode = gamemode;
+        inboxes.get(pid).unwrap().submit(Response::GameEvent(3, match gamemode {
+            GameMode::Survival => 0.0,
+            GameMode::Creative => 1.0,
+        }));
+    }
+    pub(crate) fn chat_message(&self, mut inboxes: Inboxes, msg: &str) {
+        inboxes.retain(|_, mut inbox| {
+            inbox.submit(Response::Chat(msg.to_owned()));
+            true
+        });
+    }
+    pub(crate) fn set_held_item(&mut self, pid: usize, inboxes: Inboxes, hotbar_idx: i16) {
+        // everything that uses it indexes the hotbar with it
+        match u8::try_from(hotbar_idx) {
+            Ok(idx) if idx < 9 => self.players[pid].selected_item = idx,
+            _ => log::warn!("{} tried to hold hotbar slot {hotbar_idx}", self.players[pid].name.as_str()),
+        }
+    }
+    pub(crate) fn set_block(&mut self, dimension: usize, pos: V3<i32>, block: Option<Block>, inboxes: Inboxes) {
+        self.dimensions[dimension].set_block(pos, block);
+        let viewers = self.dimensions[dimension].viewers((pos.x.div_euclid(16), pos.z.div_euclid(16)));
+        inboxes.retain(|i, mut inbox| {
+            if viewers & 1 << i != 0 {
+                inbox.submit(Response::SetBlock(pos, block));
+            }
+            true
+        });
+        self.blocks_changed(viewers, movement::Changed::Block(pos));
+    }
+    pub(crate) fn logout(&mut self, pid: usize) {
+        let player = &mut self.players[pid];
+        player.digging = None;
+        player.portal = None;
+        player.online = false;
+        let eid = player.eid;
+        self.entities.remove(eid);
+        self.entities.forget_viewer(pid);
+        self.save_player(pid);
+        // anything they were the last to see is unloaded once the autosave's written it
+        self.release_chunks(pid, |_| false);
+    }
+    /// Returns whether it worked
+    fn save_player(&self, pid: usize) -> bool {
+        let player = &self.players[pid];
+        let data = PlayerData {
+            dimension: self.dimensions[player.dimension].name.clone(),
+            position: [player.position.0, player.position.1, player.position.2],
+            selected_item: player.selected_item as i32,
+            inventory: player.inventory.iter().enumerate().filter_map(|(slot, stack)| {
+                let ItemStack { item, count, tag } = stack.clone()?;
+                Some(SavedSlot { slot: PlayerData::saved_slot(slot)?, id: item, count, tag })
+            }).collect(),
+            vitals: self.save_vitals(&player.vitals),
+        };
+        match self.level.save_player(player.name, &data) {
+            Ok(()) => true,
+            Err(e) => {
+                log::error!("couldn't save {}'s player data: {e}", player.name.as_str());
+                false
+            }
+        }
+    }
+    /// Disconnects a player, telling them why
+    pub(crate) fn kick(&mut self, pid: usize, inboxes: Inboxes, reason: &str) {
+        log::info!("kicking {}: {reason}", self.players[pid].name.as_str());
+        inboxes.retain(|i, mut inbox| {
+            if i == pid {
+                inbox.submit(Response::Disconnect(reason.to_owned()));
+                inbox.close();
+            }
+            true
+        });
+        self.logout(pid);
+    }
+    /// Kicks everyone and saves everything, before the server stops
+    pub fn shutdown(&mut self, mut inboxes: Inboxes, reason: &str) {
+        self.finish_backup();
+        self.clear_queue(inboxes.reborrow(), reason);
+        for pid in self.online().collect::<Vec<_>>() {
+            self.kick(pid, inboxes.reborrow(), reason);
+        }
+        if !self.save_chunks() | !self.save_level_data() {
+            log::error!("shutting down with unsaved changes: {}", self.status());
+        }
+    }
+    /// Writes every chunk that's changed since it was loaded
+    /// Returns whether they all saved
+    fn save_chunks(&mut self) -> bool {
+        if self.level.in_memory_overlay() {
+            return true;
+        }
+        let mut saved = true;
+        for (d, dimension) in self.dimensions.iter_mut().enumerate() {
+            for (&pos, &idx) in &dimension.index {
+                let chunk = &mut dimension.chunks[idx as usize];
+                if !chunk.dirty {
+                    continue;
+                }
+                // no point filling the log while we're waiting on the admin
+                if self.level.storage.failing() {
+                    return false;
+                }
+                match self.level.save_chunk(&dimension.name, pos, &chunk.content, &chunk.heightmaps, &self.entities.saved_items(d, pos)) {
+                    Ok(()) => chunk.dirty = false,
+                    Err(e) => {
+                        log::error!("couldn't save chunk {pos:?}: {e}");
+                        saved = false;
+                    }
+                }
+            }
+        }
+        saved
+    }
+    fn unsaved_chunks(&self) -> usize {
+        self.dimensions.iter()
+            .map(|d| d.index.values().filter(|&&idx| d.chunks[idx as usize].dirty).count())
+            .sum()
+    }
+    fn tick(&mut self, mut inboxes: Inboxes) {
+        self.tick_time(inboxes.reborrow());
+        self.tick_weather(inboxes.reborrow());
+        self.tick_digging(inboxes.reborrow());
+        self.tick_entities(inboxes.reborrow());
+        self.tick_portals(inboxes.reborrow());
+        self.send_light_updates(inboxes.reborrow());
+        if self.tick.is_multiple_of(20) {
+            self.enforce_budget(inboxes.reborrow());
+        }
+        self.tick_recovery(inboxes.reborrow());
+        self.tick_queue(inboxes.reborrow());
+        self.tick_autosave();
+        self.tick_backup();
+        self.sync_entities(inboxes.reborrow());
+        self.tick_vitals(inboxes.reborrow());
+        self.tick_movement(inboxes.reborrow());
+        if self.tick % (5 * 20) == 0 {
+            let mut timed_out = vec![];
+            // they're pinged every tick, in tick_movement
+            inboxes.retain(|pid, _| {
+                if self.tick - self.players[pid].last_ping_ack > (20 * 5) {
+                    log::warn!("{} timed out", self.players[pid].name.as_str());
+                    timed_out.push(pid);
+                    false
+                } else {
+                    true
+                }
+            });
+            for pid in timed_out {
+                self.logout(pid);
+            }
+        }
+    }
+    pub(crate) fn set_creative_slot(&mut self, pid: usize, mut inboxes: Inboxes, slot: i16, stack: Option<ItemStack>) {
+        // survival players have to dig for things
+        if self.players[pid].gamemode != GameMode::Creative {
+            return;
+        }
+        // -1 is thrown out of the creative inventory
+        if slot == -1 {
+            if let Some(stack) = stack {
+                self.throw_item(pid, stack);
+            }
+        } else if let Some(slot) = self.players[pid].inventory.get_mut(slot as usize) {
+            *slot = stack;
+        }
+    }
+    pub(crate) fn closed_inventory(&mut self, pid: usize, window: u8) {
+
+    }
+    /// Lets a player see a chunk in their dimension. If nobody else could see it, it's sent
+    /// once the chunk loaders have read or generated it
+    pub(crate) fn load_chunk(&mut self, pid: usize, mut inbox: Inbox, pos: (i32, i32)) {
+        let dimension = self.players[pid].dimension;
+        let blocks = &mut self.dimensions[dimension];
+        if let Some(chunk) = blocks.index.get(&pos).and_then(|idx| blocks.chunks.get_mut(*idx as usize)) {
+            chunk.visible_to |= 1 << pid;
+            inbox.submit(Response::LoadChunk(pos.0, pos.1));
+            self.blocks_changed(1 << pid, movement::Changed::Chunk(pos));
+        } else if let Some(loading) = blocks.loading.get_mut(&pos) {
+            loading.waiting |= 1 << pid;
+        } else {
+            let loading = self.loader.load(dimension, blocks, pos, 1 << pid);
+            blocks.loading.insert(pos, loading);
+        }
+    }
+    /// Puts the chunks that have finished loading into the world, and sends them to
+    /// whoever's still waiting on them
+    pub fn finish_loading(&mut self, mut inboxes: Inboxes) {
+        let finished: Vec<_> = self.loader.finished().collect();
+        for loaded in finished {
+            // a newer load might have replaced it
+            if loaded.cancelled() {
+                continue;
+            }
+            let loader::Loaded { dimension, chunk: pos, content, heightmaps, light, items, .. } = loaded;
+            let blocks = &mut self.dimensions[dimension];
+            let waiting = match blocks.loading.remove(&pos) {
+                Some(loading) => loading.waiting,
+                None => continue, // nobody wants it any more
+            };
+            match blocks.index.get(&pos) {
+                // someone needed it sooner and loaded it themselves
+                Some(&idx) => blocks.chunks[idx as usize].visible_to |= waiting,
+                None => {
+                    blocks.insert_chunk(pos, content, heightmaps, light, waiting);
+                    self.load_items(dimension, items);
+                }
+            }
+            // the entities in it follow once the tick's done
+            inboxes.reborrow().retain(|pid, mut inbox| {
+                if waiting & 1 << pid != 0 {
+                    inbox.submit(Response::LoadChunk(pos.0, pos.1));
+                }
+                true
+            });
+            self.blocks_changed(waiting, movement::Changed::Chunk(pos));
+        }
+    }
+    /// Makes sure a chunk's in memory right now, for when we can't wait for the chunk loaders.
+    /// Nobody can see it yet, unless they were already waiting for it
+    fn load_now(&mut self, dimension: usize, pos: (i32, i32)) {
+        let blocks = &mut self.dimensions[dimension];
+        if blocks.index.contains_key(&pos) {
+            return;
+        }
+        let (content, heightmaps, items) = match self.level.load_chunk_or_log(&blocks.name, pos, blocks.min_y(), blocks.sections()) {
+            Some(saved) => saved,
+            None => {
+                let content = blocks.generator.generate(pos, blocks.min_y(), blocks.sections());
+                let heightmaps = Heightmaps::compute(&content);
+                (content, heightmaps, vec![])
+            }
+        };
+        let light = ChunkLight::compute(&content, blocks.has_skylight());
+        blocks.insert_chunk(pos, content, heightmaps, light, 0);
+        self.load_items(dimension, items);
+    }
+    pub(crate) fn request_view_distance(&mut self, pid: usize, mut inbox: Inbox, view_distance: u8) {
+        let view_distance = view_distance.max(1);
+        let player = &mut self.players[pid];
+        player.wanted_view_distance = view_distance;
+        if player.lobby {
+            return;
+        }
+        player.view_distance = view_distance.min(self.budget.view_distance);
+        inbox.submit(Response::SetRenderDistance(player.view_distance));
+        self.update_view(pid, inbox);
+    }
+}
\ No newline at end of file
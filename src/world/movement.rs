@@ -0,0 +1,336 @@
+//! Checking that players move the way their client would let them.
+//!
+//! Each move a client sends is one tick of vanilla's physics, so it's held up against what
+//! that tick could have done from the last move that made sense: how fast they could be
+//! going, that they're falling unless something's holding them up, and that they haven't
+//! gone through anything. A move that doesn't fit puts them back where they were, and enough
+//! of them in a row gets them kicked.
+//!
+//! A client's only wrong if it had the same world we do, and what we've sent them can take a
+//! while to get there when we're sending a lot. So nothing's judged on how long things take,
+//! only on what they've said they've received. Teleports carry an id they confirm, and what
+//! they send before that is from before the teleport. Every tick ends with a ping, and until
+//! they've answered one from after some blocks changed, the checks that depend on blocks are
+//! let off for moves near them. They can't sit on pings for long, since not answering them is
+//! how they time out.
+use crate::prelude::*;
+use super::physics::{self, Body};
+use super::{entity, World};
+use std::collections::VecDeque;
+
+const BODY: Body = Body { half_width: 0.3, height: 1.8 };
+const GRAVITY: f64 = 0.08;
+const DRAG: f64 = 0.98;
+/// Walking up a slab or a stair. It's more than a jump, which is 0.42
+const STEP: f64 = 0.6;
+/// How fast sprinting speeds them up, on the ground and in the air
+const SPRINT: f64 = 0.13 * 0.98;
+const AIR_SPRINT: f64 = 0.026 * 0.98;
+/// The push forward from jumping while sprinting
+const SPRINT_JUMP: f64 = 0.2;
+/// Sprint jumping along ice under a ceiling tops out a little under this. It's as fast as we'll
+/// believe they're going when we don't know what they're on
+const RUNNING: f64 = 1.5;
+/// Sprinting while flying in creative tops out a little under this
+const FLYING: f64 = 1.2;
+/// Diving with an elytra tops out a little under this
+const GLIDING: f64 = 4.0;
+/// What's let slide for rounding, and the odd thing we don't simulate
+const SLACK: f64 = 0.1;
+const FALL_SLACK: f64 = 0.03;
+/// How many moves a client can catch up on at once, after they've been held up on the way here
+const BURST: f64 = 100.0;
+/// How long after climbing, swimming or bouncing that they can go up or down however they like
+const EXEMPT_TICKS: u32 = 20;
+/// How far the violation score drops each tick, and where it gets them kicked
+const FORGIVENESS: f64 = 0.05;
+const KICK_SCORE: f64 = 40.0;
+/// Changes they haven't seen that we'll keep track of one by one. Past that it's all of them at once
+const MAX_CHANGES: usize = 64;
+
+/// Something in the world that's changed, that a player might not have seen yet
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Changed {
+    Block(V3<i32>),
+    /// Sent whole, or not there at all until it's sent
+    Chunk((i32, i32)),
+    Everything,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Movement {
+    /// The id of the last teleport they were sent, and whether they've confirmed it
+    teleport: i32,
+    confirmed: bool,
+    /// Where the last move that made sense left them, and how far it went
+    valid: (f64, f64, f64),
+    last_move: (f64, f64, f64),
+    on_ground: bool,
+    /// Moves they can send before they've sent more than we've ticked. It's the ticks that
+    /// count, not when the moves get here, and we catch up on ticks when we fall behind
+    allowance: f64,
+    /// Until this tick they've been somewhere that moves them up and down in ways we don't simulate
+    exempt_until: u32,
+    /// Goes up with each bad move and back down over time
+    violations: f64,
+    /// Knockback we've sent them and when. Until they've had it, they could be going that fast any time
+    push: Option<(u32, (f64, f64, f64))>,
+    /// What's changed since the newest ping they've answered, and when.
+    /// Pings are numbered by the tick they were sent at the end of
+    changed: VecDeque<(u32, Changed)>,
+    acked: u32,
+}
+impl Movement {
+    pub fn new() -> Self {
+        Self {
+            teleport: 0,
+            confirmed: false,
+            valid: (0.0, 0.0, 0.0),
+            last_move: (0.0, 0.0, 0.0),
+            on_ground: false,
+            allowance: BURST,
+            exempt_until: 0,
+            violations: 0.0,
+            push: None,
+            changed: VecDeque::new(),
+            acked: 0,
+        }
+    }
+    /// Starts them over at `position`. Returns the id they'll confirm it with
+    pub fn teleported(&mut self, position: (f64, f64, f64)) -> i32 {
+        self.teleport = self.teleport.wrapping_add(1).max(1);
+        self.confirmed = false;
+        self.valid = position;
+        self.last_move = (0.0, 0.0, 0.0);
+        // they might be standing on something, and jump straight away
+        self.on_ground = true;
+        self.teleport
+    }
+    /// The last tick they've had everything from
+    pub fn acked(&self) -> u32 {
+        self.acked
+    }
+    pub fn falling(&self) -> bool {
+        !self.on_ground && self.last_move.1 < 0.0
+    }
+    /// Lets them go as fast as `velocity` once they've got it
+    pub fn push(&mut self, tick: u32, velocity: (f64, f64, f64)) {
+        self.push = Some((tick, velocity));
+    }
+    /// Whether anything they might not have seen yet is close enough to matter to a move
+    fn unseen_near(&self, from: (f64, f64, f64), to: (f64, f64, f64)) -> bool {
+        let reach = BODY.half_width + 1.0;
+        let low = V3((from.0.min(to.0) - reach).floor() as i32, (from.1.min(to.1) - 2.0).floor() as i32, (from.2.min(to.2) - reach).floor() as i32);
+        let high = V3((from.0.max(to.0) + reach).floor() as i32, (from.1.max(to.1) + BODY.height + 1.0).floor() as i32, (from.2.max(to.2) + reach).floor() as i32);
+        self.changed.iter().any(|(_, changed)| match *changed {
+            Changed::Block(pos) => (low.x..=high.x).contains(&pos.x) && (low.y..=high.y).contains(&pos.y) && (low.z..=high.z).contains(&pos.z),
+            Changed::Chunk((x, z)) => (low.x.div_euclid(16)..=high.x.div_euclid(16)).contains(&x) && (low.z.div_euclid(16)..=high.z.div_euclid(16)).contains(&z),
+            Changed::Everything => true,
+        })
+    }
+}
+/// What a move that made sense leaves them with
+struct Judged {
+    on_ground: bool,
+    exempt: bool,
+}
+
+impl World {
+    /// Whether a move they've sent should happen. If it shouldn't, they've been sent back
+    pub(super) fn check_move(&mut self, pid: usize, mut inboxes: Inboxes, to: (f64, f64, f64)) -> bool {
+        let player = &mut self.players[pid];
+        // the dead stay where they fell
+        if player.vitals.dead() {
+            return false;
+        }
+        let movement = &mut player.movement;
+        // it's from before we moved them, and they'll be somewhere else once they've caught up
+        if !movement.confirmed {
+            return false;
+        }
+        if ![to.0, to.1, to.2].iter().all(|c| c.is_finite() && c.abs() < 3.0e7) {
+            self.kick(pid, inboxes, "Invalid move");
+            return false;
+        }
+        movement.allowance -= 1.0;
+        match self.judge_move(pid, to) {
+            Ok(Judged { on_ground, exempt }) => {
+                let tick = self.tick;
+                let movement = &mut self.players[pid].movement;
+                let motion = (to.0 - movement.valid.0, to.1 - movement.valid.1, to.2 - movement.valid.2);
+                let was_on_ground = core::mem::replace(&mut movement.on_ground, on_ground);
+                movement.last_move = motion;
+                movement.valid = to;
+                // they've been going as fast as they were pushed, and it wears off like anything else
+                if movement.push.is_some_and(|(sent, _)| movement.acked >= sent) {
+                    movement.push = None;
+                }
+                if exempt {
+                    movement.exempt_until = tick + EXEMPT_TICKS;
+                }
+                self.moved(pid, inboxes, motion, was_on_ground, on_ground, exempt);
+                true
+            }
+            Err((why, excess)) => {
+                self.reject_move(pid, inboxes.reborrow(), why, excess);
+                false
+            }
+        }
+    }
+    /// Works out whether their client could have made a move. If not, says why and by how much it's off
+    fn judge_move(&self, pid: usize, to: (f64, f64, f64)) -> Result<Judged, (&'static str, f64)> {
+        let player = &self.players[pid];
+        let movement = &player.movement;
+        let dimension = &self.dimensions[player.dimension];
+        let from = movement.valid;
+        let motion = (to.0 - from.0, to.1 - from.1, to.2 - from.2);
+        if movement.allowance < 0.0 {
+            return Err(("moving more often than the game ticks", -movement.allowance));
+        }
+        // the blocks around them only count once they've seen them, and we've got them
+        let loaded = [from, to].iter().all(|&p| dimension.index.contains_key(&entity::chunk_of(p)));
+        let in_sync = loaded && !movement.unseen_near(from, to);
+        let creative = player.gamemode == GameMode::Creative;
+        let gliding = !movement.on_ground
+            && player.inventory[6].as_ref().is_some_and(|stack| stack.item.name() == "minecraft:elytra");
+        let unusual = loaded && (held_up(dimension, from) || held_up(dimension, to));
+
+        let horizontal = motion.0.hypot(motion.2);
+        let last = movement.last_move.0.hypot(movement.last_move.2);
+        let pushed = movement.push.map_or((0.0, f64::MIN), |(_, v)| (v.0.hypot(v.2) + SPRINT, v.1));
+        let limit = if gliding {
+            GLIDING
+        } else if creative {
+            FLYING
+        } else if !in_sync {
+            // we don't know what they're standing on, so it's the best of both
+            (last * 0.91 + SPRINT + SPRINT_JUMP).min(RUNNING)
+        } else if movement.on_ground {
+            let slipperiness = physics::slipperiness(physics::ground_under(dimension, from));
+            let jump = if motion.1 > 0.0 { SPRINT_JUMP } else { 0.0 };
+            last * slipperiness * 0.91 + SPRINT * 0.216 / slipperiness.powi(3) + jump
+        } else {
+            last * 0.91 + AIR_SPRINT
+        }
+        .max(pushed.0);
+        if horizontal > limit + SLACK {
+            return Err(("moving too fast", horizontal - limit));
+        }
+        let judged = !creative && !gliding && !unusual && self.tick >= movement.exempt_until;
+        // there's no telling what they're standing on, so we take their word for it. But they
+        // can only go up off something, so they're coming back down before they go up again
+        if !in_sync {
+            let rise = if movement.last_move.1 <= 0.0 { STEP } else { (movement.last_move.1 - GRAVITY) * DRAG }.max(pushed.1);
+            if judged && motion.1 > rise + FALL_SLACK {
+                return Err(("flying", motion.1 - rise));
+            }
+            return Ok(Judged { on_ground: player.on_ground, exempt: unusual });
+        }
+
+        if judged {
+            let rise = if movement.on_ground {
+                STEP
+            } else {
+                let fall = (movement.last_move.1 - GRAVITY) * DRAG;
+                BODY.travel(dimension, from, (0.0, fall, 0.0)).position.1 - from.1
+            }
+            .max(pushed.1);
+            if motion.1 > rise + FALL_SLACK {
+                return Err(("flying", motion.1 - rise));
+            }
+        }
+        // being stuck in something that's appeared around them is fine, so long as they're getting out
+        if !BODY.fits(dimension, to) && BODY.fits(dimension, from) {
+            return Err(("moving into a block", 1.0));
+        }
+        // fast enough to go all the way through something without ending up in it
+        let travelled = BODY.travel(dimension, from, motion).position;
+        let off = (travelled.0 - to.0).hypot(travelled.2 - to.2);
+        if horizontal > 1.0 && off > 1.0 {
+            return Err(("moving through blocks", off));
+        }
+        let on_ground = BODY.travel(dimension, to, (0.0, -FALL_SLACK, 0.0)).on_ground;
+        Ok(Judged { on_ground, exempt: unusual })
+    }
+    /// Puts them back where they last made sense, and kicks them if they've been at it too much
+    fn reject_move(&mut self, pid: usize, mut inboxes: Inboxes, why: &str, excess: f64) {
+        let player = &mut self.players[pid];
+        player.movement.violations += 1.0 + (excess * 4.0).min(9.0);
+        log::warn!("{} was {why} ({excess:.2} too far), so they've been put back", player.name.as_str());
+        if player.movement.violations > KICK_SCORE {
+            self.kick(pid, inboxes, "You moved wrongly too many times");
+            return;
+        }
+        let valid = player.movement.valid;
+        self.teleport(pid, inboxes.get(pid).unwrap(), valid);
+    }
+    pub(crate) fn confirm_teleport(&mut self, pid: usize, id: i32) {
+        let movement = &mut self.players[pid].movement;
+        if id == movement.teleport {
+            movement.confirmed = true;
+        }
+    }
+    pub(crate) fn acknowledge_ping(&mut self, pid: usize, id: i32) {
+        let player = &mut self.players[pid];
+        // answering a ping they've answered before doesn't show they're still there
+        if id as u32 > player.movement.acked && id as u32 <= self.tick {
+            let acked = id as u32;
+            player.movement.acked = acked;
+            player.movement.changed.retain(|&(tick, _)| tick >= acked);
+            player.last_ping_ack = self.tick;
+        }
+    }
+    /// Notes that something these players can see has changed, so they'll have the old
+    /// blocks there until they catch up
+    pub(super) fn blocks_changed(&mut self, viewers: u32, what: Changed) {
+        for (pid, player) in self.players.iter_mut().enumerate() {
+            if viewers & 1 << pid != 0 {
+                let changed = &mut player.movement.changed;
+                if changed.len() >= MAX_CHANGES {
+                    changed.clear();
+                    changed.push_back((self.tick, Changed::Everything));
+                } else {
+                    changed.push_back((self.tick, what));
+                }
+            }
+        }
+    }
+    /// Goes after everything else in the tick, so the ping says when they've had all of it
+    pub(super) fn tick_movement(&mut self, inboxes: Inboxes) {
+        let tick = self.tick;
+        inboxes.retain(|pid, mut inbox| {
+            let movement = &mut self.players[pid].movement;
+            movement.violations = (movement.violations - FORGIVENESS).max(0.0);
+            movement.allowance = (movement.allowance + 1.0).min(BURST);
+            inbox.submit(Response::Ping(tick as i32));
+            true
+        });
+    }
+}
+/// Whether something at `position` is in or next to something that moves them up and down
+/// in ways we don't simulate, like water, ladders or slime
+fn held_up(dimension: &super::Dimension, (x, y, z): (f64, f64, f64)) -> bool {
+    use BlockKind::*;
+    let reach = BODY.half_width + 0.1;
+    for bx in (x - reach).floor() as i32..=(x + reach).floor() as i32 {
+        for bz in (z - reach).floor() as i32..=(z + reach).floor() as i32 {
+            for by in (y - 1.0).floor() as i32..=(y + BODY.height).floor() as i32 {
+                let block = match dimension.block_at(V3(bx, by, bz)) {
+                    Some(block) => block,
+                    None => continue,
+                };
+                let unusual = matches!(
+                    block.kind(),
+                    Water | Lava | BubbleColumn | Ladder | Vine | Scaffolding | Cobweb | PowderSnow
+                        | TwistingVines | TwistingVinesPlant | WeepingVines | WeepingVinesPlant
+                        | CaveVines | CaveVinesPlant | SweetBerryBush | HoneyBlock | SlimeBlock
+                ) || block.info().name.ends_with("_bed")
+                    || block.get() == Some(props::Waterlogged(true));
+                if unusual {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
@@ -0,0 +1,115 @@
+//! Moving boxes through blocks the way vanilla does: one axis at a time, y first, each
+//! clipped by whatever collision boxes are in the way.
+//!
+//! Chunks that aren't loaded count as solid, so nothing wanders off into them.
+use crate::prelude::*;
+use super::Dimension;
+
+/// A box that's `2 * half_width` across, standing on `position`
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Body {
+    pub half_width: f64,
+    pub height: f64,
+}
+/// Where a body ended up after trying to move
+pub(super) struct Moved {
+    pub position: (f64, f64, f64),
+    /// What's left of the motion. It's stopped along any axis that hit something
+    pub motion: (f64, f64, f64),
+    pub on_ground: bool,
+}
+/// How far into a box two boxes can touch without counting as overlapping
+const EPSILON: f64 = 1e-7;
+
+type Aabb = [f64; 6];
+
+impl Body {
+    fn aabb(&self, (x, y, z): (f64, f64, f64)) -> Aabb {
+        [x - self.half_width, y, z - self.half_width, x + self.half_width, y + self.height, z + self.half_width]
+    }
+    /// Moves as far as it can along `motion`
+    pub fn travel(&self, dimension: &Dimension, position: (f64, f64, f64), motion: (f64, f64, f64)) -> Moved {
+        let mut aabb = self.aabb(position);
+        let swept = [
+            aabb[0] + motion.0.min(0.0), aabb[1] + motion.1.min(0.0), aabb[2] + motion.2.min(0.0),
+            aabb[3] + motion.0.max(0.0), aabb[4] + motion.1.max(0.0), aabb[5] + motion.2.max(0.0),
+        ];
+        let boxes = collision_boxes(dimension, swept);
+        let mut moved = [motion.0, motion.1, motion.2];
+        for axis in [1, 0, 2] {
+            let offset = boxes.iter().fold(moved[axis], |offset, b| clip(axis, &aabb, b, offset));
+            aabb[axis] += offset;
+            aabb[axis + 3] += offset;
+            moved[axis] = offset;
+        }
+        let stopped = |axis: usize, wanted: f64| if moved[axis] != wanted { 0.0 } else { wanted };
+        Moved {
+            position: (aabb[0] + self.half_width, aabb[1], aabb[2] + self.half_width),
+            motion: (stopped(0, motion.0), stopped(1, motion.1), stopped(2, motion.2)),
+            on_ground: motion.1 < 0.0 && moved[1] != motion.1,
+        }
+    }
+    /// Whether it's clear of every block at `position`. Touching them is fine
+    pub fn fits(&self, dimension: &Dimension, position: (f64, f64, f64)) -> bool {
+        // a little smaller, since clients round off where they are
+        let mut aabb = self.aabb(position);
+        for axis in 0..3 {
+            aabb[axis] += 1e-3;
+            aabb[axis + 3] -= 1e-3;
+        }
+        !collision_boxes(dimension, aabb).iter().any(|b| (0..3).all(|axis| overlaps(axis, &aabb, b)))
+    }
+}
+fn overlaps(axis: usize, a: &Aabb, b: &Aabb) -> bool {
+    a[axis] < b[axis + 3] - EPSILON && a[axis + 3] > b[axis] + EPSILON
+}
+/// How far `aabb` can go along `axis` (up to `offset`) before it runs into `b`
+fn clip(axis: usize, aabb: &Aabb, b: &Aabb, offset: f64) -> f64 {
+    if !(0..3).filter(|&other| other != axis).all(|other| overlaps(other, aabb, b)) {
+        return offset;
+    }
+    if offset > 0.0 && aabb[axis + 3] <= b[axis] + EPSILON {
+        offset.min(b[axis] - aabb[axis + 3])
+    } else if offset < 0.0 && aabb[axis] >= b[axis + 3] - EPSILON {
+        offset.max(b[axis + 3] - aabb[axis])
+    } else {
+        offset
+    }
+}
+/// The collision boxes of every block touching `area`, in world coordinates
+fn collision_boxes(dimension: &Dimension, area: Aabb) -> Vec<Aabb> {
+    let mut boxes = vec![];
+    for x in area[0].floor() as i32..area[3].ceil() as i32 {
+        for z in area[2].floor() as i32..area[5].ceil() as i32 {
+            if !dimension.index.contains_key(&(x.div_euclid(16), z.div_euclid(16))) {
+                boxes.push([x as f64, area[1].floor() - 1.0, z as f64, x as f64 + 1.0, area[4].ceil() + 1.0, z as f64 + 1.0]);
+                continue;
+            }
+            // fences and walls are taller than a block, so they reach up from the one below
+            for y in area[1].floor() as i32 - 1..area[4].ceil() as i32 {
+                let block = match dimension.block_at(V3(x, y, z)) {
+                    Some(block) => block,
+                    None => continue,
+                };
+                for &[x1, y1, z1, x2, y2, z2] in block.collision_boxes() {
+                    let (x, y, z) = (x as f64, y as f64, z as f64);
+                    boxes.push([x + x1 as f64, y + y1 as f64, z + z1 as f64, x + x2 as f64, y + y2 as f64, z + z2 as f64]);
+                }
+            }
+        }
+    }
+    boxes
+}
+/// How much of its speed something sliding over `block` keeps each tick
+pub(super) fn slipperiness(block: Option<Block>) -> f64 {
+    match block.map(|b| b.kind()) {
+        Some(BlockKind::Ice | BlockKind::PackedIce | BlockKind::FrostedIce) => 0.98,
+        Some(BlockKind::BlueIce) => 0.989,
+        Some(BlockKind::SlimeBlock) => 0.8,
+        _ => 0.6,
+    }
+}
+/// The block under something standing at `position`, that decides how slippery the ground is
+pub(super) fn ground_under(dimension: &Dimension, position: (f64, f64, f64)) -> Option<Block> {
+    dimension.block_at(V3(position.0.floor() as i32, (position.1 - 0.5000001).floor() as i32, position.2.floor() as i32))
+}
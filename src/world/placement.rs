@@ -0,0 +1,437 @@
+//! Working out which state a block item places, and where it goes.
+//!
+//! The client predicts all of this itself, so anything we disagree on shows up as a block
+//! snapping into a different shape once our update arrives. The rules follow vanilla's
+//! `getStateForPlacement`s, minus the blocks nobody will notice.
+use crate::prelude::*;
+use super::{vitals, World, Dimension, HOTBAR, OFFHAND};
+
+/// What the player clicked on, and how they were looking at it
+struct Click {
+    pos: V3<i32>,
+    face: BlockFace,
+    /// Where on the block the cursor was, from 0 to 1 along each axis
+    cursor: V3<f32>,
+    yaw: f32,
+    pitch: f32,
+}
+impl Click {
+    /// The direction the player is looking in, including straight up or down
+    fn looking(&self) -> BlockFace {
+        if self.pitch > 45.0 {
+            BlockFace::Bottom
+        } else if self.pitch < -45.0 {
+            BlockFace::Top
+        } else {
+            BlockFace::from_yaw(self.yaw)
+        }
+    }
+    /// Whether the click was on the top half of a block's side
+    fn upper(&self) -> bool {
+        self.face == BlockFace::Bottom || (self.face != BlockFace::Top && self.cursor.y > 0.5)
+    }
+}
+
+fn facing(face: BlockFace) -> props::Facing {
+    match face {
+        BlockFace::Top => props::Facing::Up,
+        BlockFace::Bottom => props::Facing::Down,
+        BlockFace::North => props::Facing::North,
+        BlockFace::South => props::Facing::South,
+        BlockFace::East => props::Facing::East,
+        BlockFace::West => props::Facing::West,
+    }
+}
+fn face(facing: props::Facing) -> BlockFace {
+    match facing {
+        props::Facing::Up => BlockFace::Top,
+        props::Facing::Down => BlockFace::Bottom,
+        props::Facing::North => BlockFace::North,
+        props::Facing::South => BlockFace::South,
+        props::Facing::East => BlockFace::East,
+        props::Facing::West => BlockFace::West,
+    }
+}
+fn facing_of(block: Block) -> Option<BlockFace> {
+    block.get::<props::Facing>().map(face)
+}
+fn is_stairs(block: Block) -> bool {
+    block.info().name.ends_with("_stairs")
+}
+fn is_slab(block: Block) -> bool {
+    block.info().name.ends_with("_slab")
+}
+/// Blocks that take up the top *and* bottom half of their space, like doors and tall grass
+fn is_two_high(block: Block) -> bool {
+    matches!(block.get(), Some(props::Half::Upper | props::Half::Lower))
+}
+fn is_water_source(block: Option<Block>) -> bool {
+    block.is_some_and(|b| b.kind() == BlockKind::Water && b.get() == Some(props::Level(0)))
+}
+/// Blocks that get replaced instead of placed against, like grass or water
+fn replaceable(block: Option<Block>) -> bool {
+    use BlockKind::*;
+    let block = match block {
+        Some(block) => block,
+        None => return true,
+    };
+    match block.kind() {
+        Snow => block.get() == Some(props::Layers(1)),
+        Air | CaveAir | VoidAir | Water | Lava | BubbleColumn | Grass | Fern | DeadBush | TallGrass | LargeFern
+        | Seagrass | TallSeagrass | Vine | GlowLichen | Fire | SoulFire | StructureVoid | Light
+        | CrimsonRoots | WarpedRoots | NetherSprouts | HangingRoots => true,
+        _ => false,
+    }
+}
+/// The `wall_` version of torches, signs and the like, which hang off the side of a block
+fn wall_variant(block: Block) -> Option<Block> {
+    let name = &block.info().name[10..];
+    if !["torch", "sign", "banner", "skull", "head", "fan"].iter().any(|s| name.ends_with(s)) {
+        return None;
+    }
+    let wall = match name.rsplit_once('_') {
+        Some((prefix, last)) => format!("{prefix}_wall_{last}"),
+        None => format!("wall_{name}"),
+    };
+    BlockInfo::from_name(&wall).map(|b| b.default_state())
+}
+/// Whether the collision boxes of `block` cover every one of `points` on its `face`.
+/// Points are (u, v) pairs across the face, in the order of the two axes that aren't its normal
+fn covers(block: Option<Block>, face: BlockFace, points: &[(f32, f32)]) -> bool {
+    let boxes = match block {
+        Some(block) => block.collision_boxes(),
+        None => return false,
+    };
+    let (axis, positive) = match face {
+        BlockFace::West | BlockFace::East => (0, face == BlockFace::East),
+        BlockFace::Bottom | BlockFace::Top => (1, face == BlockFace::Top),
+        BlockFace::North | BlockFace::South => (2, face == BlockFace::South),
+    };
+    let (p, q) = ((axis + 1) % 3, (axis + 2) % 3);
+    points.iter().all(|&(u, v)| boxes.iter().any(|b| {
+        let touches = if positive { b[axis + 3] >= 1.0 } else { b[axis] <= 0.0 };
+        touches && b[p] <= u && u <= b[p + 3] && b[q] <= v && v <= b[q + 3]
+    }))
+}
+/// Whether something can hang off this face of the block. Checked on a grid, so
+/// stairs count even though their back is two boxes
+fn sturdy(block: Option<Block>, face: BlockFace) -> bool {
+    let grid: Vec<_> = (0..4).flat_map(|u| (0..4).map(move |v| (u as f32 / 4.0 + 0.125, v as f32 / 4.0 + 0.125))).collect();
+    covers(block, face, &grid)
+}
+/// Whether something small (a torch, a pressure plate) can stand on the middle of this face
+fn supports_center(block: Option<Block>, face: BlockFace) -> bool {
+    covers(block, face, &[(0.5, 0.5)])
+}
+fn is_soil(block: Option<Block>) -> bool {
+    use BlockKind::*;
+    block.is_some_and(|b| matches!(b.kind(), GrassBlock | Dirt | CoarseDirt | Podzol | RootedDirt | Farmland | MossBlock | Mud))
+}
+fn is_plant(block: Block) -> bool {
+    use BlockKind::*;
+    let name = block.info().name;
+    name.ends_with("_sapling") || name.ends_with("_tulip") || matches!(block.kind(),
+        Dandelion | Poppy | BlueOrchid | Allium | AzureBluet | OxeyeDaisy | Cornflower | LilyOfTheValley | WitherRose
+        | Grass | Fern | TallGrass | LargeFern | Sunflower | Lilac | RoseBush | Peony | SweetBerryBush | Azalea | FloweringAzalea
+    )
+}
+
+impl World {
+    pub(crate) fn set_rotation(&mut self, pid: usize, yaw: f32, pitch: f32) {
+        let player = &mut self.players[pid];
+        player.yaw = yaw;
+        player.pitch = pitch;
+        self.move_player_entity(pid);
+    }
+    pub(crate) fn use_item_at_block(&mut self, pid: usize, mut inboxes: Inboxes, pos: V3<i32>, hand: Hand, face: BlockFace, cursor: V3<f32>) {
+        // they'll have put something there already, if they were holding a block
+        if !self.within_reach(pid, pos) {
+            log::warn!("{} tried to use {pos:?}, out of reach", self.players[pid].name.as_str());
+            self.resend_block(pid, inboxes.reborrow(), pos);
+            self.resend_block(pid, inboxes, pos.step(face));
+            return;
+        }
+        let player = &self.players[pid];
+        let inventory_slot = match hand {
+            Hand::Main => HOTBAR + player.selected_item as usize,
+            Hand::Secondary => OFFHAND,
+        };
+        let dimension = player.dimension;
+        let held = player.inventory[inventory_slot].as_ref().map(|i| i.item);
+        // sneaking gets past the bed to place against it, like vanilla
+        if !player.sneaking && self.dimensions[dimension].block_at(pos).is_some_and(vitals::is_bed) {
+            self.use_bed(pid, inboxes.get(pid).unwrap(), dimension, pos);
+            return;
+        }
+        if held.is_some_and(|i| i.name() == "minecraft:flint_and_steel") {
+            self.light_fire(dimension, inboxes, pos.step(face));
+            return;
+        }
+        let block = match held.and_then(|i| i.block()) {
+            Some(block) => block,
+            None => return,
+        };
+        let click = Click { pos, face, cursor, yaw: player.yaw, pitch: player.pitch };
+        let placed = match self.dimensions[dimension].placement(block, &click) {
+            Some(placed) => placed,
+            None => return,
+        };
+        if placed.iter().any(|(pos, state)| !state.collision_boxes().is_empty() && self.does_entity_collide(dimension, *pos)) {
+            return;
+        }
+        for &(pos, state) in &placed {
+            self.set_block(dimension, pos, Some(state), inboxes.reborrow());
+        }
+        for &(pos, _) in &placed {
+            self.update_neighbours(dimension, inboxes.reborrow(), pos);
+        }
+        let player = &mut self.players[pid];
+        if player.gamemode == GameMode::Survival {
+            let slot = &mut player.inventory[inventory_slot];
+            if let Some(stack) = slot {
+                stack.count -= 1;
+                if stack.count == 0 {
+                    *slot = None;
+                }
+            }
+            inboxes.get(pid).unwrap().submit(Response::SetInventorySlot(inventory_slot as u16, slot.clone(), 0));
+        }
+    }
+}
+impl Dimension {
+    /// Where `block` goes and the states it takes, or None if it can't be placed
+    fn placement(&self, block: Block, click: &Click) -> Option<Vec<(V3<i32>, Block)>> {
+        // a slab clicked on its empty half turns into a double slab
+        let merges = |existing: Option<Block>, clicked: bool| match existing {
+            Some(existing) if existing.kind() == block.kind() && is_slab(existing) => match existing.get() {
+                Some(props::Type::Double) => false,
+                _ if !clicked => true,
+                Some(props::Type::Bottom) => click.face == BlockFace::Top || click.face.is_horizontal() && click.cursor.y > 0.5,
+                _ => click.face == BlockFace::Bottom || click.face.is_horizontal() && click.cursor.y <= 0.5,
+            },
+            _ => false,
+        };
+        let clicked = self.block_at(click.pos);
+        let (pos, existing) = if replaceable(clicked) || merges(clicked, true) {
+            (click.pos, clicked)
+        } else {
+            let pos = click.pos.step(click.face);
+            (pos, self.block_at(pos))
+        };
+        if !self.in_world(pos) {
+            return None;
+        }
+        if merges(existing, pos == click.pos) {
+            let double = existing.unwrap().with(props::Type::Double).with(props::Waterlogged(false));
+            return Some(vec![(pos, double)]);
+        }
+        if !replaceable(existing) {
+            return None;
+        }
+
+        let looking = BlockFace::from_yaw(click.yaw);
+        let mut state = block;
+        let mut hung = false;
+        if click.face.is_horizontal() {
+            if let Some(wall) = wall_variant(block) {
+                // wall variants face away from their wall
+                state = wall.with(facing(click.face));
+                hung = true;
+            }
+        } else if click.face == BlockFace::Bottom && wall_variant(block).is_some() {
+            // nothing to stand on
+            return None;
+        }
+        if let Some(props::Rotation(_)) = state.get() {
+            state = state.with(props::Rotation(((click.yaw + 180.0) * 16.0 / 360.0 + 0.5).floor() as i32 as u8 & 15));
+        }
+        if state.get::<props::Axis>().is_some() {
+            state = state.with(match click.face {
+                BlockFace::Top | BlockFace::Bottom => props::Axis::Y,
+                BlockFace::North | BlockFace::South => props::Axis::Z,
+                BlockFace::East | BlockFace::West => props::Axis::X,
+            });
+        }
+        // buttons and levers
+        if state.get::<props::Face>().is_some() {
+            let (attached, direction) = match click.face {
+                BlockFace::Top => (props::Face::Floor, looking),
+                BlockFace::Bottom => (props::Face::Ceiling, looking),
+                side => (props::Face::Wall, side),
+            };
+            state = state.with(attached).with(facing(direction));
+        } else if !hung && state.get::<props::Facing>().is_some() {
+            let values = state.info().properties.iter().find(|(n, _)| *n == "facing").unwrap().1;
+            let name = &state.info().name[10..];
+            let direction = if values.contains(&"up") {
+                match state.kind() {
+                    BlockKind::Observer => click.looking(),
+                    BlockKind::EndRod | BlockKind::LightningRod | BlockKind::AmethystCluster => click.face,
+                    _ if name.ends_with("shulker_box") || name.ends_with("_bud") => click.face,
+                    _ => click.looking().opposite(),
+                }
+            } else if values.contains(&"down") {
+                // hoppers point into whatever they were placed against
+                match click.face.opposite() {
+                    BlockFace::Top => BlockFace::Bottom,
+                    direction => direction,
+                }
+            } else if name.ends_with("_trapdoor") {
+                if click.face.is_horizontal() { click.face } else { looking.opposite() }
+            } else if name.ends_with("anvil") {
+                looking.clockwise()
+            } else if is_stairs(state) || name.ends_with("_door") || name.ends_with("_bed") || name.ends_with("_fence_gate") || name.ends_with("campfire") {
+                looking
+            } else {
+                looking.opposite()
+            };
+            state = state.with(facing(direction));
+        }
+        if state.info().name.ends_with("_trapdoor") {
+            let top = if click.face.is_horizontal() { click.cursor.y > 0.5 } else { click.face == BlockFace::Bottom };
+            state = state.with(if top { props::Half::Top } else { props::Half::Bottom });
+        } else if is_stairs(state) {
+            state = state.with(if click.upper() { props::Half::Top } else { props::Half::Bottom });
+            state = state.with(self.stair_shape(pos, state));
+        } else if is_slab(state) {
+            state = state.with(if click.upper() { props::Type::Top } else { props::Type::Bottom });
+        }
+        if state.info().name.ends_with("_door") {
+            state = state.with(self.door_hinge(pos, state, click));
+        }
+        state = state.with(props::Waterlogged(is_water_source(existing)));
+        if !self.supported(pos, state) {
+            return None;
+        }
+
+        let mut placed = vec![(pos, state)];
+        if is_two_high(state) {
+            placed[0].1 = state.with(props::Half::Lower);
+            placed.push((pos.step(BlockFace::Top), state.with(props::Half::Upper)));
+        } else if let (Some(props::Part::Foot), Some(facing)) = (state.get(), facing_of(state)) {
+            placed.push((pos.step(facing), state.with(props::Part::Head)));
+        }
+        for (pos, state) in &mut placed[1..] {
+            let existing = self.block_at(*pos);
+            if !self.in_world(*pos) || !replaceable(existing) {
+                return None;
+            }
+            *state = state.with(props::Waterlogged(is_water_source(existing)));
+        }
+        Some(placed)
+    }
+    fn stair_shape(&self, pos: V3<i32>, stairs: Block) -> props::Shape {
+        let half = stairs.get::<props::Half>();
+        let facing = match facing_of(stairs) {
+            Some(facing) => facing,
+            None => return props::Shape::Straight,
+        };
+        let stairs_at = |pos| self.block_at(pos).filter(|b| is_stairs(*b) && b.get() == half).and_then(facing_of);
+        // a corner only forms if the stairs on the other side don't continue the straight run
+        let free = |side| stairs_at(pos.step(side)) != Some(facing);
+        let turns = |other: BlockFace| other != facing && other != facing.opposite();
+        if let Some(front) = stairs_at(pos.step(facing)).filter(|f| turns(*f) && free(f.opposite())) {
+            return if front == facing.counter_clockwise() { props::Shape::OuterLeft } else { props::Shape::OuterRight };
+        }
+        if let Some(back) = stairs_at(pos.step(facing.opposite())).filter(|f| turns(*f) && free(*f)) {
+            return if back == facing.counter_clockwise() { props::Shape::InnerLeft } else { props::Shape::InnerRight };
+        }
+        props::Shape::Straight
+    }
+    fn door_hinge(&self, pos: V3<i32>, door: Block, click: &Click) -> props::Hinge {
+        let facing = facing_of(door).unwrap_or(BlockFace::North);
+        // make double doors when there's already one next to us
+        let neighbour = self.block_at(pos.step(facing.counter_clockwise()));
+        if neighbour.is_some_and(|n| n.kind() == door.kind() && facing_of(n) == Some(facing) && n.get() == Some(props::Hinge::Left)) {
+            return props::Hinge::Right;
+        }
+        let dx = click.pos.x as f32 + click.cursor.x - pos.x as f32;
+        let dz = click.pos.z as f32 + click.cursor.z - pos.z as f32;
+        let right = match facing {
+            BlockFace::North => dx > 0.5,
+            BlockFace::South => dx < 0.5,
+            BlockFace::West => dz < 0.5,
+            BlockFace::East => dz > 0.5,
+            _ => false,
+        };
+        if right { props::Hinge::Right } else { props::Hinge::Left }
+    }
+    /// Whether the blocks around `pos` can hold up `block`
+    fn supported(&self, pos: V3<i32>, block: Block) -> bool {
+        use BlockKind::*;
+        let below = self.block_at(pos.step(BlockFace::Bottom));
+        let name = &block.info().name[10..];
+        if block.get() == Some(props::Half::Upper) {
+            // the bottom half holds it up
+            return true;
+        }
+        if let Some(attached) = block.get::<props::Face>().filter(|_| block.kind() != Grindstone) {
+            return match attached {
+                props::Face::Floor => sturdy(below, BlockFace::Top),
+                props::Face::Ceiling => sturdy(self.block_at(pos.step(BlockFace::Top)), BlockFace::Bottom),
+                props::Face::Wall => facing_of(block).is_some_and(|f| sturdy(self.block_at(pos.step(f.opposite())), f)),
+            };
+        }
+        if name.contains("wall_") && !name.ends_with("_skull") && !name.ends_with("_head") {
+            return facing_of(block).is_some_and(|f| sturdy(self.block_at(pos.step(f.opposite())), f));
+        }
+        match block.kind() {
+            Torch | SoulTorch | RedstoneTorch => supports_center(below, BlockFace::Top),
+            NetherPortal => self.portal_intact(pos, block),
+            Wheat | Carrots | Potatoes | Beetroots | PumpkinStem | MelonStem => below.is_some_and(|b| b.kind() == Farmland),
+            DeadBush => is_soil(below) || below.is_some_and(|b| matches!(b.kind(), Sand | RedSand) || b.info().name.ends_with("terracotta")),
+            RedstoneWire | Repeater | Comparator | Rail | PoweredRail | DetectorRail | ActivatorRail => sturdy(below, BlockFace::Top),
+            _ if name.ends_with("_pressure_plate") => supports_center(below, BlockFace::Top),
+            _ if name.ends_with("_door") => sturdy(below, BlockFace::Top),
+            _ if name.ends_with("_sign") || name.ends_with("_banner") => below.is_some_and(|b| !b.collision_boxes().is_empty()),
+            _ if name.ends_with("_carpet") => below.is_some(),
+            _ if is_plant(block) => is_soil(below),
+            _ => true,
+        }
+    }
+    /// The other half of a door, bed or tall plant, and whether it's still there
+    fn partner(&self, pos: V3<i32>, block: Block) -> Option<bool> {
+        let (pos, part) = match (block.get(), block.get(), facing_of(block)) {
+            (Some(props::Half::Upper), _, _) => (pos.step(BlockFace::Bottom), "lower"),
+            (Some(props::Half::Lower), _, _) => (pos.step(BlockFace::Top), "upper"),
+            (_, Some(props::Part::Foot), Some(facing)) => (pos.step(facing), "head"),
+            (_, Some(props::Part::Head), Some(facing)) => (pos.step(facing.opposite()), "foot"),
+            _ => return None,
+        };
+        let other = self.block_at(pos);
+        Some(other.is_some_and(|o| o.kind() == block.kind() && (o.property("half") == Some(part) || o.property("part") == Some(part))))
+    }
+}
+impl World {
+    /// Fixes up the blocks around `pos` after it changed: stairs turn corners, and
+    /// anything that lost its support or its other half breaks
+    pub(super) fn update_neighbours(&mut self, dimension: usize, mut inboxes: Inboxes, pos: V3<i32>) {
+        let mut changed = vec![pos];
+        while let Some(pos) = changed.pop() {
+            for side in [BlockFace::Top, BlockFace::Bottom, BlockFace::North, BlockFace::South, BlockFace::East, BlockFace::West] {
+                let pos = pos.step(side);
+                let blocks = &self.dimensions[dimension];
+                let block = match blocks.block_at(pos) {
+                    Some(block) => block,
+                    None => continue,
+                };
+                if blocks.partner(pos, block) == Some(false) {
+                    self.set_block(dimension, pos, None, inboxes.reborrow());
+                    changed.push(pos);
+                } else if !blocks.supported(pos, block) {
+                    self.set_block(dimension, pos, None, inboxes.reborrow());
+                    changed.push(pos);
+                    if let Some(item) = block.info().drop.and_then(Item::new) {
+                        let position = (pos.x as f64 + 0.5, pos.y as f64 + 0.25, pos.z as f64 + 0.5);
+                        self.drop_item(dimension, position, ItemStack::new(item, 1));
+                    }
+                } else if is_stairs(block) && side.is_horizontal() {
+                    let shaped = block.with(blocks.stair_shape(pos, block));
+                    if shaped != block {
+                        self.set_block(dimension, pos, Some(shaped), inboxes.reborrow());
+                    }
+                }
+            }
+        }
+    }
+}
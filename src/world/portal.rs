@@ -0,0 +1,243 @@
+//! Nether and end portals.
+//!
+//! Flint and steel inside an obsidian frame fills it with portal, and standing in that takes
+//! you between the overworld and the nether, coming out of the closest portal on the other
+//! side, or a new one if there isn't one nearby. End portals go to the obsidian platform in
+//! the end, and back from there to spawn.
+use crate::prelude::*;
+use super::{World, Dimension, OVERWORLD, NETHER, END};
+
+/// The biggest the inside of a portal frame can be, in either direction
+const MAX_SIZE: i32 = 21;
+/// How long survival players have to stand in a nether portal before it takes them
+const PORTAL_TICKS: u32 = 80;
+/// How long after coming out of a portal until it'll take someone back
+const PORTAL_COOLDOWN: u32 = 300;
+/// How far from where someone comes out we look for a portal to put them in
+const SEARCH_RADIUS: i32 = 16;
+
+fn is_empty(block: Option<Block>) -> bool {
+    block.is_none_or(|b| b.kind() == BlockKind::Fire)
+}
+fn is_portal(block: Option<Block>) -> bool {
+    block.is_some_and(|b| b.kind() == BlockKind::NetherPortal)
+}
+/// The directions along a portal's frame, from its left to its right
+fn sides(axis: props::Axis) -> (BlockFace, BlockFace) {
+    match axis {
+        props::Axis::X => (BlockFace::West, BlockFace::East),
+        _ => (BlockFace::North, BlockFace::South),
+    }
+}
+fn offset(pos: V3<i32>, face: BlockFace, n: i32) -> V3<i32> {
+    (0..n).fold(pos, |pos, _| pos.step(face))
+}
+
+impl Dimension {
+    /// The inside of the obsidian frame around `pos`, if it's the right shape for a portal
+    fn portal_frame(&self, pos: V3<i32>, axis: props::Axis) -> Option<Vec<V3<i32>>> {
+        let (left, right) = sides(axis);
+        let obsidian = |pos| self.block_at(pos) == Some(Block::OBSIDIAN);
+        let empty = |pos| self.in_world(pos) && is_empty(self.block_at(pos));
+        if !empty(pos) {
+            return None;
+        }
+        // down to the bottom of the frame, and along to its left
+        let mut corner = pos;
+        while empty(corner.step(BlockFace::Bottom)) && pos.y - corner.y < MAX_SIZE {
+            corner = corner.step(BlockFace::Bottom);
+        }
+        for _ in 0..MAX_SIZE {
+            if !empty(corner.step(left)) {
+                break;
+            }
+            corner = corner.step(left);
+        }
+        if !obsidian(corner.step(left)) {
+            return None;
+        }
+        let width = (0..=MAX_SIZE).find(|&i| {
+            let pos = offset(corner, right, i);
+            !empty(pos) || !obsidian(pos.step(BlockFace::Bottom))
+        })?;
+        if width < 2 || !obsidian(offset(corner, right, width)) {
+            return None;
+        }
+        // then up, until a row of obsidian closes it off
+        let mut height = 0;
+        loop {
+            let row = offset(corner, BlockFace::Top, height);
+            if (0..width).all(|i| obsidian(offset(row, right, i))) {
+                break;
+            }
+            let walled = obsidian(row.step(left)) && obsidian(offset(row, right, width));
+            if height == MAX_SIZE || !walled || !(0..width).all(|i| empty(offset(row, right, i))) {
+                return None;
+            }
+            height += 1;
+        }
+        if height < 3 {
+            return None;
+        }
+        Some((0..height).flat_map(|y| (0..width).map(move |x| offset(offset(corner, BlockFace::Top, y), right, x))).collect())
+    }
+    /// Whether a portal block is still held in place by its frame
+    pub(super) fn portal_intact(&self, pos: V3<i32>, portal: Block) -> bool {
+        let (left, right) = sides(portal.get().unwrap_or(props::Axis::X));
+        [left, right, BlockFace::Top, BlockFace::Bottom].into_iter().all(|side| {
+            let next = pos.step(side);
+            !self.in_world(next) || matches!(self.block_at(next), Some(b) if b == Block::OBSIDIAN || b == portal)
+        })
+    }
+    /// The bottom of the closest portal to `near`, in the chunks that are loaded
+    fn find_portal(&self, near: V3<i32>) -> Option<V3<i32>> {
+        let mut closest = None;
+        for x in near.x - SEARCH_RADIUS..=near.x + SEARCH_RADIUS {
+            for z in near.z - SEARCH_RADIUS..=near.z + SEARCH_RADIUS {
+                if !self.in_world(V3(x, self.min_y(), z)) {
+                    continue;
+                }
+                for y in self.min_y() + 1..self.top() {
+                    let pos = V3(x, y, z);
+                    if !is_portal(self.block_at(pos)) || is_portal(self.block_at(pos.step(BlockFace::Bottom))) {
+                        continue;
+                    }
+                    let distance = (x - near.x).pow(2) + (y - near.y).pow(2) + (z - near.z).pow(2);
+                    if closest.is_none_or(|(d, _)| distance < d) {
+                        closest = Some((distance, pos));
+                    }
+                }
+            }
+        }
+        closest.map(|(_, pos)| pos)
+    }
+}
+
+impl World {
+    /// Flint and steel lights a portal if `pos` is inside a frame, and otherwise just starts a fire
+    pub(super) fn light_fire(&mut self, dimension: usize, mut inboxes: Inboxes, pos: V3<i32>) {
+        let blocks = &self.dimensions[dimension];
+        if !blocks.in_world(pos) || blocks.block_at(pos).is_some() {
+            return;
+        }
+        let portal = [props::Axis::X, props::Axis::Z].into_iter()
+            .find_map(|axis| Some((axis, blocks.portal_frame(pos, axis)?)))
+            .filter(|_| dimension != END);
+        match portal {
+            Some((axis, inside)) => {
+                for pos in inside {
+                    self.set_block(dimension, pos, Some(Block::NETHER_PORTAL.with(axis)), inboxes.reborrow());
+                }
+            }
+            None if blocks.block_at(pos.step(BlockFace::Bottom)).is_some() => self.set_block(dimension, pos, Some(Block::FIRE), inboxes),
+            None => {}
+        }
+    }
+    /// Keeps track of whether a player is standing in a portal, after they've moved
+    pub(super) fn enter_portal(&mut self, pid: usize) {
+        let player = &self.players[pid];
+        let (x, y, z) = player.position;
+        let delay = match self.dimension(pid).block_at(V3(x.floor() as i32, y.floor() as i32, z.floor() as i32)).map(|b| b.kind()) {
+            Some(BlockKind::NetherPortal) if player.gamemode == GameMode::Survival => PORTAL_TICKS,
+            Some(BlockKind::NetherPortal | BlockKind::EndPortal) => 0,
+            _ => {
+                self.players[pid].portal = None;
+                return;
+            }
+        };
+        let tick = self.tick;
+        self.players[pid].portal.get_or_insert(tick + delay);
+    }
+    pub(super) fn tick_portals(&mut self, mut inboxes: Inboxes) {
+        for pid in 0..self.players.len() {
+            if self.players[pid].portal.is_none_or(|due| due > self.tick) {
+                continue;
+            }
+            let (x, y, z) = self.players[pid].position;
+            match self.dimension(pid).block_at(V3(x.floor() as i32, y.floor() as i32, z.floor() as i32)).map(|b| b.kind()) {
+                Some(BlockKind::NetherPortal) => self.nether_travel(pid, inboxes.reborrow()),
+                Some(BlockKind::EndPortal) => self.end_travel(pid, inboxes.reborrow()),
+                _ => self.players[pid].portal = None,
+            }
+        }
+    }
+    fn nether_travel(&mut self, pid: usize, mut inboxes: Inboxes) {
+        let from = self.players[pid].dimension;
+        let to = match from {
+            OVERWORLD => NETHER,
+            NETHER => OVERWORLD,
+            _ => {
+                // nowhere to go from here, until they step out
+                self.players[pid].portal = Some(u32::MAX);
+                return;
+            }
+        };
+        let scale = self.dimensions[from].dimension_type.coordinate_scale / self.dimensions[to].dimension_type.coordinate_scale;
+        let (x, y, z) = self.players[pid].position;
+        let (x, z) = (x * scale, z * scale);
+        let target = &self.dimensions[to];
+        let y = (y.floor() as i32).clamp(target.min_y() + 1, target.min_y() + target.dimension_type.logical_height - 1);
+        let near = V3(x.floor() as i32, y, z.floor() as i32);
+        // we need to know where the portal is before they get there, so this can't wait on the chunk loaders
+        for x in (near.x - SEARCH_RADIUS).div_euclid(16)..=(near.x + SEARCH_RADIUS).div_euclid(16) {
+            for z in (near.z - SEARCH_RADIUS).div_euclid(16)..=(near.z + SEARCH_RADIUS).div_euclid(16) {
+                self.load_now(to, (x, z));
+            }
+        }
+        let portal = match self.dimensions[to].find_portal(near) {
+            Some(portal) => portal,
+            None => self.build_portal(to, inboxes.reborrow(), near),
+        };
+        self.change_dimension(pid, inboxes.get(pid).unwrap(), to, (portal.x as f64 + 0.5, portal.y as f64, portal.z as f64 + 0.5));
+        self.players[pid].portal = Some(self.tick + PORTAL_COOLDOWN);
+    }
+    /// Makes a portal for someone coming out near `near`, with somewhere to stand either side of it.
+    /// Returns the bottom of its inside
+    fn build_portal(&mut self, dimension: usize, mut inboxes: Inboxes, near: V3<i32>) -> V3<i32> {
+        let blocks = &self.dimensions[dimension];
+        // on the ground, but low enough that it doesn't poke out of the top (or through the nether's roof)
+        let highest = blocks.min_y() + blocks.dimension_type.logical_height - 4;
+        let base = V3(near.x, blocks.surface(near.x, near.z).clamp(blocks.min_y() + 1, highest), near.z);
+        let mut changes = vec![];
+        for dx in -1..=2 {
+            for dy in -1..=3 {
+                let frame = dx == -1 || dx == 2 || dy == -1 || dy == 3;
+                let block = if frame { Block::OBSIDIAN } else { Block::NETHER_PORTAL.with(props::Axis::X) };
+                changes.push((V3(base.x + dx, base.y + dy, base.z), Some(block)));
+                if (0..=1).contains(&dx) && dy < 3 {
+                    let ground = if dy == -1 { Some(Block::OBSIDIAN) } else { None };
+                    changes.push((V3(base.x + dx, base.y + dy, base.z - 1), ground));
+                    changes.push((V3(base.x + dx, base.y + dy, base.z + 1), ground));
+                }
+            }
+        }
+        for (pos, block) in changes {
+            if self.dimensions[dimension].in_world(pos) {
+                self.set_block(dimension, pos, block, inboxes.reborrow());
+            }
+        }
+        base
+    }
+    fn end_travel(&mut self, pid: usize, mut inboxes: Inboxes) {
+        if self.players[pid].dimension == END {
+            let spawn = self.spawn_point();
+            self.change_dimension(pid, inboxes.get(pid).unwrap(), OVERWORLD, spawn);
+        } else {
+            // the obsidian platform vanilla puts you on, with room to stand
+            self.load_now(END, (6, -1));
+            self.load_now(END, (6, 0));
+            for x in 98..=102 {
+                for z in -2..=2 {
+                    for y in 48..=51 {
+                        let block = if y == 48 { Some(Block::OBSIDIAN) } else { None };
+                        if self.dimensions[END].block_at(V3(x, y, z)) != block {
+                            self.set_block(END, V3(x, y, z), block, inboxes.reborrow());
+                        }
+                    }
+                }
+            }
+            self.change_dimension(pid, inboxes.get(pid).unwrap(), END, (100.5, 49.0, 0.5));
+        }
+        self.players[pid].portal = None;
+    }
+}
@@ -0,0 +1,136 @@
+//! Holding on to people who turn up when the server's full, until there's room for them.
+//!
+//! They wait in limbo: logged in to the end, but with no chunks around them, so there's
+//! nothing to fall through and nothing for us to keep in memory. They're told where they
+//! are in line whenever it changes, and let in one a tick from the front. Ops skip ahead
+//! of everyone who isn't one, and can have a few slots of their own.
+use crate::prelude::*;
+use super::{World, END};
+
+/// Where people wait
+pub(super) const LIMBO: (f64, f64, f64) = (0.5, 128.0, 0.5);
+/// Chunks keep track of who can see them in a u32, so players need ids below this
+pub(super) const MAX_PID: usize = 32;
+
+/// How someone's login went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    Playing,
+    Queued,
+}
+
+pub(super) struct Queued {
+    pid: usize,
+    name: Name,
+    eid: i32,
+    op: bool,
+    /// What their client asked for, for when they get in
+    view_distance: u8,
+    /// Where in line we last told them they were
+    told: usize,
+    last_ping_ack: u32,
+}
+
+impl World {
+    /// Lets someone in if there's room (and nobody's waiting ahead of them), or puts them in the queue.
+    /// Fails if the queue's full too
+    pub(crate) fn login(&mut self, pid: usize, name: Name, mut inbox: Inbox) -> Result<Admission, String> {
+        if let Some(reason) = &self.budget.shutdown {
+            return Err(reason.clone());
+        }
+        let op = self.level.is_op(name);
+        let ahead = self.queue.iter().filter(|queued| queued.op || !op).count();
+        let reason = match self.admit(op) {
+            // higher ids have to wait for the network to find them a lower one
+            Ok(()) if ahead == 0 && pid < MAX_PID => {
+                let eid = self.new_eid();
+                self.join(pid, name, eid, 1, inbox, false);
+                return Ok(Admission::Playing);
+            }
+            Ok(()) if ahead > 0 => "There are people waiting to join".to_owned(),
+            Ok(()) => "The server is busy".to_owned(),
+            Err(reason) => reason,
+        };
+        if self.queue.len() >= self.budget.limits.max_queue {
+            return Err(format!("{reason}, and the queue's full"));
+        }
+        log::info!("{} is waiting to join: {reason}", name.as_str());
+        let eid = self.new_eid();
+        inbox.submit(Response::Login(name));
+        inbox.submit(Response::Play(eid, GameMode::Survival, END, None));
+        // nobody moves in limbo, so there's nothing to confirm
+        inbox.submit(Response::Position(LIMBO, 0));
+        inbox.submit(Response::Chat(format!("{reason}, so you're waiting in line to join")));
+        self.queue.insert(ahead, Queued { pid, name, eid, op, view_distance: 1, told: 0, last_ping_ack: self.tick });
+        Ok(Admission::Queued)
+    }
+    /// Forgets someone who's stopped waiting
+    pub(crate) fn leave_queue(&mut self, pid: usize) {
+        self.queue.retain(|queued| queued.pid != pid);
+    }
+    pub(crate) fn acknowledge_queued_ping(&mut self, pid: usize) {
+        if let Some(queued) = self.queue.iter_mut().find(|queued| queued.pid == pid) {
+            queued.last_ping_ack = self.tick;
+        }
+    }
+    pub(crate) fn request_queued_view_distance(&mut self, pid: usize, view_distance: u8) {
+        if let Some(queued) = self.queue.iter_mut().find(|queued| queued.pid == pid) {
+            queued.view_distance = view_distance.max(1);
+        }
+    }
+    /// Lets the next person in if there's room, and keeps everyone else up to date
+    pub(super) fn tick_queue(&mut self, mut inboxes: Inboxes) {
+        if self.queue.is_empty() {
+            return;
+        }
+        // one a tick, so the budget's caught up with them before the next
+        if self.admit(self.queue[0].op).is_ok() {
+            if let Some(pid) = inboxes.renumber(self.queue[0].pid, MAX_PID) {
+                let Queued { name, eid, view_distance, .. } = self.queue.pop_front().unwrap();
+                log::info!("letting {} in from the queue", name.as_str());
+                inboxes.reborrow().queued(|i, mut inbox| {
+                    if i == pid {
+                        inbox.start_playing();
+                        self.join(pid, name, eid, view_distance, inbox, true);
+                    }
+                    true
+                });
+                self.budget.usage.players += self.player_bytes();
+            }
+        }
+        let ping = self.tick.is_multiple_of(5 * 20);
+        let mut timed_out = vec![];
+        let (queue, tick) = (&mut self.queue, self.tick);
+        inboxes.queued(|pid, mut inbox| {
+            let (place, queued) = match queue.iter_mut().enumerate().find(|(_, queued)| queued.pid == pid) {
+                Some(found) => found,
+                None => return true,
+            };
+            if ping && tick - queued.last_ping_ack > 5 * 20 {
+                log::warn!("{} timed out in the queue", queued.name.as_str());
+                timed_out.push(pid);
+                return false;
+            }
+            if ping {
+                inbox.submit(Response::Ping(tick as i32));
+            }
+            if queued.told != place + 1 {
+                queued.told = place + 1;
+                inbox.submit(Response::Chat(format!("You're number {} in line", place + 1)));
+            }
+            true
+        });
+        for pid in timed_out {
+            self.leave_queue(pid);
+        }
+    }
+    /// Sends everyone in the queue away
+    pub(super) fn clear_queue(&mut self, inboxes: Inboxes, reason: &str) {
+        inboxes.queued(|_, mut inbox| {
+            inbox.submit(Response::Disconnect(reason.to_owned()));
+            inbox.close();
+            true
+        });
+        self.queue.clear();
+    }
+}
@@ -0,0 +1,160 @@
+//! What we do when the world can't be saved, like the notes at the top of mod.rs plan out.
+//!
+//! Once enough saves in a row have failed we stop trying, and send everyone to a lobby (the
+//! same void the queue waits in) so they can't make changes we'd lose. Whatever's unsaved
+//! stays in memory for as long as the budget lets it. Then it's up to the admin, from the
+//! console: `retry` once they've freed up some space, `save-path` to move the world somewhere
+//! that works, or `stop`.
+use crate::prelude::*;
+use super::{queue::LIMBO, World, END};
+use super::storage::copy_missing;
+use std::path::PathBuf;
+use std::sync::{atomic::{AtomicBool, AtomicU32, Ordering}, Mutex};
+
+/// How many saves can fail in a row before we give up on them
+const MAX_FAILURES: u32 = 5;
+
+#[derive(Default)]
+pub(super) struct StorageHealth {
+    /// Saves that have failed since the last one that worked
+    failures: AtomicU32,
+    last_error: Mutex<String>,
+    /// Turned off with `save-off`
+    off: AtomicBool,
+}
+impl StorageHealth {
+    pub fn failing(&self) -> bool {
+        self.failures.load(Ordering::Relaxed) >= MAX_FAILURES
+    }
+    /// Whether chunks should be written as they're unloaded or autosaved
+    pub fn saving(&self) -> bool {
+        !self.failing() && !self.off.load(Ordering::Relaxed)
+    }
+    pub fn set_saving(&self, on: bool) {
+        self.off.store(!on, Ordering::Relaxed);
+    }
+    pub fn last_error(&self) -> String {
+        self.last_error.lock().unwrap().clone()
+    }
+    /// Writes something, unless we've given up on writing
+    pub fn attempt(&self, write: impl FnOnce() -> io::Result<()>) -> io::Result<()> {
+        if self.failing() {
+            return Err(io::Error::other("saving is paused until storage is fixed"));
+        }
+        let result = write();
+        match &result {
+            Ok(()) => self.failures.store(0, Ordering::Relaxed),
+            Err(e) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                *self.last_error.lock().unwrap() = e.to_string();
+            }
+        }
+        result
+    }
+    fn retry(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+    }
+    fn give_up(&self) {
+        self.failures.fetch_max(MAX_FAILURES, Ordering::Relaxed);
+    }
+}
+
+impl World {
+    pub(crate) fn in_lobby(&self, pid: usize) -> bool {
+        self.players[pid].lobby
+    }
+    pub(super) fn tick_recovery(&mut self, inboxes: Inboxes) {
+        if !self.recovering && self.level.storage.failing() {
+            self.enter_recovery(inboxes);
+        }
+    }
+    fn enter_recovery(&mut self, inboxes: Inboxes) {
+        let error = self.level.storage.last_error();
+        log::error!("saving keeps failing ({error}), so everyone's going to the lobby. \
+            `retry` once it's fixed, `save-path <directory>` to save somewhere else, or `stop`");
+        self.recovering = true;
+        let msg = format!("The server can't save the world right now ({error}), so you'll wait here until it's fixed");
+        inboxes.retain(|pid, mut inbox| {
+            let released = self.release_chunks(pid, |_| false);
+            inbox.cancel(|response| matches!(response, Response::LoadChunk(..) | Response::UpdateLight(..)));
+            for (x, z) in released {
+                inbox.submit(Response::UnloadChunk(x, z));
+            }
+            let player = &mut self.players[pid];
+            player.lobby = true;
+            player.digging = None;
+            player.portal = None;
+            inbox.submit(Response::Respawn(player.gamemode, END, None));
+            inbox.submit(Response::Position(LIMBO, 0));
+            inbox.submit(Response::Chat(msg.clone()));
+            true
+        });
+    }
+    fn leave_recovery(&mut self, inboxes: Inboxes) {
+        log::info!("saving works again, so everyone's going back");
+        self.recovering = false;
+        inboxes.retain(|pid, mut inbox| {
+            if self.players[pid].lobby {
+                let player = &mut self.players[pid];
+                player.lobby = false;
+                let (dimension, position) = (player.dimension, player.position);
+                self.change_dimension(pid, inbox.reborrow(), dimension, position);
+                inbox.submit(Response::Chat("Saving works again, so you're back where you were".to_owned()));
+            }
+            true
+        });
+    }
+    /// Tries to save everything that's unsaved, and lets everyone out of the lobby if that works
+    pub(super) fn retry_saving(&mut self, inboxes: Inboxes) -> String {
+        self.level.storage.retry();
+        let mut saved = self.save_chunks() & self.save_level_data();
+        for pid in self.online().collect::<Vec<_>>() {
+            saved &= self.save_player(pid);
+        }
+        if !saved {
+            self.level.storage.give_up();
+            return format!("still can't save: {}", self.level.storage.last_error());
+        }
+        if self.recovering {
+            self.leave_recovery(inboxes);
+        }
+        "saved everything".to_owned()
+    }
+    /// Saves the world somewhere else from now on, bringing over what's been saved so far
+    pub(super) fn set_save_path(&mut self, inboxes: Inboxes, path: &str) -> String {
+        let new = PathBuf::from(path);
+        let old = self.level.path();
+        if old.as_ref().is_some_and(|old| new.starts_with(old)) {
+            return format!("{path} is inside the world it'd be copying");
+        }
+        if let Err(e) = std::fs::create_dir_all(new.join("playerdata")) {
+            return format!("couldn't use {path}: {e}");
+        }
+        let missed = old.map_or(0, |old| copy_missing(&old, &new));
+        log::info!("saving the world to {path} from now on");
+        *self.level.path.write().unwrap() = Some(new);
+        let retried = self.retry_saving(inboxes);
+        match missed {
+            0 => format!("moved the world to {path}, and {retried}"),
+            _ => format!("moved the world to {path}, but {missed} files couldn't be copied (see the log). {retried}"),
+        }
+    }
+    pub(super) fn status(&self) -> String {
+        let mut status = format!(
+            "{} playing, {} waiting, {} chunks unsaved, using {} MiB",
+            self.online().count(),
+            self.queue.len(),
+            self.unsaved_chunks(),
+            self.budget.usage.total() / (1024 * 1024),
+        );
+        if self.backup_running() {
+            status += ". a backup's being taken";
+        } else if !self.recovering && !self.level.storage.saving() {
+            status += ". saving's off until save-on";
+        }
+        if self.recovering {
+            status += &format!(". saving's stopped after failing: {}", self.level.storage.last_error());
+        }
+        status
+    }
+}
@@ -0,0 +1,410 @@
+//! Chunks on disk, in vanilla's region (`.mca`) format, so worlds can be opened by
+//! either server.
+//!
+//! A region file holds 32x32 chunks. It starts with a table of where each chunk is
+//! (in 4KiB sectors) and a table of when each was saved, followed by the chunks themselves
+//! as zlib compressed NBT.
+use crate::prelude::*;
+use super::{Level, ChunkContent, Heightmap, Heightmaps};
+use super::items::SavedItem;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const SECTOR: usize = 4096;
+/// The version of the chunk format we write, from 1.19
+const DATA_VERSION: i32 = 3105;
+const ZLIB: u8 = 2;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SavedChunk {
+    #[serde(rename = "DataVersion")]
+    data_version: i32,
+    #[serde(rename = "xPos")]
+    x: i32,
+    #[serde(rename = "zPos")]
+    z: i32,
+    #[serde(rename = "yPos")]
+    y: i32,
+    #[serde(rename = "Status")]
+    status: String,
+    // we don't save light, so get vanilla to work it out again
+    #[serde(rename = "isLightOn")]
+    light_on: bool,
+    sections: Vec<SavedSection>,
+    #[serde(rename = "Heightmaps", default)]
+    heightmaps: HashMap<String, fastnbt::LongArray>,
+    /// Vanilla keeps these in their own region files, but ours go with the chunk
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    entities: Vec<fastnbt::Value>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SavedSection {
+    #[serde(rename = "Y")]
+    y: i8,
+    block_states: Option<Palette<SavedBlock>>,
+    biomes: Option<Palette<String>>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Palette<T> {
+    palette: Vec<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<fastnbt::LongArray>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SavedBlock {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Properties", skip_serializing_if = "HashMap::is_empty", default)]
+    properties: HashMap<String, String>,
+}
+impl SavedBlock {
+    fn new(block: Option<Block>) -> Self {
+        match block {
+            Some(block) => Self {
+                name: block.info().name.to_owned(),
+                properties: block.properties().map(|(k, v)| (k.to_owned(), v.to_owned())).collect(),
+            },
+            None => Self { name: "minecraft:air".to_owned(), properties: HashMap::new() },
+        }
+    }
+    fn block(&self) -> Option<Block> {
+        let mut block = match BlockInfo::from_name(&self.name) {
+            Some(info) => Block::new(info.default_state)?,
+            None => {
+                log::warn!("unknown block {} in a saved chunk", self.name);
+                return None;
+            }
+        };
+        for (name, value) in &self.properties {
+            block = block.with_property(name, value).unwrap_or(block);
+        }
+        Some(block).filter(|b| !b.is_air())
+    }
+}
+
+/// Sections use at least this many bits for each block in their data
+const MIN_BLOCK_BITS: u32 = 4;
+const MIN_BIOME_BITS: u32 = 1;
+
+/// Splits `values` into the distinct values and where each one is in that list
+fn palettize<T: PartialEq>(values: impl Iterator<Item = T>) -> (Vec<T>, Vec<u16>) {
+    let mut palette = vec![];
+    let indices = values.map(|value| {
+        palette.iter().position(|v| *v == value).unwrap_or_else(|| {
+            palette.push(value);
+            palette.len() - 1
+        }) as u16
+    }).collect();
+    (palette, indices)
+}
+/// Packs palette indices into longs, without letting any index straddle two of them
+fn pack(indices: &[u16], palette_len: usize, min_bits: u32) -> Option<fastnbt::LongArray> {
+    if palette_len <= 1 {
+        return None;
+    }
+    let bits = (usize::BITS - (palette_len - 1).leading_zeros()).max(min_bits) as usize;
+    let per_long = 64 / bits;
+    let mut data = vec![0i64; indices.len().div_ceil(per_long)];
+    for (i, index) in indices.iter().enumerate() {
+        data[i / per_long] |= (*index as i64) << (i % per_long * bits);
+    }
+    Some(fastnbt::LongArray::new(data))
+}
+fn unpack(data: &[i64], palette_len: usize, len: usize, min_bits: u32) -> Vec<usize> {
+    if palette_len <= 1 {
+        return vec![0; len];
+    }
+    let bits = (usize::BITS - (palette_len - 1).leading_zeros()).max(min_bits) as usize;
+    let per_long = 64 / bits;
+    (0..len).map(|i| {
+        let long = data.get(i / per_long).copied().unwrap_or(0) as u64;
+        (long >> (i % per_long * bits) & ((1 << bits) - 1)) as usize
+    }).map(|i| if i < palette_len { i } else { 0 }).collect()
+}
+
+fn invalid(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+/// Where vanilla keeps each dimension's region files, in the world at `base`
+pub(super) fn region_dir(base: &Path, dimension: &str) -> PathBuf {
+    match dimension {
+        "minecraft:overworld" => base.join("region"),
+        "minecraft:the_nether" => base.join("DIM-1").join("region"),
+        "minecraft:the_end" => base.join("DIM1").join("region"),
+        _ => {
+            let (namespace, name) = dimension.split_once(':').unwrap_or(("minecraft", dimension));
+            base.join("dimensions").join(namespace).join(name).join("region")
+        }
+    }
+}
+fn region_file(dir: &Path, chunk: (i32, i32)) -> PathBuf {
+    let (rx, rz) = (chunk.0.div_euclid(32), chunk.1.div_euclid(32));
+    dir.join(format!("r.{rx}.{rz}.mca"))
+}
+/// Reads a chunk's NBT out of the region files in `dir`, if it was ever saved there
+pub(super) fn read_region(dir: &Path, chunk: (i32, i32)) -> io::Result<Option<Vec<u8>>> {
+    let path = region_file(dir, chunk);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut file = std::fs::File::open(path)?;
+    let entry = (chunk.0.rem_euclid(32) + chunk.1.rem_euclid(32) * 32) as u64 * 4;
+    let mut location = [0; 4];
+    file.seek(SeekFrom::Start(entry))?;
+    file.read_exact(&mut location)?;
+    let sector = u32::from_be_bytes([0, location[0], location[1], location[2]]) as u64;
+    if sector == 0 {
+        return Ok(None);
+    }
+    let mut header = [0; 5];
+    file.seek(SeekFrom::Start(sector * SECTOR as u64))?;
+    file.read_exact(&mut header)?;
+    let len = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+    if header[4] != ZLIB || len == 0 || len > location[3] as usize * SECTOR {
+        return Err(invalid(format!("chunk {chunk:?} is stored in a way we can't read")));
+    }
+    let mut compressed = vec![0; len - 1];
+    file.read_exact(&mut compressed)?;
+    let mut nbt = vec![];
+    flate2::read::ZlibDecoder::new(&compressed[..]).read_to_end(&mut nbt)?;
+    Ok(Some(nbt))
+}
+/// Puts a chunk's NBT into its region file in `dir`, in its old spot if it fits or the first gap that's big enough
+fn write_region(dir: &Path, chunk: (i32, i32), nbt: &[u8]) -> io::Result<()> {
+    let path = region_file(dir, chunk);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let mut file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+    let mut header = vec![0; 2 * SECTOR];
+    if file.metadata()?.len() >= header.len() as u64 {
+        file.read_exact(&mut header)?;
+    }
+    let mut compressed = flate2::write::ZlibEncoder::new(vec![], Default::default());
+    compressed.write_all(nbt)?;
+    let compressed = compressed.finish()?;
+    let sectors = (compressed.len() + 5).div_ceil(SECTOR);
+    if sectors > 255 {
+        return Err(invalid(format!("chunk {chunk:?} is too big to save")));
+    }
+
+    let entry = (chunk.0.rem_euclid(32) + chunk.1.rem_euclid(32) * 32) as usize;
+    let mut used = vec![true, true];
+    for (_, location) in header[..SECTOR].chunks(4).enumerate().filter(|(i, _)| *i != entry) {
+        let start = u32::from_be_bytes([0, location[0], location[1], location[2]]) as usize;
+        let end = start + location[3] as usize;
+        if start >= 2 {
+            used.resize(used.len().max(end), false);
+            used[start..end].iter_mut().for_each(|s| *s = true);
+        }
+    }
+    let start = (2..=used.len())
+        .find(|&s| (s..s + sectors).all(|s| !used.get(s).copied().unwrap_or(false)))
+        .unwrap();
+
+    let mut data = Vec::with_capacity(sectors * SECTOR);
+    data.extend((compressed.len() as u32 + 1).to_be_bytes());
+    data.push(ZLIB);
+    data.extend(compressed);
+    data.resize(sectors * SECTOR, 0);
+    file.seek(SeekFrom::Start((start * SECTOR) as u64))?;
+    file.write_all(&data)?;
+
+    let location = (start as u32) << 8 | sectors as u32;
+    let timestamp = time::SystemTime::now().duration_since(time::UNIX_EPOCH).map_or(0, |t| t.as_secs() as u32);
+    header[entry * 4..][..4].copy_from_slice(&location.to_be_bytes());
+    header[SECTOR + entry * 4..][..4].copy_from_slice(&timestamp.to_be_bytes());
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&header)?;
+    file.sync_data()
+}
+/// The chunks that have been saved in the region files in `dir`
+pub(super) fn saved_chunks(dir: &Path) -> io::Result<Vec<(i32, i32)>> {
+    let mut chunks = vec![];
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(chunks),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let region = name.to_str()
+            .and_then(|name| name.strip_prefix("r.")?.strip_suffix(".mca")?.split_once('.'))
+            .and_then(|(x, z)| Some((x.parse::<i32>().ok()?, z.parse::<i32>().ok()?)));
+        let (rx, rz) = match region {
+            Some(region) => region,
+            None => continue,
+        };
+        let mut locations = vec![0; SECTOR];
+        std::fs::File::open(entry.path())?.read_exact(&mut locations)?;
+        for (i, location) in locations.chunks(4).enumerate() {
+            if location != [0; 4] {
+                chunks.push((rx * 32 + i as i32 % 32, rz * 32 + i as i32 / 32));
+            }
+        }
+    }
+    Ok(chunks)
+}
+/// Copies a saved chunk from one world's region files to another's
+pub(super) fn copy_chunk(from: &Path, to: &Path, chunk: (i32, i32)) -> io::Result<()> {
+    match read_region(from, chunk)? {
+        Some(nbt) => write_region(to, chunk, &nbt),
+        None => Ok(()),
+    }
+}
+/// Copies everything in `from` that isn't in `to` yet. Returns how many files it couldn't
+pub(super) fn copy_missing(from: &Path, to: &Path) -> usize {
+    let entries = match std::fs::read_dir(from) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("couldn't read {}: {e}", from.display());
+            return 1;
+        }
+    };
+    let mut missed = 0;
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::error!("couldn't read {}: {e}", from.display());
+                missed += 1;
+                continue;
+            }
+        };
+        let (from, to) = (entry.path(), to.join(entry.file_name()));
+        let copied = if from.is_dir() {
+            match std::fs::create_dir_all(&to) {
+                Ok(()) => {
+                    missed += copy_missing(&from, &to);
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            }
+        } else if to.exists() {
+            Ok(())
+        } else {
+            std::fs::copy(&from, &to).map(|_| ())
+        };
+        if let Err(e) = copied {
+            log::error!("couldn't copy {} to {}: {e}", from.display(), to.display());
+            missed += 1;
+        }
+    }
+    missed
+}
+
+impl Level {
+    /// Like `load_chunk`, but chunks we can't read are treated like they've never been saved
+    pub(super) fn load_chunk_or_log(&self, dimension: &str, chunk: (i32, i32), min_y: i32, sections: usize) -> Option<(ChunkContent, Heightmaps, Vec<SavedItem>)> {
+        self.load_chunk(dimension, chunk, min_y, sections).unwrap_or_else(|e| {
+            log::error!("couldn't load chunk {chunk:?}: {e}");
+            None
+        })
+    }
+    /// A chunk as it was last saved, and the items in it, in a dimension `sections` tall starting at `min_y`
+    pub(super) fn load_chunk(&self, dimension: &str, chunk: (i32, i32), min_y: i32, sections: usize) -> io::Result<Option<(ChunkContent, Heightmaps, Vec<SavedItem>)>> {
+        // edits are saved in the world, and the template (if there is one) has everything else
+        let mut nbt = match self.path() {
+            Some(path) => read_region(&region_dir(&path, dimension), chunk)?,
+            None => None,
+        };
+        if let (None, Some(template)) = (&nbt, &self.template) {
+            nbt = read_region(&region_dir(template, dimension), chunk)?;
+        }
+        let nbt = match nbt {
+            Some(nbt) => nbt,
+            None => return Ok(None),
+        };
+        let saved: SavedChunk = fastnbt::from_bytes(&nbt).map_err(invalid)?;
+        if saved.status != "full" && saved.status != "minecraft:full" {
+            // vanilla hadn't finished generating it
+            return Ok(None);
+        }
+        let mut content = ChunkContent::empty(min_y, sections, self.codec.plains());
+        let bottom = min_y.div_euclid(16);
+        for section in saved.sections.iter().filter(|s| (bottom..bottom + sections as i32).contains(&(s.y as i32))) {
+            let y = section.y as i32 * 16;
+            if let Some(saved) = &section.biomes {
+                let palette: Vec<_> = saved.palette.iter().map(|name| self.codec.biome_id(name).unwrap_or_else(|| {
+                    log::warn!("unknown biome {name} in a saved chunk");
+                    self.codec.plains()
+                })).collect();
+                let indices = unpack(saved.data.as_deref().unwrap_or(&[]), palette.len(), 64, MIN_BIOME_BITS);
+                let ChunkContent::OneToOne { biomes, .. } = &mut content;
+                let base = (section.y as i32 - bottom) as usize * 64;
+                for (i, index) in indices.into_iter().enumerate() {
+                    biomes[base + i] = palette[index];
+                }
+            }
+            let states = match &section.block_states {
+                Some(states) => states,
+                None => continue,
+            };
+            let palette: Vec<_> = states.palette.iter().map(SavedBlock::block).collect();
+            let indices = unpack(states.data.as_deref().unwrap_or(&[]), palette.len(), 4096, MIN_BLOCK_BITS);
+            for (i, index) in indices.into_iter().enumerate() {
+                if palette[index].is_some() {
+                    content.set_block(i % 16, y + i as i32 / 256, i / 16 % 16, palette[index]);
+                }
+            }
+        }
+        let mut heightmaps = Heightmaps::empty(content.sections());
+        let loaded = Heightmap::ALL.into_iter().all(|kind| {
+            saved.heightmaps.get(kind.name()).and_then(|packed| heightmaps.unpack(kind, packed)).is_some()
+        });
+        if !loaded {
+            heightmaps = Heightmaps::compute(&content);
+        }
+        // one we can't make sense of shouldn't cost us the rest of the chunk
+        let items = saved.entities.iter()
+            .filter(|entity| matches!(entity, fastnbt::Value::Compound(c) if matches!(c.get("id"), Some(fastnbt::Value::String(id)) if id == SavedItem::ID)))
+            .filter_map(|entity| fastnbt::from_value(entity).map_err(|e| log::warn!("skipping an item in chunk {chunk:?}: {e}")).ok())
+            .collect();
+        Ok(Some((content, heightmaps, items)))
+    }
+    pub(super) fn save_chunk(&self, dimension: &str, chunk: (i32, i32), content: &ChunkContent, heightmaps: &Heightmaps, items: &[SavedItem]) -> io::Result<()> {
+        let path = match self.path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let nbt = self.encode_chunk(chunk, content, heightmaps, items)?;
+        self.storage.attempt(|| write_region(&region_dir(&path, dimension), chunk, &nbt))
+    }
+    /// Writes a chunk straight into the template world
+    pub(super) fn save_template_chunk(&self, dimension: &str, chunk: (i32, i32), content: &ChunkContent, heightmaps: &Heightmaps, items: &[SavedItem]) -> io::Result<()> {
+        let template = match &self.template {
+            Some(template) => template,
+            None => return Ok(()),
+        };
+        write_region(&region_dir(template, dimension), chunk, &self.encode_chunk(chunk, content, heightmaps, items)?)
+    }
+    fn encode_chunk(&self, chunk: (i32, i32), content: &ChunkContent, heightmaps: &Heightmaps, items: &[SavedItem]) -> io::Result<Vec<u8>> {
+        let mut sections = vec![];
+        let bottom = content.min_y().div_euclid(16);
+        for y in bottom..bottom + content.sections() as i32 {
+            let (palette, indices) = palettize((0..4096).map(|i| content.block(i % 16, y * 16 + i as i32 / 256, i / 16 % 16)));
+            let data = pack(&indices, palette.len(), MIN_BLOCK_BITS);
+            let block_states = Palette { palette: palette.into_iter().map(SavedBlock::new).collect(), data };
+
+            let (palette, indices) = palettize((0..64).map(|i| content.biome(i % 4 * 4, y * 16 + i as i32 / 16 * 4, i / 4 % 4 * 4)));
+            let data = pack(&indices, palette.len(), MIN_BIOME_BITS);
+            let biomes = Palette { palette: palette.into_iter().map(|id| self.codec.biome_name(id).to_owned()).collect(), data };
+            sections.push(SavedSection { y: y as i8, block_states: Some(block_states), biomes: Some(biomes) });
+        }
+        let saved = SavedChunk {
+            data_version: DATA_VERSION,
+            x: chunk.0,
+            z: chunk.1,
+            y: bottom,
+            status: "full".to_owned(),
+            light_on: false,
+            sections,
+            heightmaps: Heightmap::ALL.into_iter()
+                .map(|kind| (kind.name().to_owned(), fastnbt::LongArray::new(heightmaps.pack(kind))))
+                .collect(),
+            entities: items.iter().map(fastnbt::to_value).collect::<Result<_, _>>().map_err(invalid)?,
+        };
+        fastnbt::to_bytes(&saved).map_err(invalid)
+    }
+}
@@ -0,0 +1,242 @@
+//! Template worlds, like the notes at the top of mod.rs have been wanting: a base world we
+//! only ever read, streamed in a chunk at a time like any other, with everything that
+//! changes kept in an overlay on top.
+//!
+//! The overlay is just the world's own directory, so edited chunks are saved there and
+//! shadow the template's. Without one, edits stay in memory until we stop. Either way the
+//! admin can throw them away (to reset a map between games), snapshot them, restore a
+//! snapshot, or merge them into the template for good.
+use crate::prelude::*;
+use super::entity::Kind;
+use super::storage::{copy_chunk, copy_missing, region_dir, saved_chunks};
+use super::{movement, ChunkContent, ChunkLight, Dimension, Heightmaps, Level, World};
+use std::path::PathBuf;
+
+impl Level {
+    /// Reads chunks that haven't been saved in this world from the one at `path`
+    pub fn set_template(&mut self, path: impl Into<PathBuf>) -> io::Result<()> {
+        let path = path.into();
+        if !path.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("there's no world at {}", path.display())));
+        }
+        if self.data.is_none() {
+            self.data = Self::read_level_data(&path)?;
+        }
+        self.template = Some(path);
+        Ok(())
+    }
+    /// Edits to a template with nowhere to save them only live in memory
+    pub(super) fn in_memory_overlay(&self) -> bool {
+        self.template.is_some() && self.path.read().unwrap().is_none()
+    }
+}
+
+impl Dimension {
+    /// Swaps what's in a loaded chunk for something else, and lights it again
+    fn replace_chunk(&mut self, pos: (i32, i32), content: ChunkContent, heightmaps: Heightmaps) {
+        let light = Box::new(ChunkLight::compute(&content, self.has_skylight()));
+        let chunk = &mut self.chunks[self.index[&pos] as usize];
+        chunk.content = content;
+        chunk.heightmaps = heightmaps;
+        chunk.light = light;
+        chunk.dirty = false;
+        self.light_chunk(pos);
+    }
+}
+
+impl World {
+    pub(super) fn overlay_command(&mut self, inboxes: Inboxes, args: &str) -> String {
+        match args.split_whitespace().collect::<Vec<_>>()[..] {
+            ["discard"] => self.discard_overlay(inboxes),
+            ["snapshot", name] => self.snapshot_overlay(name),
+            ["restore", name] => self.restore_overlay(inboxes, name),
+            ["merge"] => self.merge_overlay(),
+            _ => "usage: overlay discard|snapshot <name>|restore <name>|merge".to_owned(),
+        }
+    }
+    /// Deletes the overlay's region files
+    fn clear_overlay(&self) -> Result<(), String> {
+        let path = match self.level.path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        for dimension in &self.dimensions {
+            let dir = region_dir(&path, &dimension.name);
+            match std::fs::remove_dir_all(&dir) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(format!("couldn't delete {}: {e}", dir.display())),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    /// Throws away every change, going back to the template
+    fn discard_overlay(&mut self, inboxes: Inboxes) -> String {
+        if self.level.template.is_none() {
+            return "there's no template world to go back to".to_owned();
+        }
+        if let Err(e) = self.clear_overlay() {
+            return e;
+        }
+        self.reload_chunks(inboxes);
+        log::info!("threw away the changes to the template");
+        "threw away every change to the template".to_owned()
+    }
+    /// The world's directory, and where the snapshot called `name` goes in it
+    fn snapshot_dir(&self, name: &str) -> Result<(PathBuf, PathBuf), String> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err("snapshot names can only have letters, numbers, - and _".to_owned());
+        }
+        match self.level.path() {
+            Some(path) => Ok((path.clone(), path.join("snapshots").join(name))),
+            None => Err("snapshots are kept in the world's directory, and there isn't one".to_owned()),
+        }
+    }
+    /// Copies what's been changed so far, to come back to later
+    fn snapshot_overlay(&mut self, name: &str) -> String {
+        let (path, snapshot) = match self.snapshot_dir(name) {
+            Ok(dirs) => dirs,
+            Err(e) => return e,
+        };
+        if snapshot.exists() {
+            return format!("there's already a snapshot called {name}");
+        }
+        if !self.save_chunks() {
+            return format!("couldn't save everything first: {}", self.level.storage.last_error());
+        }
+        let mut missed = 0;
+        for dimension in &self.dimensions {
+            let (from, to) = (region_dir(&path, &dimension.name), region_dir(&snapshot, &dimension.name));
+            if !from.exists() {
+                continue;
+            }
+            missed += match std::fs::create_dir_all(&to) {
+                Ok(()) => copy_missing(&from, &to),
+                Err(e) => {
+                    log::error!("couldn't create {}: {e}", to.display());
+                    1
+                }
+            };
+        }
+        match missed {
+            0 => format!("saved a snapshot called {name}"),
+            _ => format!("saved a snapshot called {name}, but {missed} files couldn't be copied (see the log)"),
+        }
+    }
+    /// Puts the world back how it was when the snapshot was taken
+    fn restore_overlay(&mut self, inboxes: Inboxes, name: &str) -> String {
+        let (path, snapshot) = match self.snapshot_dir(name) {
+            Ok(dirs) => dirs,
+            Err(e) => return e,
+        };
+        if !snapshot.is_dir() {
+            return format!("there's no snapshot called {name}");
+        }
+        if let Err(e) = self.clear_overlay() {
+            return e;
+        }
+        let mut missed = 0;
+        for dimension in &self.dimensions {
+            let (from, to) = (region_dir(&snapshot, &dimension.name), region_dir(&path, &dimension.name));
+            if from.exists() {
+                missed += std::fs::create_dir_all(&to).map_or(1, |()| copy_missing(&from, &to));
+            }
+        }
+        self.reload_chunks(inboxes);
+        log::info!("restored the {name} snapshot");
+        match missed {
+            0 => format!("restored the {name} snapshot"),
+            _ => format!("restored the {name} snapshot, but {missed} files couldn't be copied (see the log)"),
+        }
+    }
+    /// Writes every change into the template, so they're part of it from now on
+    fn merge_overlay(&mut self) -> String {
+        let template = match &self.level.template {
+            Some(template) => template.clone(),
+            None => return "there's no template world to merge into".to_owned(),
+        };
+        let mut merged = 0;
+        match self.level.path() {
+            Some(path) => {
+                if !self.save_chunks() {
+                    return format!("couldn't save everything first: {}", self.level.storage.last_error());
+                }
+                for dimension in &self.dimensions {
+                    let (from, to) = (region_dir(&path, &dimension.name), region_dir(&template, &dimension.name));
+                    let copied = saved_chunks(&from).and_then(|chunks| {
+                        for &chunk in &chunks {
+                            copy_chunk(&from, &to, chunk)?;
+                        }
+                        Ok(chunks.len())
+                    });
+                    match copied {
+                        Ok(chunks) => merged += chunks,
+                        Err(e) => return format!("couldn't merge {} into the template: {e}", dimension.name),
+                    }
+                }
+                if let Err(e) = self.clear_overlay() {
+                    return format!("merged {merged} chunks into the template, but {e}");
+                }
+            }
+            None => {
+                for (d, dimension) in self.dimensions.iter_mut().enumerate() {
+                    for (&pos, &idx) in &dimension.index {
+                        let chunk = &mut dimension.chunks[idx as usize];
+                        if !chunk.dirty {
+                            continue;
+                        }
+                        let items = self.entities.saved_items(d, pos);
+                        if let Err(e) = self.level.save_template_chunk(&dimension.name, pos, &chunk.content, &chunk.heightmaps, &items) {
+                            return format!("couldn't merge chunk {pos:?} into the template: {e}");
+                        }
+                        chunk.dirty = false;
+                        merged += 1;
+                    }
+                }
+            }
+        }
+        log::info!("merged {merged} chunks into the template");
+        format!("merged {merged} chunks into the template")
+    }
+    /// Reads every loaded chunk in again, and sends them to whoever can see them
+    fn reload_chunks(&mut self, inboxes: Inboxes) {
+        // anything that was dropped goes with the changes
+        let items: Vec<_> = self.entities.iter().filter(|e| matches!(e.kind, Kind::Item(_))).map(|e| e.eid).collect();
+        for eid in items {
+            self.entities.remove(eid);
+        }
+        for d in 0..self.dimensions.len() {
+            let loaded: Vec<_> = self.dimensions[d].index.keys().copied().collect();
+            for pos in loaded {
+                let blocks = &self.dimensions[d];
+                let (content, heightmaps, items) = match self.level.load_chunk_or_log(&blocks.name, pos, blocks.min_y(), blocks.sections()) {
+                    Some(saved) => saved,
+                    None => {
+                        let content = blocks.generator.generate(pos, blocks.min_y(), blocks.sections());
+                        let heightmaps = Heightmaps::compute(&content);
+                        (content, heightmaps, vec![])
+                    }
+                };
+                self.dimensions[d].replace_chunk(pos, content, heightmaps);
+                self.load_items(d, items);
+            }
+            // loads that were already under way might have read the old overlay
+            let loading: Vec<_> = self.dimensions[d].loading.keys().copied().collect();
+            for pos in loading {
+                let old = self.dimensions[d].loading.remove(&pos).unwrap();
+                old.cancel();
+                let loading = self.loader.load(d, &self.dimensions[d], pos, old.waiting);
+                self.dimensions[d].loading.insert(pos, loading);
+            }
+        }
+        inboxes.retain(|pid, mut inbox| {
+            let dimension = &self.dimensions[self.players[pid].dimension];
+            for (&pos, &idx) in &dimension.index {
+                if dimension.chunks[idx as usize].visible_to & 1 << pid != 0 {
+                    inbox.submit(Response::LoadChunk(pos.0, pos.1));
+                }
+            }
+            true
+        });
+        self.blocks_changed(u32::MAX, movement::Changed::Everything);
+    }
+}
@@ -0,0 +1,73 @@
+//! The day/night cycle. The time's kept in `level.dat`, and clients are told it every second
+//! so their sky keeps up (they move it along themselves in between).
+use crate::prelude::*;
+use super::World;
+
+const DAY: i64 = 24000;
+
+impl World {
+    pub(super) fn tick_time(&mut self, inboxes: Inboxes) {
+        self.data.time += 1;
+        if self.data.rule_enabled("doDaylightCycle") {
+            self.data.day_time += 1;
+        }
+        if self.tick.is_multiple_of(20) {
+            self.send_time(inboxes);
+        }
+    }
+    pub(super) fn time_packet(&self) -> Response {
+        // a negative time of day stops the client moving the sun itself
+        let day_time = match self.data.rule_enabled("doDaylightCycle") {
+            true => self.data.day_time,
+            false => -self.data.day_time.max(1),
+        };
+        Response::UpdateTime(self.data.time, day_time)
+    }
+    fn send_time(&self, inboxes: Inboxes) {
+        inboxes.retain(|_, mut inbox| {
+            inbox.submit(self.time_packet());
+            true
+        });
+    }
+    /// `time set|add|query`, like vanilla's
+    pub(super) fn time_command(&mut self, inboxes: Inboxes, args: &str) -> String {
+        let usage = "usage: time set <time>|add <time>|query daytime|gametime|day";
+        let time = match args.split_whitespace().collect::<Vec<_>>()[..] {
+            ["set", time] => match parse_time(time) {
+                Some(time) => time,
+                None => return format!("{time:?} isn't a time"),
+            },
+            ["add", time] => match parse_time(time).and_then(|time| self.data.day_time.checked_add(time)) {
+                Some(time) => time,
+                None => return format!("{time:?} isn't a time"),
+            },
+            ["query", "daytime"] => return format!("the time is {}", self.data.day_time.rem_euclid(DAY)),
+            ["query", "gametime"] => return format!("the world is {} ticks old", self.data.time),
+            ["query", "day"] => return format!("it's day {}", self.data.day_time.div_euclid(DAY)),
+            _ => return usage.to_owned(),
+        };
+        self.data.day_time = time;
+        self.send_time(inboxes);
+        log::info!("set the time to {time}");
+        format!("set the time to {time}")
+    }
+}
+
+/// Ticks, or a number of days (`1d`) or seconds (`30s`), or one of the names vanilla knows.
+/// Like vanilla it has to fit in an int, so adding it can't run the clock over
+fn parse_time(time: &str) -> Option<i64> {
+    let ticks = match time {
+        "day" => 1000.0,
+        "noon" => 6000.0,
+        "night" => 13000.0,
+        "midnight" => 18000.0,
+        _ if time.ends_with('d') => time[..time.len() - 1].parse::<f64>().ok()? * DAY as f64,
+        _ if time.ends_with('s') => time[..time.len() - 1].parse::<f64>().ok()? * 20.0,
+        _ => time.strip_suffix('t').unwrap_or(time).parse::<f64>().ok()?,
+    };
+    let ticks = ticks.round();
+    match (0.0..=i32::MAX as f64).contains(&ticks) {
+        true => Some(ticks as i64),
+        false => None,
+    }
+}
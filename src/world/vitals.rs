@@ -0,0 +1,596 @@
+//! Health, hunger and breath, the things that wear them down, and dying and coming back.
+//!
+//! Hunger works like vanilla's: what they do adds up as exhaustion, which eats into their
+//! saturation and then their food. Full enough, they heal. Empty, they starve.
+use crate::prelude::*;
+use super::entity::{self, Entity, Kind};
+use super::{items, World, HOTBAR, OFFHAND, OVERWORLD};
+
+pub(super) const MAX_HEALTH: f32 = 20.0;
+const MAX_FOOD: i32 = 20;
+const STARTING_SATURATION: f32 = 5.0;
+/// Ticks of breath
+const MAX_AIR: i32 = 300;
+/// Each time exhaustion gets this high, it costs a point of saturation, or of food once that's gone
+const EXHAUSTION_PER_FOOD: f32 = 4.0;
+/// After being hurt, how long until something else can do its full damage
+const INVULNERABLE_TICKS: u32 = 10;
+/// How far below the bottom of the world the void starts
+const VOID_DEPTH: f64 = 64.0;
+/// How far they can fall without it hurting
+const SAFE_FALL: f64 = 3.0;
+const EYE_HEIGHT: f64 = 1.62;
+
+/// What eating something does for them
+struct Food {
+    nutrition: i32,
+    /// Saturation per point of nutrition, halved the way vanilla keeps it
+    saturation: f32,
+    /// Whether it can be eaten on a full stomach
+    always: bool,
+    ticks: u32,
+    /// What's left in their hand once it's gone
+    leaves: Option<&'static str>,
+}
+/// Vanilla's foods, minus the effects some of them come with
+fn food(item: Item) -> Option<Food> {
+    let name = &item.name()[10..];
+    let (nutrition, saturation) = match name {
+        "apple" | "chorus_fruit" => (4, 0.3),
+        "golden_apple" | "enchanted_golden_apple" => (4, 1.2),
+        "baked_potato" | "bread" | "cooked_cod" | "cooked_rabbit" => (5, 0.6),
+        "beef" | "porkchop" | "rabbit" => (3, 0.3),
+        "beetroot" => (1, 0.6),
+        "carrot" => (3, 0.6),
+        "chicken" | "mutton" | "melon_slice" => (2, 0.3),
+        "cod" | "salmon" | "cookie" | "sweet_berries" | "glow_berries" => (2, 0.1),
+        "cooked_beef" | "cooked_porkchop" => (8, 0.8),
+        "cooked_chicken" | "beetroot_soup" | "mushroom_stew" | "suspicious_stew" => (6, 0.6),
+        "cooked_mutton" | "cooked_salmon" => (6, 0.8),
+        "dried_kelp" | "potato" => (1, 0.3),
+        "golden_carrot" => (6, 1.2),
+        "honey_bottle" => (6, 0.1),
+        "pumpkin_pie" => (8, 0.3),
+        "rabbit_stew" => (10, 0.6),
+        "tropical_fish" | "pufferfish" => (1, 0.1),
+        "rotten_flesh" => (4, 0.1),
+        "spider_eye" => (2, 0.8),
+        "poisonous_potato" => (2, 0.3),
+        _ => return None,
+    };
+    Some(Food {
+        nutrition,
+        saturation,
+        always: matches!(name, "chorus_fruit" | "golden_apple" | "enchanted_golden_apple" | "suspicious_stew" | "honey_bottle"),
+        ticks: match name {
+            "dried_kelp" => 16,
+            "honey_bottle" => 40,
+            _ => 32,
+        },
+        leaves: match name {
+            "beetroot_soup" | "mushroom_stew" | "suspicious_stew" | "rabbit_stew" => Some("minecraft:bowl"),
+            "honey_bottle" => Some("minecraft:glass_bottle"),
+            _ => None,
+        },
+    })
+}
+
+/// What hurt someone, for when it kills them
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Cause {
+    Attack(usize),
+    Fall(f64),
+    Void,
+    Drowning,
+    Suffocation,
+    Starving,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Vitals {
+    pub health: f32,
+    food: i32,
+    saturation: f32,
+    exhaustion: f32,
+    air: i32,
+    fall_distance: f64,
+    /// When they were last hurt, and by how much
+    last_hurt: Option<(u32, f32)>,
+    /// Ticks since hunger last healed or hurt them
+    food_timer: u32,
+    /// The slot they're eating out of, and the tick they'll have finished
+    eating: Option<(usize, u32)>,
+    /// What their client was last told, so it's only told again when it changes
+    sent: Option<(f32, i32, f32)>,
+    /// The bed they respawn at, if they've got one, and where they last died
+    pub bed: Option<(usize, V3<i32>)>,
+    pub death: Option<(usize, V3<i32>)>,
+}
+impl Vitals {
+    pub fn new() -> Self {
+        Self {
+            health: MAX_HEALTH,
+            food: MAX_FOOD,
+            saturation: STARTING_SATURATION,
+            exhaustion: 0.0,
+            air: MAX_AIR,
+            fall_distance: 0.0,
+            last_hurt: None,
+            food_timer: 0,
+            eating: None,
+            sent: None,
+            bed: None,
+            death: None,
+        }
+    }
+    pub fn dead(&self) -> bool {
+        self.health <= 0.0
+    }
+    pub fn exhaust(&mut self, amount: f32) {
+        self.exhaustion = (self.exhaustion + amount).min(40.0);
+    }
+    fn heal(&mut self, amount: f32) {
+        self.health = (self.health + amount).min(MAX_HEALTH);
+    }
+    /// Anywhere they'd been falling from doesn't count any more, like after a teleport
+    pub fn stop_falling(&mut self) {
+        self.fall_distance = 0.0;
+    }
+}
+
+/// The parts of `playerdata` about staying alive, in vanilla's names
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct SavedVitals {
+    #[serde(rename = "Health", default = "max_health")]
+    health: f32,
+    #[serde(rename = "foodLevel", default = "max_food")]
+    food: i32,
+    #[serde(rename = "foodSaturationLevel", default = "starting_saturation")]
+    saturation: f32,
+    #[serde(rename = "foodExhaustionLevel", default)]
+    exhaustion: f32,
+    #[serde(rename = "Air", default = "max_air")]
+    air: i16,
+    #[serde(rename = "FallDistance", default)]
+    fall_distance: f32,
+    #[serde(rename = "SpawnX", skip_serializing_if = "Option::is_none", default)]
+    spawn_x: Option<i32>,
+    #[serde(rename = "SpawnY", skip_serializing_if = "Option::is_none", default)]
+    spawn_y: Option<i32>,
+    #[serde(rename = "SpawnZ", skip_serializing_if = "Option::is_none", default)]
+    spawn_z: Option<i32>,
+    #[serde(rename = "SpawnDimension", skip_serializing_if = "Option::is_none", default)]
+    spawn_dimension: Option<String>,
+    #[serde(rename = "LastDeathLocation", skip_serializing_if = "Option::is_none", default)]
+    death: Option<SavedLocation>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SavedLocation {
+    dimension: String,
+    pos: fastnbt::IntArray,
+}
+fn max_health() -> f32 {
+    MAX_HEALTH
+}
+fn max_food() -> i32 {
+    MAX_FOOD
+}
+fn starting_saturation() -> f32 {
+    STARTING_SATURATION
+}
+fn max_air() -> i16 {
+    MAX_AIR as i16
+}
+
+impl World {
+    pub(super) fn save_vitals(&self, vitals: &Vitals) -> SavedVitals {
+        let bed = vitals.bed.map(|(dimension, pos)| (self.dimensions[dimension].name.clone(), pos));
+        SavedVitals {
+            health: vitals.health,
+            food: vitals.food,
+            saturation: vitals.saturation,
+            exhaustion: vitals.exhaustion,
+            air: vitals.air as i16,
+            fall_distance: vitals.fall_distance as f32,
+            spawn_x: bed.as_ref().map(|(_, pos)| pos.x),
+            spawn_y: bed.as_ref().map(|(_, pos)| pos.y),
+            spawn_z: bed.as_ref().map(|(_, pos)| pos.z),
+            spawn_dimension: bed.map(|(dimension, _)| dimension),
+            death: vitals.death.map(|(dimension, pos)| SavedLocation {
+                dimension: self.dimensions[dimension].name.clone(),
+                pos: fastnbt::IntArray::new(vec![pos.x, pos.y, pos.z]),
+            }),
+        }
+    }
+    pub(super) fn load_vitals(&self, saved: SavedVitals) -> Vitals {
+        let bed = match (saved.spawn_x, saved.spawn_y, saved.spawn_z) {
+            (Some(x), Some(y), Some(z)) => {
+                let dimension = saved.spawn_dimension.as_deref().map_or(Some(OVERWORLD), |name| self.find_dimension(name));
+                dimension.map(|dimension| (dimension, V3(x, y, z)))
+            }
+            _ => None,
+        };
+        let death = saved.death.and_then(|SavedLocation { dimension, pos }| match pos[..] {
+            [x, y, z] => Some((self.find_dimension(&dimension)?, V3(x, y, z))),
+            _ => None,
+        });
+        Vitals {
+            health: saved.health.clamp(0.0, MAX_HEALTH),
+            food: saved.food.clamp(0, MAX_FOOD),
+            saturation: saved.saturation,
+            exhaustion: saved.exhaustion,
+            air: saved.air as i32,
+            fall_distance: saved.fall_distance as f64,
+            bed,
+            death,
+            ..Vitals::new()
+        }
+    }
+    /// Takes `amount` off their health, killing them if it's all gone. Returns whether it was
+    /// a fresh hit, rather than one landing while they're still recovering from the last
+    pub(super) fn hurt(&mut self, pid: usize, mut inboxes: Inboxes, amount: f32, cause: Cause) -> bool {
+        let tick = self.tick;
+        let player = &mut self.players[pid];
+        let vitals = &mut player.vitals;
+        // only the void gets through creative
+        if (player.gamemode == GameMode::Creative && !matches!(cause, Cause::Void)) || vitals.dead() {
+            return false;
+        }
+        let (fresh, taken) = match vitals.last_hurt {
+            // only a harder hit counts, and only by how much harder it is
+            Some((when, last)) if tick - when < INVULNERABLE_TICKS => {
+                if amount <= last {
+                    return false;
+                }
+                vitals.last_hurt = Some((when, amount));
+                (false, amount - last)
+            }
+            _ => {
+                vitals.last_hurt = Some((tick, amount));
+                (true, amount)
+            }
+        };
+        vitals.health = (vitals.health - taken).max(0.0);
+        if let Cause::Attack(_) = cause {
+            vitals.exhaust(0.1);
+        }
+        let (eid, position, dead) = (player.eid, player.position, vitals.dead());
+        let viewers = self.dimension(pid).viewers(entity::chunk_of(position)) | 1 << pid;
+        if fresh {
+            inboxes.reborrow().retain(|i, mut inbox| {
+                if viewers & 1 << i != 0 {
+                    inbox.submit(Response::EntityEvent(eid, 2));
+                }
+                true
+            });
+        }
+        if dead {
+            self.die(pid, inboxes, cause);
+        }
+        fresh
+    }
+    fn die(&mut self, pid: usize, mut inboxes: Inboxes, cause: Cause) {
+        let player = &mut self.players[pid];
+        let (name, eid, dimension, (x, y, z)) = (player.name, player.eid, player.dimension, player.position);
+        player.vitals.death = Some((dimension, V3(x.floor() as i32, y.floor() as i32, z.floor() as i32)));
+        player.digging = None;
+        player.portal = None;
+        player.vitals.eating = None;
+        // they're gone until they respawn
+        self.entities.remove(eid);
+        if !self.data.rule_enabled("keepInventory") {
+            let stacks: Vec<_> = self.players[pid].inventory.iter_mut().filter_map(Option::take).collect();
+            for stack in stacks {
+                self.drop_item(dimension, (x, y + EYE_HEIGHT - 0.3, z), stack);
+            }
+        }
+        let name = name.as_str();
+        let (message, killer) = match cause {
+            Cause::Attack(killer) => (format!("{name} was slain by {}", self.players[killer].name.as_str()), self.players[killer].eid),
+            Cause::Fall(distance) if distance > 5.0 => (format!("{name} fell from a high place"), -1),
+            Cause::Fall(_) => (format!("{name} hit the ground too hard"), -1),
+            Cause::Void => (format!("{name} fell out of the world"), -1),
+            Cause::Drowning => (format!("{name} drowned"), -1),
+            Cause::Suffocation => (format!("{name} suffocated in a wall"), -1),
+            Cause::Starving => (format!("{name} starved to death"), -1),
+        };
+        log::info!("{message}");
+        let show = self.data.rule_enabled("showDeathMessages");
+        inboxes.reborrow().retain(|i, mut inbox| {
+            if show {
+                inbox.submit(Response::Chat(message.clone()));
+            }
+            if i == pid {
+                inbox.submit(Response::SetHealth(0.0, 0, 0.0));
+                // the death screen, which says where they died
+                let shown = if show { format!("{message} at {} {} {}", x.floor(), y.floor(), z.floor()) } else { String::new() };
+                inbox.submit(Response::CombatDeath(eid, killer, shown));
+            }
+            true
+        });
+        self.players[pid].vitals.sent = Some((0.0, 0, 0.0));
+    }
+    /// Brings them back from the dead, at their bed if it's still there or at the world's spawn
+    pub(crate) fn respawn(&mut self, pid: usize, mut inboxes: Inboxes) {
+        if !self.players[pid].vitals.dead() {
+            return;
+        }
+        let mut inbox = inboxes.get(pid).unwrap();
+        let (dimension, position) = match self.find_bed(pid) {
+            Some(found) => found,
+            None => {
+                if self.players[pid].vitals.bed.take().is_some() {
+                    inbox.submit(Response::Chat("You have no home bed, or it was obstructed".to_owned()));
+                }
+                (OVERWORLD, self.spawn_point())
+            }
+        };
+        let player = &mut self.players[pid];
+        player.vitals = Vitals { bed: player.vitals.bed, death: player.vitals.death, ..Vitals::new() };
+        self.entities.add(Entity::new(player.eid, dimension, position, Kind::Player { pid, name: player.name }));
+        self.change_dimension(pid, inbox, dimension, position);
+    }
+    /// Somewhere to stand next to their bed, if it's still there
+    fn find_bed(&mut self, pid: usize) -> Option<(usize, (f64, f64, f64))> {
+        let (dimension, bed) = self.players[pid].vitals.bed?;
+        self.load_now(dimension, (bed.x.div_euclid(16), bed.z.div_euclid(16)));
+        let blocks = &self.dimensions[dimension];
+        if !blocks.block_at(bed).is_some_and(is_bed) {
+            return None;
+        }
+        let clear = |pos: V3<i32>| blocks.block_at(pos).is_none_or(|b| b.collision_boxes().is_empty());
+        let solid = |pos: V3<i32>| blocks.block_at(pos).is_some_and(|b| !b.collision_boxes().is_empty());
+        let beside = (-1..=1).flat_map(|x| (-1..=1).map(move |z| V3(bed.x + x, bed.y, bed.z + z)));
+        if let Some(spot) = beside.filter(|&pos| pos != bed).find(|&pos| clear(pos) && clear(V3(pos.x, pos.y + 1, pos.z)) && solid(V3(pos.x, pos.y - 1, pos.z))) {
+            return Some((dimension, (spot.x as f64 + 0.5, spot.y as f64, spot.z as f64 + 0.5)));
+        }
+        // on top of it, if there's room
+        let (above, higher) = (V3(bed.x, bed.y + 1, bed.z), V3(bed.x, bed.y + 2, bed.z));
+        (clear(above) && clear(higher)).then_some((dimension, (bed.x as f64 + 0.5, bed.y as f64 + 0.5625, bed.z as f64 + 0.5)))
+    }
+    /// Right clicking a bed makes it where they respawn. There's no sleeping yet
+    pub(super) fn use_bed(&mut self, pid: usize, mut inbox: Inbox, dimension: usize, pos: V3<i32>) {
+        let player = &mut self.players[pid];
+        if dimension != OVERWORLD {
+            inbox.submit(Response::Chat("You can't sleep here".to_owned()));
+            return;
+        }
+        if player.vitals.bed != Some((dimension, pos)) {
+            player.vitals.bed = Some((dimension, pos));
+            inbox.submit(Response::Chat("Respawn point set".to_owned()));
+        }
+    }
+    /// Keeps track of how far they've fallen, and tires them out, after a move that made sense
+    pub(super) fn moved(&mut self, pid: usize, inboxes: Inboxes, motion: (f64, f64, f64), was_on_ground: bool, on_ground: bool, held_up: bool) {
+        let player = &mut self.players[pid];
+        let vitals = &mut player.vitals;
+        // jumping's the only way to go up exactly that fast
+        if was_on_ground && (0.41..0.43).contains(&motion.1) {
+            vitals.exhaust(if player.sprinting { 0.2 } else { 0.05 });
+        }
+        if player.sprinting {
+            vitals.exhaust(0.1 * motion.0.hypot(motion.2) as f32);
+        }
+        if held_up || player.gamemode == GameMode::Creative {
+            vitals.fall_distance = 0.0;
+        } else if motion.1 < 0.0 && !on_ground {
+            vitals.fall_distance -= motion.1;
+        } else if on_ground {
+            let fallen = core::mem::take(&mut vitals.fall_distance) - motion.1.min(0.0);
+            let damage = (fallen - SAFE_FALL).ceil();
+            if damage > 0.0 && self.data.rule_enabled("fallDamage") {
+                self.hurt(pid, inboxes, damage as f32, Cause::Fall(fallen));
+            }
+        }
+    }
+    pub(super) fn tick_vitals(&mut self, mut inboxes: Inboxes) {
+        for pid in self.online().collect::<Vec<_>>() {
+            let player = &self.players[pid];
+            if player.lobby || player.vitals.dead() {
+                continue;
+            }
+            let blocks = self.dimension(pid);
+            let (x, y, z) = player.position;
+            let eyes = blocks.block_at(V3(x.floor() as i32, (y + EYE_HEIGHT).floor() as i32, z.floor() as i32));
+            let underwater = eyes.is_some_and(|b| b.kind() == BlockKind::Water || b.get() == Some(props::Waterlogged(true)));
+            let buried = eyes.is_some_and(|b| b.info().opacity == 15 && b.collision_boxes() == [[0.0, 0.0, 0.0, 1.0, 1.0, 1.0]]);
+            let exposed = player.gamemode == GameMode::Survival;
+            if y < blocks.min_y() as f64 - VOID_DEPTH {
+                self.hurt(pid, inboxes.reborrow(), 4.0, Cause::Void);
+            }
+            if buried && exposed {
+                self.hurt(pid, inboxes.reborrow(), 1.0, Cause::Suffocation);
+            }
+            let vitals = &mut self.players[pid].vitals;
+            if !underwater || !exposed {
+                vitals.air = (vitals.air + 4).min(MAX_AIR);
+            } else {
+                vitals.air -= 1;
+                if vitals.air <= -20 {
+                    vitals.air = 0;
+                    if self.data.rule_enabled("drowningDamage") {
+                        self.hurt(pid, inboxes.reborrow(), 2.0, Cause::Drowning);
+                    }
+                }
+            }
+            self.tick_hunger(pid, inboxes.reborrow());
+            self.tick_eating(pid, inboxes.reborrow());
+        }
+        inboxes.retain(|pid, mut inbox| {
+            let vitals = &mut self.players[pid].vitals;
+            let now = (vitals.health, vitals.food, vitals.saturation);
+            if vitals.sent != Some(now) && !vitals.dead() {
+                vitals.sent = Some(now);
+                inbox.submit(Response::SetHealth(now.0, now.1, now.2));
+            }
+            true
+        });
+    }
+    /// Right clicking with something, not at a block. Only food does anything with it yet
+    pub(crate) fn use_item(&mut self, pid: usize, hand: Hand) {
+        let tick = self.tick;
+        let player = &mut self.players[pid];
+        let slot = match hand {
+            Hand::Main => HOTBAR + player.selected_item as usize,
+            Hand::Secondary => OFFHAND,
+        };
+        let food = match player.inventory[slot].as_ref().and_then(|s| food(s.item)) {
+            Some(food) => food,
+            None => return,
+        };
+        // their client won't have started either, if they're full
+        let hungry = player.vitals.food < MAX_FOOD || food.always || player.gamemode == GameMode::Creative;
+        if hungry && !player.vitals.dead() {
+            player.vitals.eating = Some((slot, tick + food.ticks));
+        }
+    }
+    /// They let go of right click, or switched away from what they were using
+    pub(crate) fn stop_using_item(&mut self, pid: usize) {
+        self.players[pid].vitals.eating = None;
+    }
+    fn tick_eating(&mut self, pid: usize, mut inboxes: Inboxes) {
+        let player = &mut self.players[pid];
+        let slot = match player.vitals.eating {
+            Some((slot, done)) if done <= self.tick => slot,
+            _ => return,
+        };
+        player.vitals.eating = None;
+        // whatever they started on might not be there any more
+        let stack = match &mut player.inventory[slot] {
+            Some(stack) => stack,
+            None => return,
+        };
+        let food = match food(stack.item) {
+            Some(food) => food,
+            None => return,
+        };
+        let vitals = &mut player.vitals;
+        vitals.food = (vitals.food + food.nutrition).min(MAX_FOOD);
+        vitals.saturation = (vitals.saturation + food.nutrition as f32 * food.saturation * 2.0).min(vitals.food as f32);
+        let mut inbox = inboxes.get(pid).unwrap();
+        // tells their client they're done, so it stops the animation
+        inbox.submit(Response::EntityEvent(player.eid, 9));
+        if player.gamemode == GameMode::Creative {
+            return;
+        }
+        stack.count -= 1;
+        let mut leftover = food.leaves.and_then(Item::from_name).map(|item| ItemStack::new(item, 1));
+        if stack.count == 0 {
+            player.inventory[slot] = leftover.take();
+        }
+        inbox.submit(Response::SetInventorySlot(slot as u16, player.inventory[slot].clone(), 0));
+        let mut leftover = match leftover {
+            Some(leftover) => leftover,
+            None => return,
+        };
+        for slot in items::give(&mut player.inventory, &mut leftover) {
+            inbox.submit(Response::SetInventorySlot(slot as u16, player.inventory[slot].clone(), 0));
+        }
+        if leftover.count > 0 {
+            self.throw_item(pid, leftover);
+        }
+    }
+    fn tick_hunger(&mut self, pid: usize, inboxes: Inboxes) {
+        let (difficulty, regenerates) = (self.data.difficulty, self.data.rule_enabled("naturalRegeneration"));
+        let vitals = &mut self.players[pid].vitals;
+        if vitals.dead() {
+            return;
+        }
+        if vitals.exhaustion > EXHAUSTION_PER_FOOD {
+            vitals.exhaustion -= EXHAUSTION_PER_FOOD;
+            if vitals.saturation > 0.0 {
+                vitals.saturation = (vitals.saturation - 1.0).max(0.0);
+            } else if difficulty > 0 {
+                vitals.food = (vitals.food - 1).max(0);
+            }
+        }
+        // nobody goes hungry on peaceful
+        if difficulty == 0 {
+            if regenerates && self.tick.is_multiple_of(20) {
+                vitals.heal(1.0);
+            }
+            if self.tick.is_multiple_of(10) {
+                vitals.food = (vitals.food + 1).min(MAX_FOOD);
+            }
+        }
+        let hurting = vitals.health < MAX_HEALTH;
+        vitals.food_timer += 1;
+        if regenerates && hurting && vitals.saturation > 0.0 && vitals.food >= MAX_FOOD {
+            if vitals.food_timer >= 10 {
+                let amount = vitals.saturation.min(6.0);
+                vitals.heal(amount / 6.0);
+                vitals.exhaust(amount);
+                vitals.food_timer = 0;
+            }
+        } else if regenerates && hurting && vitals.food >= 18 {
+            if vitals.food_timer >= 80 {
+                vitals.heal(1.0);
+                vitals.exhaust(6.0);
+                vitals.food_timer = 0;
+            }
+        } else if vitals.food <= 0 {
+            if vitals.food_timer >= 80 {
+                vitals.food_timer = 0;
+                // easy stops at half their health, normal at half a heart, and hard doesn't stop
+                let starves = match difficulty {
+                    1 => vitals.health > 10.0,
+                    2 => vitals.health > 1.0,
+                    _ => true,
+                };
+                if starves {
+                    self.hurt(pid, inboxes, 1.0, Cause::Starving);
+                }
+            }
+        } else {
+            vitals.food_timer = 0;
+        }
+    }
+}
+pub(super) fn is_bed(block: Block) -> bool {
+    block.info().name.ends_with("_bed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Level;
+
+    #[test]
+    fn dead_players_leave_their_things() {
+        let mut network = crate::network::Network::local();
+        let mut world = World::new(Level::empty(), network.notifier());
+        let (pid, _conn) = network.join(&mut world, "corpse");
+        let stack = ItemStack { item: Item::new(1).unwrap(), count: 5, tag: None };
+        world.players[pid].inventory[HOTBAR] = Some(stack);
+        world.tick_until(2, network.inboxes());
+        world.hurt(pid, network.inboxes(), f32::MAX, Cause::Void);
+        assert!(world.players[pid].vitals.dead());
+        // long enough for anyone standing there to have picked them up
+        world.tick_until(100, network.inboxes());
+        assert!(world.players[pid].inventory.iter().all(Option::is_none));
+        let position = world.players[pid].position;
+        let dropped = world.entities.near(OVERWORLD, position, 2.5).filter(|e| matches!(e.kind, Kind::Item(_))).count();
+        assert_eq!(dropped, 1);
+    }
+
+    #[test]
+    fn eating_fills_them_up() {
+        let mut network = crate::network::Network::local();
+        let mut world = World::new(Level::empty(), network.notifier());
+        world.data.difficulty = 2;
+        let (pid, _conn) = network.join(&mut world, "hungry");
+        let stew = ItemStack::new(Item::from_name("mushroom_stew").unwrap(), 1);
+        world.players[pid].inventory[HOTBAR] = Some(stew);
+        world.players[pid].vitals.food = 10;
+        world.players[pid].vitals.saturation = 0.0;
+        world.use_item(pid, Hand::Main);
+        world.tick_until(20, network.inboxes());
+        assert_eq!(world.players[pid].vitals.food, 10);
+        world.tick_until(40, network.inboxes());
+        let vitals = &world.players[pid].vitals;
+        assert_eq!(vitals.food, 16);
+        assert!((vitals.saturation - 7.2).abs() < 1e-4);
+        let left = world.players[pid].inventory[HOTBAR].as_ref().map(|s| s.item.name());
+        assert_eq!(left, Some("minecraft:bowl"));
+    }
+}
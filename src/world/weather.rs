@@ -0,0 +1,142 @@
+//! Rain and thunder, on the same timers as vanilla. They're saved in `level.dat`, and only
+//! fall in dimensions with a sky.
+//!
+//! Clients are told when it starts or stops raining, and then how heavy the rain and thunder
+//! are as they fade in and out.
+use crate::prelude::*;
+use super::generator::splitmix;
+use super::{LevelData, World};
+
+const BEGIN_RAINING: u8 = 1;
+const END_RAINING: u8 = 2;
+const RAIN_LEVEL: u8 = 7;
+const THUNDER_LEVEL: u8 = 8;
+/// How long `/weather` lasts if it isn't told
+const DEFAULT_DURATION: i32 = 5 * 60 * 20;
+
+pub(super) struct Weather {
+    rain_level: f32,
+    thunder_level: f32,
+    rng: u64,
+}
+impl Weather {
+    pub fn new(data: &LevelData) -> Self {
+        let now = time::SystemTime::now().duration_since(time::UNIX_EPOCH).map_or(0, |since| since.as_nanos() as u64);
+        Self {
+            rain_level: if data.raining { 1.0 } else { 0.0 },
+            thunder_level: if data.raining && data.thundering { 1.0 } else { 0.0 },
+            rng: now ^ data.seed() as u64,
+        }
+    }
+    /// Like vanilla, it only counts once the rain's heavy enough to see
+    fn raining(&self) -> bool {
+        self.rain_level > 0.2
+    }
+    fn between(&mut self, min: i32, max: i32) -> i32 {
+        min + (splitmix(&mut self.rng) % (max - min + 1) as u64) as i32
+    }
+}
+
+impl World {
+    pub(super) fn tick_weather(&mut self, inboxes: Inboxes) {
+        let was_raining = self.weather.raining();
+        let (old_rain, old_thunder) = (self.weather.rain_level, self.weather.thunder_level);
+        if self.data.rule_enabled("doWeatherCycle") {
+            self.advance_weather();
+        }
+        let step = |level: f32, on: bool| (level + if on { 0.01 } else { -0.01 }).clamp(0.0, 1.0);
+        self.weather.thunder_level = step(self.weather.thunder_level, self.data.thundering);
+        self.weather.rain_level = step(self.weather.rain_level, self.data.raining);
+        let started = was_raining != self.weather.raining();
+        let mut events = vec![];
+        if started {
+            events.push((if was_raining { END_RAINING } else { BEGIN_RAINING }, 0.0));
+        }
+        if started || old_rain != self.weather.rain_level {
+            events.push((RAIN_LEVEL, self.weather.rain_level));
+        }
+        if started || old_thunder != self.weather.thunder_level {
+            events.push((THUNDER_LEVEL, self.weather.thunder_level));
+        }
+        if events.is_empty() {
+            return;
+        }
+        inboxes.retain(|pid, mut inbox| {
+            if self.dimensions[self.players[pid].dimension].has_skylight() {
+                for &(event, value) in &events {
+                    inbox.submit(Response::GameEvent(event, value));
+                }
+            }
+            true
+        });
+    }
+    /// Counts down to the next time the rain or thunder starts or stops
+    fn advance_weather(&mut self) {
+        let data = &mut self.data;
+        if data.clear_weather_time > 0 {
+            data.clear_weather_time -= 1;
+            data.thunder_time = if data.thundering { 0 } else { 1 };
+            data.rain_time = if data.raining { 0 } else { 1 };
+            data.thundering = false;
+            data.raining = false;
+            return;
+        }
+        if data.thunder_time > 0 {
+            data.thunder_time -= 1;
+            if data.thunder_time == 0 {
+                data.thundering = !data.thundering;
+            }
+        } else if data.thundering {
+            data.thunder_time = self.weather.between(3600, 15600);
+        } else {
+            data.thunder_time = self.weather.between(12000, 180000);
+        }
+        if data.rain_time > 0 {
+            data.rain_time -= 1;
+            if data.rain_time == 0 {
+                data.raining = !data.raining;
+            }
+        } else if data.raining {
+            data.rain_time = self.weather.between(12000, 24000);
+        } else {
+            data.rain_time = self.weather.between(12000, 180000);
+        }
+    }
+    /// What someone arriving in a dimension needs to see the weather that's already there
+    pub(super) fn send_weather(&self, pid: usize, mut inbox: Inbox) {
+        if !self.weather.raining() || !self.dimensions[self.players[pid].dimension].has_skylight() {
+            return;
+        }
+        inbox.submit(Response::GameEvent(BEGIN_RAINING, 0.0));
+        inbox.submit(Response::GameEvent(RAIN_LEVEL, self.weather.rain_level));
+        inbox.submit(Response::GameEvent(THUNDER_LEVEL, self.weather.thunder_level));
+    }
+    /// `weather clear|rain|thunder [seconds]`, like vanilla's. On its own it says what the weather's doing
+    pub(super) fn weather_command(&mut self, args: &str) -> String {
+        let args: Vec<_> = args.split_whitespace().collect();
+        let duration = match args.get(1).map(|secs| secs.parse::<i32>()) {
+            None => DEFAULT_DURATION,
+            Some(Ok(secs)) if (1..=1_000_000).contains(&secs) => secs * 20,
+            Some(_) => return format!("{:?} isn't a number of seconds (up to 1000000)", args[1]),
+        };
+        let data = &mut self.data;
+        let (raining, thundering) = match args[..] {
+            [] => return match (data.raining, data.thundering) {
+                (true, true) => "it's thundering".to_owned(),
+                (true, false) => "it's raining".to_owned(),
+                _ => "it's clear".to_owned(),
+            },
+            ["clear", ..] => (false, false),
+            ["rain", ..] => (true, false),
+            ["thunder", ..] => (true, true),
+            _ => return "usage: weather [clear|rain|thunder [seconds]]".to_owned(),
+        };
+        data.clear_weather_time = if raining { 0 } else { duration };
+        data.rain_time = if raining { duration } else { 0 };
+        data.thunder_time = if raining { duration } else { 0 };
+        data.raining = raining;
+        data.thundering = thundering;
+        log::info!("set the weather to {} for {}s", args[0], duration / 20);
+        format!("set the weather to {} for {}s", args[0], duration / 20)
+    }
+}